@@ -0,0 +1,215 @@
+//! Answering authentication challenges
+//!
+//! `Credentials` answer the challenges of a 401 (Unauthorized) or 407
+//! (Proxy Authentication Required) response, with a `Digest` if one is
+//! offered, else with `Basic`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+
+use header::{Authorization, Basic, Challenge, Digest, DigestAlgorithm, Headers, ProxyAuthenticate,
+             ProxyAuthorization, WwwAuthenticate};
+use method::Method;
+use random;
+use status::StatusCode;
+use Url;
+use super::Response;
+
+/// How many nonces to keep counting, before starting over.
+const MAX_NONCES: usize = 64;
+
+/// A username and password to answer authentication challenges with.
+///
+/// Clones share the counts of the `Digest` nonces they have answered.
+#[derive(Clone)]
+pub struct Credentials {
+    username: String,
+    password: String,
+    nonces: Arc<Mutex<HashMap<String, u32>>>,
+}
+
+impl Credentials {
+    /// Create `Credentials` of a username and password.
+    pub fn new<U: Into<String>, P: Into<String>>(username: U, password: P) -> Credentials {
+        Credentials {
+            username: username.into(),
+            password: password.into(),
+            nonces: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// The username.
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Answer the challenges of a 401 or 407 response to a request of
+    /// `method` and `url`, by setting `Authorization` or
+    /// `ProxyAuthorization` in the `headers` to send the request again with.
+    ///
+    /// Returns whether the response could be answered.
+    pub fn answer(&self, res: &Response, method: &Method, url: &Url, headers: &mut Headers) -> bool {
+        match *res.status() {
+            StatusCode::Unauthorized => {
+                let challenges = match res.headers().get::<WwwAuthenticate>() {
+                    Some(challenges) => challenges,
+                    None => return false
+                };
+                match self.choose(challenges, method, url) {
+                    Some(Answer::Digest(digest)) => headers.set(Authorization(digest)),
+                    Some(Answer::Basic(basic)) => headers.set(Authorization(basic)),
+                    None => return false
+                }
+            },
+            StatusCode::ProxyAuthenticationRequired => {
+                let challenges = match res.headers().get::<ProxyAuthenticate>() {
+                    Some(challenges) => challenges,
+                    None => return false
+                };
+                match self.choose(challenges, method, url) {
+                    Some(Answer::Digest(digest)) => headers.set(ProxyAuthorization(digest)),
+                    Some(Answer::Basic(basic)) => headers.set(ProxyAuthorization(basic)),
+                    None => return false
+                }
+            },
+            _ => return false
+        }
+        true
+    }
+
+    /// Picks the strongest challenge that can be answered: `Digest` with
+    /// SHA-256, then with MD5, then `Basic`.
+    fn choose(&self, challenges: &[Challenge], method: &Method, url: &Url) -> Option<Answer> {
+        let mut uri = url.path().to_owned();
+        if let Some(query) = url.query() {
+            uri.push('?');
+            uri.push_str(query);
+        }
+        let mut best = None;
+        for challenge in challenges {
+            if challenge.is_scheme("Digest") {
+                // a trial answer, to learn if the challenge can be answered
+                // without counting a use of its nonce
+                let algorithm = match Digest::answer(challenge, &self.username, &self.password,
+                                                     method, &uri, 1, "") {
+                    Some(digest) => digest.algorithm,
+                    None => continue
+                };
+                best = Some(challenge);
+                if algorithm == Some(DigestAlgorithm::Sha256) {
+                    break;
+                }
+            } else if challenge.is_scheme("Basic") && best.is_none() {
+                best = Some(challenge);
+            }
+        }
+        match best {
+            Some(challenge) if challenge.is_scheme("Digest") => {
+                let nonce = challenge.param("nonce").expect("answered challenge has a nonce");
+                Digest::answer(challenge, &self.username, &self.password, method, &uri,
+                               self.count(nonce), &cnonce()).map(Answer::Digest)
+            },
+            Some(_) => Some(Answer::Basic(Basic {
+                username: self.username.clone(),
+                password: Some(self.password.clone()),
+            })),
+            None => None
+        }
+    }
+
+    /// Counts another use of the nonce, returning the count.
+    fn count(&self, nonce: &str) -> u32 {
+        let mut nonces = match self.nonces.lock() {
+            Ok(nonces) => nonces,
+            Err(poisoned) => poisoned.into_inner()
+        };
+        if nonces.len() >= MAX_NONCES && !nonces.contains_key(nonce) {
+            nonces.clear();
+        }
+        let count = nonces.entry(nonce.to_owned()).or_insert(0);
+        *count += 1;
+        *count
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .finish()
+    }
+}
+
+enum Answer {
+    Digest(Digest),
+    Basic(Basic),
+}
+
+/// A client nonce, which must not be predictable.
+fn cnonce() -> String {
+    random::hex(16)
+}
+
+#[cfg(test)]
+mod tests {
+    use header::{Authorization, Basic, Challenge, DigestAlgorithm};
+    use method::Method;
+    use Url;
+    use super::{Answer, Credentials, cnonce};
+
+    fn url() -> Url {
+        Url::parse("http://example.domain/dir/index.html?a=b").unwrap()
+    }
+
+    #[test]
+    fn test_credentials_choose_digest() {
+        let credentials = Credentials::new("Mufasa", "Circle of Life");
+        let challenges = vec![
+            Challenge::new("Basic").with_param("realm", "r"),
+            Challenge::new("Digest").with_param("realm", "r").with_param("nonce", "n").with_param("qop", "auth"),
+            Challenge::new("Digest").with_param("realm", "r").with_param("nonce", "n")
+                .with_param("algorithm", "SHA-256").with_param("qop", "auth"),
+        ];
+        let digest = match credentials.choose(&challenges[..2], &Method::Get, &url()) {
+            Some(Answer::Digest(digest)) => digest,
+            _ => panic!("expected a digest")
+        };
+        assert_eq!(digest.algorithm, None);
+        assert_eq!(digest.uri, "/dir/index.html?a=b");
+        assert_eq!(digest.nc, Some(1));
+        assert!(digest.verify("Circle of Life", &Method::Get));
+
+        let digest = match credentials.choose(&challenges, &Method::Get, &url()) {
+            Some(Answer::Digest(digest)) => digest,
+            _ => panic!("expected a digest")
+        };
+        assert_eq!(digest.algorithm, Some(DigestAlgorithm::Sha256));
+        assert_eq!(digest.nc, Some(2));
+    }
+
+    #[test]
+    fn test_credentials_choose_basic() {
+        let credentials = Credentials::new("Aladdin", "open sesame");
+        let challenges = vec![
+            Challenge::new("Digest").with_param("realm", "r").with_param("nonce", "n")
+                .with_param("algorithm", "SHA-512-256"),
+            Challenge::new("Basic").with_param("realm", "r"),
+        ];
+        match credentials.choose(&challenges, &Method::Get, &url()) {
+            Some(Answer::Basic(basic)) => assert_eq!(Authorization(basic), Authorization(Basic {
+                username: "Aladdin".to_owned(),
+                password: Some("open sesame".to_owned()),
+            })),
+            _ => panic!("expected basic")
+        }
+        assert!(credentials.choose(&[Challenge::new("Negotiate")], &Method::Get, &url()).is_none());
+    }
+
+    #[test]
+    fn test_cnonce() {
+        assert!(cnonce() != cnonce());
+        assert_eq!(cnonce().len(), 32);
+    }
+}
@@ -0,0 +1,519 @@
+//! A blocking Client
+//!
+//! The blocking `Client` drives the asynchronous `Client` on its event loop
+//! thread, and waits for the results on the calling thread. It suits scripts
+//! and tools making a few requests at a time, without implementing a
+//! `Handler`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::Read;
+//! use hyper::client::blocking::Client;
+//!
+//! let client = Client::new().unwrap();
+//! let mut res = client.get("http://example.domain".parse().unwrap()).send().unwrap();
+//! let mut body = String::new();
+//! res.read_to_string(&mut body).unwrap();
+//! ```
+
+use std::cmp;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use form::Form;
+use header::{Header, Headers};
+use http::{Control, Decoder, Encoder, Next};
+use method::Method;
+use net::Transport;
+use status::StatusCode;
+use version::HttpVersion;
+use Url;
+use super::{Config, Connect, Request};
+
+/// How many response chunks the event loop reads ahead of the caller.
+const MAX_BUFFERED: usize = 4;
+/// How many request body chunks the caller reads ahead of the event loop.
+const MAX_BODY_CHUNKS: usize = 2;
+const CHUNK_SIZE: usize = 8192;
+
+/// A Client that blocks the calling thread until each response is ready.
+///
+/// Dropping the `Client` closes its event loop.
+pub struct Client {
+    client: Option<super::Client<Blocking>>,
+}
+
+impl Client {
+    /// Create a new blocking Client with the default config.
+    pub fn new() -> ::Result<Client> {
+        Client::configured(super::Client::<Blocking>::configure())
+    }
+
+    /// Create a new blocking Client from a `Config`.
+    ///
+    /// The timeouts of the `Config` apply to every request.
+    pub fn configured<C>(config: Config<C>) -> ::Result<Client>
+    where C: Connect + Send + 'static {
+        config.build().map(|client| Client {
+            client: Some(client)
+        })
+    }
+
+    /// Build a `GET` request.
+    pub fn get(&self, url: Url) -> RequestBuilder {
+        self.request(Method::Get, url)
+    }
+
+    /// Build a `POST` request.
+    pub fn post(&self, url: Url) -> RequestBuilder {
+        self.request(Method::Post, url)
+    }
+
+    /// Build a request with any `Method`.
+    pub fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        RequestBuilder {
+            client: self,
+            method: method,
+            url: url,
+            headers: Headers::new(),
+            body: None,
+            timeout: None,
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.client.take().map(|client| client.close());
+    }
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Client")
+    }
+}
+
+/// A request to be sent with a blocking `Client`.
+pub struct RequestBuilder<'a> {
+    client: &'a Client,
+    method: Method,
+    url: Url,
+    headers: Headers,
+    body: Option<Box<Read + 'a>>,
+    timeout: Option<Duration>,
+}
+
+impl<'a> RequestBuilder<'a> {
+    /// Set a header on the request.
+    pub fn header<H: Header>(mut self, header: H) -> RequestBuilder<'a> {
+        self.headers.set(header);
+        self
+    }
+
+    /// Add all of `headers` to the request.
+    pub fn headers(mut self, headers: Headers) -> RequestBuilder<'a> {
+        self.headers.extend(headers.iter());
+        self
+    }
+
+    /// Send a urlencoded `Form` as the body, with its `ContentType` and
+    /// `ContentLength`.
+    pub fn form(mut self, form: &Form) -> RequestBuilder<'a> {
+        let body = form.to_body(&mut self.headers);
+        self.body(io::Cursor::new(body))
+    }
+
+    /// Send a body with the request, read from `body` on the calling thread.
+    ///
+    /// Unless a `ContentLength` header is set, the body is sent chunked.
+    pub fn body<R: Read + 'a>(mut self, body: R) -> RequestBuilder<'a> {
+        self.body = Some(Box::new(body));
+        self
+    }
+
+    /// Set a timeout for each wait on the connection, reading or writing,
+    /// in addition to the timeouts of the `Config`.
+    ///
+    /// Exceeding it is reported as `Error::Timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> RequestBuilder<'a> {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Send the request, blocking until the response head has arrived.
+    ///
+    /// The response body is read from the returned `Response`.
+    pub fn send(self) -> ::Result<Response> {
+        let RequestBuilder { client, method, url, headers, body, timeout } = self;
+        let shared = Arc::new(Shared {
+            control: Mutex::new(None),
+            waiting: AtomicBool::new(false),
+            buffered: AtomicUsize::new(0),
+        });
+        let (tx, rx) = mpsc::channel();
+        let (body_tx, body_rx) = match body {
+            Some(_) => {
+                let (body_tx, body_rx) = mpsc::sync_channel(MAX_BODY_CHUNKS);
+                (Some(body_tx), Some(body_rx))
+            },
+            None => (None, None)
+        };
+        let handler = Blocking {
+            method: method,
+            headers: Some(headers),
+            timeout: timeout,
+            body: body_rx,
+            chunk: None,
+            tx: tx,
+            shared: shared.clone(),
+        };
+        let queued = match client.client {
+            Some(ref client) => client.request(url, handler).is_ok(),
+            None => false
+        };
+        if !queued {
+            return Err(::Error::Io(io::Error::new(io::ErrorKind::Other, "client event loop is closed")));
+        }
+
+        if let (Some(mut body), Some(body_tx)) = (body, body_tx) {
+            pump(&mut body, &body_tx, &shared);
+        }
+
+        match rx.recv() {
+            Ok(Event::Head(head)) => Ok(Response {
+                head: head,
+                rx: rx,
+                shared: shared,
+                buf: Vec::new(),
+                pos: 0,
+                done: false,
+            }),
+            Ok(Event::Error(e)) => Err(e),
+            Ok(_) => unreachable!("response body before head"),
+            Err(_) => Err(::Error::Io(aborted())),
+        }
+    }
+}
+
+impl<'a> fmt::Debug for RequestBuilder<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RequestBuilder")
+            .field("method", &self.method)
+            .field("url", &self.url)
+            .field("headers", &self.headers)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// A response from a blocking `Client`.
+///
+/// The body is read with `Read`, blocking until more of it has arrived.
+/// Dropping the `Response` before the body has been read closes the
+/// connection.
+pub struct Response {
+    head: super::Response,
+    rx: mpsc::Receiver<Event>,
+    shared: Arc<Shared>,
+    buf: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl Response {
+    /// Get the headers from the server.
+    #[inline]
+    pub fn headers(&self) -> &Headers { self.head.headers() }
+
+    /// Get the status from the server.
+    #[inline]
+    pub fn status(&self) -> &StatusCode { self.head.status() }
+
+    /// Get the HTTP version of this response from the server.
+    #[inline]
+    pub fn version(&self) -> &HttpVersion { self.head.version() }
+}
+
+impl Read for Response {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.buf.len() {
+            if self.done {
+                return Ok(0);
+            }
+            match self.rx.recv() {
+                Ok(Event::Chunk(chunk)) => {
+                    self.shared.buffered.fetch_sub(1, Ordering::SeqCst);
+                    wake(&self.shared, Next::read());
+                    self.buf = chunk;
+                    self.pos = 0;
+                },
+                Ok(Event::End) => self.done = true,
+                Ok(Event::Error(e)) => {
+                    self.done = true;
+                    return Err(match e {
+                        ::Error::Io(e) => e,
+                        e => io::Error::new(io::ErrorKind::Other, e)
+                    });
+                },
+                Ok(Event::Head(..)) => unreachable!("second response head"),
+                Err(_) => {
+                    self.done = true;
+                    return Err(aborted());
+                }
+            }
+        }
+        let n = cmp::min(buf.len(), self.buf.len() - self.pos);
+        buf[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl fmt::Debug for Response {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Response")
+            .field("head", &self.head)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+impl Drop for Response {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        // the body may already have finished, leaving the connection to be
+        // reused; otherwise wake the handler, which may be parked waiting
+        // for us to catch up, and have it close the connection.
+        while let Ok(event) = self.rx.try_recv() {
+            match event {
+                Event::End | Event::Error(..) => return,
+                _ => ()
+            }
+        }
+        if let Some(control) = self.shared.control.lock().unwrap().take() {
+            let _ = control.ready(Next::remove());
+        }
+    }
+}
+
+fn aborted() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, "request aborted")
+}
+
+/// Reads the request body on the calling thread, passing it to the event
+/// loop in chunks.
+fn pump<R: Read + ?Sized>(body: &mut R, tx: &mpsc::SyncSender<io::Result<Vec<u8>>>, shared: &Shared) {
+    loop {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let (chunk, last) = match body.read(&mut chunk) {
+            Ok(n) => {
+                chunk.truncate(n);
+                (Ok(chunk), n == 0)
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => (Err(e), true)
+        };
+        if tx.send(chunk).is_err() {
+            // the request has already ended
+            return;
+        }
+        wake(shared, Next::write());
+        if last {
+            return;
+        }
+    }
+}
+
+/// Wakes the `Blocking` handler, if it is waiting on the caller.
+fn wake(shared: &Shared, next: Next) {
+    if shared.waiting.swap(false, Ordering::SeqCst) {
+        if let Some(ref control) = *shared.control.lock().unwrap() {
+            let _ = control.ready(next);
+        }
+    }
+}
+
+/// State shared between the calling thread and the event loop.
+struct Shared {
+    control: Mutex<Option<Control>>,
+    waiting: AtomicBool,
+    buffered: AtomicUsize,
+}
+
+enum Event {
+    Head(super::Response),
+    Chunk(Vec<u8>),
+    End,
+    Error(::Error),
+}
+
+/// The `Handler` run on the event loop for each blocking request.
+struct Blocking {
+    method: Method,
+    headers: Option<Headers>,
+    timeout: Option<Duration>,
+    body: Option<mpsc::Receiver<io::Result<Vec<u8>>>>,
+    chunk: Option<(Vec<u8>, usize)>,
+    tx: mpsc::Sender<Event>,
+    shared: Arc<Shared>,
+}
+
+impl fmt::Debug for Blocking {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Blocking")
+            .field("method", &self.method)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl Blocking {
+    fn next(&self, next: Next) -> Next {
+        match self.timeout {
+            Some(timeout) => next.timeout(timeout),
+            None => next
+        }
+    }
+
+    /// Waits for the caller to make room for more of the response.
+    fn wait_for_caller(&self) -> bool {
+        if self.shared.buffered.load(Ordering::SeqCst) < MAX_BUFFERED {
+            return false;
+        }
+        self.shared.waiting.store(true, Ordering::SeqCst);
+        if self.shared.buffered.load(Ordering::SeqCst) < MAX_BUFFERED {
+            self.shared.waiting.store(false, Ordering::SeqCst);
+            false
+        } else {
+            true
+        }
+    }
+
+    fn send(&self, event: Event) -> Next {
+        match self.tx.send(event) {
+            Ok(()) => self.next(Next::read()),
+            // the Response was dropped
+            Err(_) => Next::remove()
+        }
+    }
+}
+
+impl<T: Transport> super::Handler<T> for Blocking {
+    fn on_request(&mut self, req: &mut Request) -> Next {
+        req.set_method(self.method.clone());
+        // kept, since a retried request is started again
+        if let Some(ref headers) = self.headers {
+            req.headers_mut().extend(headers.iter());
+        }
+        if self.body.is_some() {
+            self.next(Next::write())
+        } else {
+            self.next(Next::read())
+        }
+    }
+
+    fn on_request_writable(&mut self, encoder: &mut Encoder<T>) -> Next {
+        loop {
+            if self.chunk.is_none() {
+                self.shared.waiting.store(true, Ordering::SeqCst);
+                let chunk = match self.body {
+                    Some(ref body) => body.try_recv(),
+                    None => Ok(Ok(Vec::new()))
+                };
+                match chunk {
+                    Ok(Ok(chunk)) => {
+                        self.shared.waiting.store(false, Ordering::SeqCst);
+                        if chunk.is_empty() {
+                            encoder.close();
+                            self.body = None;
+                            return self.next(Next::read());
+                        }
+                        self.chunk = Some((chunk, 0));
+                    },
+                    Ok(Err(e)) => {
+                        debug!("error reading request body: {:?}", e);
+                        let _ = self.tx.send(Event::Error(::Error::Io(e)));
+                        return Next::remove();
+                    },
+                    Err(mpsc::TryRecvError::Empty) => return self.next(Next::wait()),
+                    Err(mpsc::TryRecvError::Disconnected) => return Next::remove(),
+                }
+            }
+
+            let written = match self.chunk {
+                Some((ref chunk, ref mut pos)) => match encoder.write(&chunk[*pos..]) {
+                    Ok(n) => {
+                        *pos += n;
+                        *pos == chunk.len()
+                    },
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => false,
+                    Err(e) => {
+                        let _ = self.tx.send(Event::Error(::Error::Io(e)));
+                        return Next::remove();
+                    }
+                },
+                None => unreachable!("chunk was just set")
+            };
+            if !written {
+                return self.next(Next::write());
+            }
+            self.chunk = None;
+        }
+    }
+
+    fn on_response(&mut self, res: super::Response) -> Next {
+        use header::ContentLength;
+        let has_body = match *res.status() {
+            StatusCode::NoContent | StatusCode::NotModified => false,
+            _ => self.method != Method::Head && res.headers().get() != Some(&ContentLength(0))
+        };
+        let next = self.send(Event::Head(res));
+        if has_body {
+            next
+        } else {
+            self.shared.control.lock().unwrap().take();
+            let _ = self.tx.send(Event::End);
+            Next::end()
+        }
+    }
+
+    fn on_response_readable(&mut self, decoder: &mut Decoder<T>) -> Next {
+        if self.wait_for_caller() {
+            return self.next(Next::wait());
+        }
+        let mut chunk = vec![0; CHUNK_SIZE];
+        match decoder.read(&mut chunk) {
+            Ok(0) => {
+                self.shared.control.lock().unwrap().take();
+                let _ = self.tx.send(Event::End);
+                Next::end()
+            },
+            Ok(n) => {
+                chunk.truncate(n);
+                self.shared.buffered.fetch_add(1, Ordering::SeqCst);
+                self.send(Event::Chunk(chunk))
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => self.next(Next::read()),
+            Err(e) => {
+                let _ = self.tx.send(Event::Error(::Error::Io(e)));
+                Next::remove()
+            }
+        }
+    }
+
+    fn on_error(&mut self, err: ::Error) -> Next {
+        let _ = self.tx.send(Event::Error(err));
+        Next::remove()
+    }
+
+    fn on_control(&mut self, control: Control) {
+        *self.shared.control.lock().unwrap() = Some(control);
+    }
+}
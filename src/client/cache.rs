@@ -0,0 +1,690 @@
+//! A private HTTP cache for buffered requests.
+//!
+//! A `Cache` keeps responses to `Fetch` requests, following the rules of
+//! [RFC7234](https://tools.ietf.org/html/rfc7234) for a private cache:
+//!
+//! - A fresh response is served without sending the request at all.
+//! - A stale response with an `ETag` or `LastModified` is revalidated with
+//!   `IfNoneMatch` or `IfModifiedSince`, and a `304 Not Modified` reply
+//!   serves the stored response, refreshed with the new headers.
+//! - Freshness comes from `CacheControl` `max-age`, then `Expires`, then a
+//!   heuristic of a tenth of the time since `LastModified`. The age counts
+//!   the `Age` header, and the time the response took to arrive.
+//! - `no-store` in the request or response keeps it out of the cache, and
+//!   `no-cache` always revalidates.
+//! - A successful unsafe request, such as a `POST`, removes the stored
+//!   response for its URL.
+//!
+//! - Partial content, and responses to `Range` requests, are not stored.
+//!
+//! One response is kept per URL. It is only served to requests that send
+//! the same values of the headers its `Vary` names.
+//!
+//! Only `Fetch` requests use a `Cache`, since storing and serving a response
+//! needs its whole body at once. Requests made with any other `Handler`
+//! stream their bodies, and always go to the network.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use hyper::Client;
+//! use hyper::client::Cache;
+//! use hyper::client::fetch::Fetch;
+//! use hyper::method::Method;
+//!
+//! let client = Client::<Fetch>::new().unwrap();
+//! let cache = Cache::memory();
+//! let url = "http://example.domain/data".parse().unwrap();
+//! let (res, body) = client.fetch(url, Fetch::new(Method::Get).cache(&cache)).wait().unwrap();
+//! ```
+
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use time;
+use unicase::UniCase;
+
+use header::{Age, CacheControl, CacheDirective, Date, ETag, Expires, Headers, HttpDate,
+             IfModifiedSince, IfNoneMatch, IfRange, LastModified, Pragma, Range, Vary};
+use http::{self, RawStatus};
+use method::Method;
+use status::StatusCode;
+use version::HttpVersion;
+use Url;
+use super::{response, Response};
+
+/// The default number of responses a `MemoryStore` keeps.
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// A response kept by a `Cache`.
+///
+/// The fields are public so that a `Store` can save them anywhere.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// The status code of the response.
+    pub status: u16,
+    /// The reason phrase of the response.
+    pub reason: String,
+    /// The HTTP version of the response.
+    pub version: HttpVersion,
+    /// The headers of the response.
+    pub headers: Headers,
+    /// The whole body of the response.
+    pub body: Vec<u8>,
+    /// The request's values of the headers named by the response's `Vary`.
+    pub vary: Vec<(String, Option<String>)>,
+    /// When the request was sent, in seconds since the epoch.
+    pub request_time: i64,
+    /// When the response was received, in seconds since the epoch.
+    pub response_time: i64,
+}
+
+/// Where a `Cache` keeps its entries.
+pub trait Store: Send + 'static {
+    /// Get the entry for a key.
+    fn get(&mut self, key: &str) -> Option<Entry>;
+    /// Keep an entry for a key, replacing any there was.
+    fn put(&mut self, key: String, entry: Entry);
+    /// Remove the entry for a key.
+    fn remove(&mut self, key: &str);
+}
+
+/// A `Store` in memory, which forgets the oldest entries once full.
+#[derive(Debug)]
+pub struct MemoryStore {
+    entries: HashMap<String, Entry>,
+    order: VecDeque<String>,
+    capacity: usize,
+}
+
+impl MemoryStore {
+    /// Create a `MemoryStore` keeping up to `capacity` entries.
+    pub fn new(capacity: usize) -> MemoryStore {
+        MemoryStore {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            capacity: capacity,
+        }
+    }
+
+    /// The number of entries kept.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Store for MemoryStore {
+    fn get(&mut self, key: &str) -> Option<Entry> {
+        self.entries.get(key).cloned()
+    }
+
+    fn put(&mut self, key: String, entry: Entry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.insert(key.clone(), entry).is_some() {
+            self.order.retain(|k| *k != key);
+        }
+        self.order.push_back(key);
+        while self.entries.len() > self.capacity {
+            match self.order.pop_front() {
+                Some(oldest) => { self.entries.remove(&oldest); },
+                None => break
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if self.entries.remove(key).is_some() {
+            self.order.retain(|k| k != key);
+        }
+    }
+}
+
+/// A private cache of responses, shared by any number of `Fetch` requests.
+#[derive(Clone)]
+pub struct Cache {
+    store: Arc<Mutex<Box<Store>>>,
+}
+
+impl Cache {
+    /// Create a `Cache` keeping its entries in a `Store`.
+    pub fn new<S: Store>(store: S) -> Cache {
+        Cache {
+            store: Arc::new(Mutex::new(Box::new(store)))
+        }
+    }
+
+    /// Create a `Cache` keeping up to `DEFAULT_CAPACITY` entries in memory.
+    pub fn memory() -> Cache {
+        Cache::new(MemoryStore::new(DEFAULT_CAPACITY))
+    }
+
+    /// Remove the entry for a `GET` of `url`.
+    pub fn remove(&self, url: &Url) {
+        self.with(|store| store.remove(&key(url)));
+    }
+
+    fn with<F, R>(&self, f: F) -> R where F: FnOnce(&mut Store) -> R {
+        let mut store = match self.store.lock() {
+            Ok(store) => store,
+            // a panicking Store leaves nothing half done that matters here
+            Err(poisoned) => poisoned.into_inner()
+        };
+        f(&mut **store)
+    }
+}
+
+impl fmt::Debug for Cache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Cache")
+    }
+}
+
+/// What the cache has for a request about to be sent.
+pub enum Lookup {
+    /// A response to use without sending the request.
+    Hit(Response, Vec<u8>),
+    /// The request must be sent, and the `Pending` told how it went.
+    Send(Pending),
+}
+
+/// A request that may update the cache once its response arrives.
+pub struct Pending {
+    cache: Cache,
+    url: Url,
+    method: Method,
+    request: Headers,
+    entry: Option<Entry>,
+    request_time: i64,
+}
+
+/// Looks for a response to a request, adding the headers to revalidate a
+/// stale one to `headers`.
+pub fn lookup(cache: &Cache, method: &Method, url: &Url, headers: &mut Headers) -> Lookup {
+    let mut entry = None;
+    if *method == Method::Get && !directive(headers, |d| *d == CacheDirective::NoStore) {
+        let now = now();
+        let stored = cache.with(|store| store.get(&key(url)))
+            .and_then(|stored| if matches_vary(&stored, headers) { Some(stored) } else { None });
+        // only-if-cached forbids the network, even to revalidate
+        let only_if_cached = directive(headers, |d| *d == CacheDirective::OnlyIfCached);
+        if let Some(stored) = stored {
+            if is_fresh(&stored, headers, now) {
+                debug!("cache hit: {}", url);
+                return Lookup::Hit(to_response(&stored, Some(current_age(&stored, now))), stored.body);
+            }
+            // a Handler's own conditions are for it to handle
+            let conditional = headers.has::<IfNoneMatch>() || headers.has::<IfModifiedSince>();
+            if !only_if_cached && !conditional && revalidate(&stored, headers) {
+                debug!("cache revalidating: {}", url);
+                entry = Some(stored);
+            }
+        }
+        if only_if_cached {
+            let entry = Entry {
+                status: 504,
+                reason: "Gateway Timeout".to_owned(),
+                version: HttpVersion::Http11,
+                headers: Headers::new(),
+                body: Vec::new(),
+                vary: Vec::new(),
+                request_time: now,
+                response_time: now,
+            };
+            return Lookup::Hit(to_response(&entry, None), Vec::new());
+        }
+    }
+    Lookup::Send(Pending {
+        cache: cache.clone(),
+        url: url.clone(),
+        method: method.clone(),
+        request: headers.clone(),
+        entry: entry,
+        request_time: now(),
+    })
+}
+
+impl Pending {
+    /// Notes that the request is being sent.
+    pub fn start(&mut self) {
+        self.request_time = now();
+    }
+
+    /// Serves the stored response, if `res` is a `304 Not Modified` reply to
+    /// revalidating it.
+    pub fn not_modified(&mut self, res: &Response) -> Option<(Response, Vec<u8>)> {
+        if *res.status() != StatusCode::NotModified {
+            return None;
+        }
+        self.entry.take().map(|mut entry| {
+            debug!("cache revalidated: {}", self.url);
+            for header in res.headers().iter() {
+                let name = UniCase(header.name());
+                let framing = ["Content-Length", "Transfer-Encoding", "Connection", "Keep-Alive"];
+                if !framing.iter().any(|framing| name == UniCase(*framing)) {
+                    entry.headers.set_raw(header.name().to_owned(), header.value_string());
+                }
+            }
+            entry.request_time = self.request_time;
+            entry.response_time = now();
+            let res = to_response(&entry, Some(current_age(&entry, entry.response_time)));
+            let body = entry.body.clone();
+            self.cache.with(|store| store.put(key(&self.url), entry));
+            (res, body)
+        })
+    }
+
+    /// Keeps a whole response, if it may be stored.
+    pub fn store(&mut self, res: &Response, body: &[u8]) {
+        let success = match res.status_raw().0 {
+            200...399 => true,
+            _ => false
+        };
+        if !self.method.safe() {
+            if success {
+                debug!("cache invalidated by {}: {}", self.method, self.url);
+                self.cache.remove(&self.url);
+            }
+            return;
+        }
+        if self.method != Method::Get {
+            return;
+        }
+        let entry = Entry {
+            status: res.status_raw().0,
+            reason: res.status_raw().1.clone().into_owned(),
+            version: *res.version(),
+            headers: res.headers().clone(),
+            body: body.to_vec(),
+            vary: match vary(res.headers(), &self.request) {
+                Some(vary) => vary,
+                None => return
+            },
+            request_time: self.request_time,
+            response_time: now(),
+        };
+        if storable(&entry, &self.request) {
+            trace!("cache storing: {}", self.url);
+            self.cache.with(|store| store.put(key(&self.url), entry));
+        }
+    }
+}
+
+impl fmt::Debug for Lookup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Lookup::Hit(ref res, _) => f.debug_tuple("Hit").field(res).finish(),
+            Lookup::Send(ref pending) => f.debug_tuple("Send").field(pending).finish(),
+        }
+    }
+}
+
+impl fmt::Debug for Pending {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pending")
+            .field("url", &self.url)
+            .field("method", &self.method)
+            .field("revalidating", &self.entry.is_some())
+            .finish()
+    }
+}
+
+fn key(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    format!("GET {}", url)
+}
+
+fn now() -> i64 {
+    time::get_time().sec
+}
+
+fn to_response(entry: &Entry, age: Option<i64>) -> Response {
+    let mut headers = entry.headers.clone();
+    if let Some(age) = age {
+        headers.set(Age(cmp::max(0, age) as u32));
+    }
+    response::new(http::ResponseHead {
+        version: entry.version,
+        subject: RawStatus(entry.status, Cow::Owned(entry.reason.clone())),
+        headers: headers,
+    })
+}
+
+fn directive<F: Fn(&CacheDirective) -> bool>(headers: &Headers, f: F) -> bool {
+    headers.get::<CacheControl>().map_or(false, |cc| cc.iter().any(f))
+}
+
+fn find_directive<T, F: Fn(&CacheDirective) -> Option<T>>(headers: &Headers, f: F) -> Option<T> {
+    headers.get::<CacheControl>().and_then(|cc| cc.iter().filter_map(f).next())
+}
+
+fn header_value(headers: &Headers, name: &str) -> Option<String> {
+    headers.iter()
+        .find(|header| UniCase(header.name()) == UniCase(name))
+        .map(|header| header.value_string())
+}
+
+/// Whether a request sends the same values of the headers a stored
+/// response varies on.
+fn matches_vary(entry: &Entry, request: &Headers) -> bool {
+    entry.vary.iter().all(|&(ref name, ref value)| header_value(request, name) == *value)
+}
+
+/// The request's values of the headers the response `Vary`s on, or `None`
+/// if it varies on everything.
+fn vary(response: &Headers, request: &Headers) -> Option<Vec<(String, Option<String>)>> {
+    match response.get::<Vary>() {
+        Some(&Vary::Any) => None,
+        Some(&Vary::Items(ref names)) => Some(names.iter().map(|name| {
+            (name.to_string(), header_value(request, name))
+        }).collect()),
+        None => Some(Vec::new())
+    }
+}
+
+fn seconds(date: &HttpDate) -> i64 {
+    date.0.to_timespec().sec
+}
+
+fn date(entry: &Entry) -> i64 {
+    entry.headers.get::<Date>().map_or(entry.response_time, |date| seconds(&date.0))
+}
+
+/// The age of a response, as in RFC7234 section 4.2.3.
+fn current_age(entry: &Entry, now: i64) -> i64 {
+    let age_value = entry.headers.get::<Age>().map_or(0, |age| age.0 as i64);
+    let apparent_age = cmp::max(0, entry.response_time - date(entry));
+    let response_delay = entry.response_time - entry.request_time;
+    let corrected_initial_age = cmp::max(apparent_age, age_value + response_delay);
+    corrected_initial_age + (now - entry.response_time)
+}
+
+/// Whether a status may be given a heuristic freshness, as in RFC7231
+/// section 6.1.
+fn heuristic_status(status: u16) -> bool {
+    match status {
+        200 | 203 | 204 | 300 | 301 | 404 | 405 | 410 | 414 | 501 => true,
+        _ => false
+    }
+}
+
+/// How long a response is fresh for, as in RFC7234 section 4.2.1.
+fn freshness_lifetime(entry: &Entry) -> i64 {
+    let headers = &entry.headers;
+    if let Some(max_age) = find_directive(headers, |d| match *d {
+        CacheDirective::MaxAge(secs) => Some(secs),
+        _ => None
+    }) {
+        return max_age as i64;
+    }
+    if let Some(expires) = headers.get::<Expires>() {
+        return cmp::max(0, seconds(&expires.0) - date(entry));
+    }
+    if let Some(last_modified) = headers.get::<LastModified>() {
+        if heuristic_status(entry.status) {
+            return cmp::max(0, date(entry) - seconds(&last_modified.0)) / 10;
+        }
+    }
+    0
+}
+
+/// Whether a stored response may be used for a request without asking the
+/// server, as in RFC7234 section 4.2.
+fn is_fresh(entry: &Entry, request: &Headers, now: i64) -> bool {
+    let no_cache = |headers: &Headers| directive(headers, |d| *d == CacheDirective::NoCache);
+    if no_cache(request) || no_cache(&entry.headers) || request.get::<Pragma>() == Some(&Pragma::NoCache) {
+        return false;
+    }
+    let age = current_age(entry, now);
+    let lifetime = freshness_lifetime(entry);
+    if let Some(max_age) = find_directive(request, |d| match *d {
+        CacheDirective::MaxAge(secs) => Some(secs as i64),
+        _ => None
+    }) {
+        if age > max_age {
+            return false;
+        }
+    }
+    let min_fresh = find_directive(request, |d| match *d {
+        CacheDirective::MinFresh(secs) => Some(secs as i64),
+        _ => None
+    }).unwrap_or(0);
+    let must_revalidate = directive(&entry.headers, |d| *d == CacheDirective::MustRevalidate);
+    let max_stale = if must_revalidate {
+        0
+    } else {
+        find_directive(request, |d| match *d {
+            CacheDirective::MaxStale(secs) => Some(secs as i64),
+            _ => None
+        }).unwrap_or(0)
+    };
+    age + min_fresh < lifetime + max_stale
+}
+
+/// Adds the headers to revalidate a stored response, returning whether it
+/// has any validators.
+fn revalidate(entry: &Entry, request: &mut Headers) -> bool {
+    let mut any = false;
+    if let Some(&ETag(ref tag)) = entry.headers.get::<ETag>() {
+        request.set(IfNoneMatch::Items(vec![tag.clone()]));
+        any = true;
+    }
+    if let Some(&LastModified(date)) = entry.headers.get::<LastModified>() {
+        request.set(IfModifiedSince(date));
+        any = true;
+    }
+    any
+}
+
+/// Whether a response may be stored, as in RFC7234 section 3.
+fn storable(entry: &Entry, request: &Headers) -> bool {
+    // partial content can't stand in for the whole representation
+    if entry.status == 206 || request.has::<Range>() || request.has::<IfRange>() {
+        return false;
+    }
+    let no_store = |headers: &Headers| directive(headers, |d| *d == CacheDirective::NoStore);
+    if no_store(request) || no_store(&entry.headers) {
+        return false;
+    }
+    let explicit = entry.headers.has::<Expires>() || directive(&entry.headers, |d| match *d {
+        CacheDirective::MaxAge(_) | CacheDirective::Public => true,
+        _ => false
+    });
+    if !explicit && !heuristic_status(entry.status) {
+        return false;
+    }
+    let validators = entry.headers.has::<ETag>() || entry.headers.has::<LastModified>();
+    freshness_lifetime(entry) > 0 || validators
+}
+
+#[cfg(test)]
+mod tests {
+    use header::{Age, CacheControl, CacheDirective, Date, ETag, EntityTag, Expires, Headers,
+                 HttpDate, IfNoneMatch, IfRange, LastModified, Range};
+    use method::Method;
+    use status::StatusCode;
+    use time;
+    use version::HttpVersion;
+    use super::{Cache, Entry, Lookup, MemoryStore, Store, current_age, freshness_lifetime,
+                is_fresh, key, lookup, now, storable};
+
+    const NOW: i64 = 1_000_000;
+
+    fn date(sec: i64) -> HttpDate {
+        HttpDate(time::at_utc(time::Timespec::new(sec, 0)))
+    }
+
+    fn entry(headers: Headers) -> Entry {
+        Entry {
+            status: 200,
+            reason: "OK".to_owned(),
+            version: HttpVersion::Http11,
+            headers: headers,
+            body: b"hello".to_vec(),
+            vary: Vec::new(),
+            request_time: NOW - 2,
+            response_time: NOW - 1,
+        }
+    }
+
+    /// An entry just received, as `lookup` compares ages to the clock.
+    fn recent(headers: Headers) -> Entry {
+        let mut entry = entry(headers);
+        entry.request_time = now();
+        entry.response_time = entry.request_time;
+        entry
+    }
+
+    #[test]
+    fn test_cache_age() {
+        let mut headers = Headers::new();
+        headers.set(Date(date(NOW - 10)));
+        // the clock of the server differs, so the apparent age counts
+        assert_eq!(current_age(&entry(headers.clone()), NOW), 10);
+        headers.set(Age(30));
+        // the delay of the response is added to a received Age
+        assert_eq!(current_age(&entry(headers), NOW), 32);
+    }
+
+    #[test]
+    fn test_cache_freshness_lifetime() {
+        let mut headers = Headers::new();
+        headers.set(Date(date(NOW)));
+        headers.set(LastModified(date(NOW - 1000)));
+        assert_eq!(freshness_lifetime(&entry(headers.clone())), 100);
+        headers.set(Expires(date(NOW + 300)));
+        assert_eq!(freshness_lifetime(&entry(headers.clone())), 300);
+        headers.set(CacheControl(vec![CacheDirective::MaxAge(60)]));
+        assert_eq!(freshness_lifetime(&entry(headers)), 60);
+    }
+
+    #[test]
+    fn test_cache_is_fresh() {
+        let mut headers = Headers::new();
+        headers.set(CacheControl(vec![CacheDirective::MaxAge(60)]));
+        let fresh = entry(headers);
+        let mut request = Headers::new();
+        assert!(is_fresh(&fresh, &request, NOW));
+        assert!(!is_fresh(&fresh, &request, NOW + 60));
+
+        request.set(CacheControl(vec![CacheDirective::MaxStale(120)]));
+        assert!(is_fresh(&fresh, &request, NOW + 60));
+        request.set(CacheControl(vec![CacheDirective::MinFresh(59)]));
+        assert!(!is_fresh(&fresh, &request, NOW));
+        request.set(CacheControl(vec![CacheDirective::NoCache]));
+        assert!(!is_fresh(&fresh, &request, NOW));
+    }
+
+    #[test]
+    fn test_cache_storable() {
+        let request = Headers::new();
+        assert!(!storable(&entry(Headers::new()), &request));
+
+        let mut headers = Headers::new();
+        headers.set(ETag(EntityTag::strong("v1".to_owned())));
+        assert!(storable(&entry(headers), &request));
+
+        let mut headers = Headers::new();
+        headers.set(CacheControl(vec![CacheDirective::MaxAge(60), CacheDirective::NoStore]));
+        assert!(!storable(&entry(headers), &request));
+    }
+
+    #[test]
+    fn test_cache_storable_partial() {
+        let mut headers = Headers::new();
+        headers.set(CacheControl(vec![CacheDirective::MaxAge(60)]));
+        let mut partial = entry(headers.clone());
+        partial.status = 206;
+        assert!(!storable(&partial, &Headers::new()));
+
+        let mut request = Headers::new();
+        request.set(Range::bytes(0, 4));
+        assert!(!storable(&entry(headers.clone()), &request));
+
+        let mut request = Headers::new();
+        request.set(IfRange::EntityTag(EntityTag::strong("v1".to_owned())));
+        assert!(!storable(&entry(headers), &request));
+    }
+
+    #[test]
+    fn test_cache_lookup() {
+        let cache = Cache::new(MemoryStore::new(2));
+        let url = "http://example.domain/a#frag".parse().unwrap();
+        let mut stale = Headers::new();
+        stale.set(ETag(EntityTag::strong("v1".to_owned())));
+        cache.with(|store| store.put(key(&url), entry(stale)));
+
+        let mut request = Headers::new();
+        match lookup(&cache, &Method::Get, &url, &mut request) {
+            Lookup::Send(ref pending) => assert!(pending.entry.is_some()),
+            other => panic!("expected revalidation, actual: {:?}", other)
+        }
+        assert_eq!(request.get::<IfNoneMatch>(),
+                   Some(&IfNoneMatch::Items(vec![EntityTag::strong("v1".to_owned())])));
+
+        let mut fresh = Headers::new();
+        fresh.set(CacheControl(vec![CacheDirective::MaxAge(3600)]));
+        cache.with(|store| store.put(key(&url), recent(fresh)));
+        match lookup(&cache, &Method::Get, &url, &mut Headers::new()) {
+            Lookup::Hit(res, body) => {
+                assert!(res.headers().has::<Age>());
+                assert_eq!(body, b"hello");
+            },
+            other => panic!("expected hit, actual: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_cache_lookup_only_if_cached() {
+        let cache = Cache::new(MemoryStore::new(2));
+        let url = "http://example.domain/a".parse().unwrap();
+        let mut stale = Headers::new();
+        stale.set(ETag(EntityTag::strong("v1".to_owned())));
+        cache.with(|store| store.put(key(&url), recent(stale)));
+
+        let mut request = Headers::new();
+        request.set(CacheControl(vec![CacheDirective::OnlyIfCached]));
+        match lookup(&cache, &Method::Get, &url, &mut request) {
+            Lookup::Hit(res, body) => {
+                assert_eq!(*res.status(), StatusCode::GatewayTimeout);
+                assert!(body.is_empty());
+            },
+            other => panic!("expected gateway timeout, actual: {:?}", other)
+        }
+        assert!(!request.has::<IfNoneMatch>());
+
+        // a stale response allowed by max-stale is served
+        request.set(CacheControl(vec![CacheDirective::OnlyIfCached, CacheDirective::MaxStale(3600)]));
+        match lookup(&cache, &Method::Get, &url, &mut request) {
+            Lookup::Hit(res, body) => {
+                assert_eq!(*res.status(), StatusCode::Ok);
+                assert_eq!(body, b"hello");
+            },
+            other => panic!("expected hit, actual: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_memory_store_capacity() {
+        let mut store = MemoryStore::new(2);
+        store.put("a".to_owned(), entry(Headers::new()));
+        store.put("b".to_owned(), entry(Headers::new()));
+        store.put("a".to_owned(), entry(Headers::new()));
+        store.put("c".to_owned(), entry(Headers::new()));
+        assert_eq!(store.len(), 2);
+        assert!(store.get("b").is_none());
+        assert!(store.get("a").is_some());
+        store.remove("a");
+        assert!(store.get("a").is_none());
+    }
+}
@@ -2,15 +2,26 @@ use std::collections::hash_map::{HashMap, Entry};
 use std::hash::Hash;
 use std::fmt;
 use std::io;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
 
+use net2::TcpBuilder;
 use rotor::mio::tcp::TcpStream;
 use url::Url;
 
+use header::Headers;
 use net::{HttpStream, HttpsStream, Transport, SslClient};
 use super::dns::Dns;
 use super::Registration;
 
+pub use self::proxy::{Proxy, ProxyConnector};
+pub use self::socks::{Socks5, SocksConnector};
+pub use self::tunnel::Tunnel;
+
+mod proxy;
+mod socks;
+mod tunnel;
+
 /// A connector creates a Transport to a remote address..
 pub trait Connect {
     /// Type of Transport to create
@@ -23,18 +34,31 @@ pub trait Connect {
     fn connect(&mut self, &Url) -> io::Result<Self::Key>;
     /// Returns a connected socket and associated host.
     fn connected(&mut self) -> Option<(Self::Key, io::Result<Self::Output>)>;
+    /// Returns the extra headers to send when a request for this `Url` goes
+    /// through an HTTP proxy.
+    ///
+    /// Returning `Some` means the request is written in absolute-form, as
+    /// proxies require. The default is `None`, for requests written directly
+    /// to the origin server.
+    fn proxy(&self, _url: &Url) -> Option<Headers> {
+        None
+    }
     #[doc(hidden)]
     fn register(&mut self, Registration);
 }
 
 type Scheme = String;
 type Port = u16;
+type Key = (&'static str, String, u16);
 
 /// A connector for the `http` scheme.
 pub struct HttpConnector {
     dns: Option<Dns>,
     threads: usize,
-    resolving: HashMap<String, Vec<(&'static str, String, u16)>>,
+    resolving: HashMap<String, Vec<(Key, Port)>>,
+    local_address: Option<IpAddr>,
+    nodelay: bool,
+    keepalive: Option<Duration>,
 }
 
 impl HttpConnector {
@@ -46,6 +70,60 @@ impl HttpConnector {
         self.threads = threads;
         self
     }
+
+    /// Bind outgoing sockets to a local address before connecting, picking
+    /// the source IP on hosts with several.
+    ///
+    /// The address must be of the same family as the addresses connected to.
+    /// By default, the OS chooses.
+    pub fn local_address(mut self, addr: IpAddr) -> HttpConnector {
+        self.local_address = Some(addr);
+        self
+    }
+
+    /// Set `TCP_NODELAY` on connected streams.
+    ///
+    /// Default is `false`.
+    pub fn nodelay(mut self, nodelay: bool) -> HttpConnector {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// Send keepalive probes on connected streams once they have been idle
+    /// for this long.
+    ///
+    /// Default is `None`, sending no probes.
+    pub fn keepalive(mut self, idle: Option<Duration>) -> HttpConnector {
+        self.keepalive = idle;
+        self
+    }
+
+    /// Opens a non-blocking connection to `addr`, from the local address if
+    /// one is set.
+    fn connect_to(&self, addr: &SocketAddr) -> io::Result<HttpStream> {
+        let stream = match self.local_address {
+            Some(local) => {
+                let builder = try!(match local {
+                    IpAddr::V4(..) => TcpBuilder::new_v4(),
+                    IpAddr::V6(..) => TcpBuilder::new_v6(),
+                });
+                try!(builder.bind(&SocketAddr::new(local, 0)));
+                try!(TcpStream::connect_stream(try!(builder.to_tcp_stream()), addr))
+            },
+            None => try!(TcpStream::connect(addr))
+        };
+        let stream = HttpStream(stream);
+        try!(stream.set_nodelay(self.nodelay));
+        try!(stream.set_keepalive(self.keepalive));
+        Ok(stream)
+    }
+
+    /// Starts resolving `host`, and once resolved, connecting to it on `port`
+    /// on behalf of `key`.
+    fn resolve(&mut self, host: &str, port: Port, key: Key) {
+        self.dns.as_ref().expect("dns workers lost").resolve(host);
+        self.resolving.entry(host.to_owned()).or_insert_with(Vec::new).push((key, port));
+    }
 }
 
 impl Default for HttpConnector {
@@ -54,6 +132,9 @@ impl Default for HttpConnector {
             dns: None,
             threads: 4,
             resolving: HashMap::new(),
+            local_address: None,
+            nodelay: false,
+            keepalive: None,
         }
     }
 }
@@ -63,6 +144,9 @@ impl fmt::Debug for HttpConnector {
         f.debug_struct("HttpConnector")
             .field("threads", &self.threads)
             .field("resolving", &self.resolving)
+            .field("local_address", &self.local_address)
+            .field("nodelay", &self.nodelay)
+            .field("keepalive", &self.keepalive)
             .finish()
     }
 }
@@ -87,8 +171,7 @@ impl Connect for HttpConnector {
         debug!("Http::connect({:?})", url);
         if let Some(key) = self.key(url) {
             let host = url.host_str().expect("http scheme must have a host");
-            self.dns.as_ref().expect("dns workers lost").resolve(host);
-            self.resolving.entry(host.to_owned()).or_insert_with(Vec::new).push(key.clone());
+            self.resolve(host, key.2, key.clone());
             Ok(key)
         } else {
             Err(io::Error::new(io::ErrorKind::InvalidInput, "scheme must be http"))
@@ -103,19 +186,17 @@ impl Connect for HttpConnector {
         //TODO: try all addrs
         let addr = addrs.and_then(|mut addrs| Ok(addrs.next().unwrap()));
         debug!("Http::resolved <- ({:?}, {:?})", host, addr);
-        if let Entry::Occupied(mut entry) = self.resolving.entry(host) {
+        let (resolved, port) = if let Entry::Occupied(mut entry) = self.resolving.entry(host) {
             let resolved = entry.get_mut().remove(0);
             if entry.get().is_empty() {
                 entry.remove();
             }
-            let port = resolved.2;
-            Some((resolved, addr.and_then(|addr| TcpStream::connect(&SocketAddr::new(addr, port))
-                                                            .map(HttpStream))
-                ))
+            resolved
         } else {
             trace!("^--  resolved but not in hashmap?");
-            None
-        }
+            return None;
+        };
+        Some((resolved, addr.and_then(|addr| self.connect_to(&SocketAddr::new(addr, port)))))
     }
 
     fn register(&mut self, reg: Registration) {
@@ -161,8 +242,7 @@ impl<S: SslClient> Connect for HttpsConnector<S> {
         debug!("Https::connect({:?})", url);
         if let Some(key) = self.key(url) {
             let host = url.host_str().expect("http scheme must have a host");
-            self.http.dns.as_ref().expect("dns workers lost").resolve(host);
-            self.http.resolving.entry(host.to_owned()).or_insert_with(Vec::new).push(key.clone());
+            self.http.resolve(host, key.2, key.clone());
             Ok(key)
         } else {
             Err(io::Error::new(io::ErrorKind::InvalidInput, "scheme must be http or https"))
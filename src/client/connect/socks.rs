@@ -0,0 +1,504 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+
+use url::Url;
+
+use net::{HttpStream, SslClient, Blocked};
+use super::{Connect, HttpConnector};
+use super::tunnel::{self, Handshake, Tunnel};
+use super::super::Registration;
+
+const VERSION: u8 = 0x05;
+const AUTH_VERSION: u8 = 0x01;
+
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_USERNAME_PASSWORD: u8 = 0x02;
+const METHOD_NONE_ACCEPTABLE: u8 = 0xff;
+
+const CMD_CONNECT: u8 = 0x01;
+
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// Describes a SOCKS5 proxy server.
+///
+/// # Example
+///
+/// ```
+/// use hyper::client::Socks5;
+///
+/// let socks = Socks5::new("localhost", 1080)
+///     .auth("Aladdin", "open sesame");
+/// ```
+#[derive(Clone)]
+pub struct Socks5 {
+    host: String,
+    port: u16,
+    auth: Option<(String, String)>,
+}
+
+impl Socks5 {
+    /// Create a `Socks5` for the proxy server at `host:port`.
+    pub fn new<H: Into<String>>(host: H, port: u16) -> Socks5 {
+        Socks5 {
+            host: host.into(),
+            port: port,
+            auth: None,
+        }
+    }
+
+    /// Authenticate with the proxy using a username and password, as defined
+    /// in [RFC1929](https://tools.ietf.org/html/rfc1929).
+    ///
+    /// Without credentials, only the "no authentication" method is offered.
+    pub fn auth<U, P>(mut self, username: U, password: P) -> Socks5
+    where U: Into<String>, P: Into<String> {
+        self.auth = Some((username.into(), password.into()));
+        self
+    }
+}
+
+impl fmt::Debug for Socks5 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Socks5")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("auth", &self.auth.as_ref().map(|&(ref user, _)| user))
+            .finish()
+    }
+}
+
+/// A connector that sends requests through a SOCKS5 proxy.
+///
+/// Host names are resolved by the proxy, not locally. Once the proxy has
+/// opened the connection, requests for `https` URLs are protected using the
+/// `SslClient`, just as the `HttpsConnector` would.
+pub struct SocksConnector<S: SslClient> {
+    http: HttpConnector,
+    socks: Socks5,
+    ssl: Arc<S>,
+}
+
+impl<S: SslClient> SocksConnector<S> {
+    /// Create a new connector using the `Socks5` proxy and the provided SSL
+    /// implementation.
+    pub fn new(socks: Socks5, ssl: S) -> SocksConnector<S> {
+        SocksConnector {
+            http: HttpConnector::default(),
+            socks: socks,
+            ssl: Arc::new(ssl),
+        }
+    }
+}
+
+impl<S: SslClient> fmt::Debug for SocksConnector<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SocksConnector")
+            .field("http", &self.http)
+            .field("socks", &self.socks)
+            .finish()
+    }
+}
+
+impl<S: SslClient> Connect for SocksConnector<S> {
+    type Output = Tunnel<S>;
+    type Key = (&'static str, String, u16);
+
+    fn key(&self, url: &Url) -> Option<Self::Key> {
+        let scheme = match url.scheme() {
+            "http" => "http",
+            "https" => "https",
+            _ => return None
+        };
+        Some((
+            scheme,
+            url.host_str().expect("http scheme must have host").to_owned(),
+            url.port_or_known_default().expect("http scheme must have a port"),
+        ))
+    }
+
+    fn connect(&mut self, url: &Url) -> io::Result<Self::Key> {
+        debug!("Socks::connect({:?})", url);
+        if let Some(key) = self.key(url) {
+            let host = self.socks.host.clone();
+            self.http.resolve(&host, self.socks.port, key.clone());
+            Ok(key)
+        } else {
+            Err(io::Error::new(io::ErrorKind::InvalidInput, "scheme must be http or https"))
+        }
+    }
+
+    fn connected(&mut self) -> Option<(Self::Key, io::Result<Self::Output>)> {
+        self.http.connected().map(|(key, res)| {
+            let res = res.and_then(|http| {
+                trace!("socks connecting to {}:{}", key.1, key.2);
+                let handshake = try!(Socks5Handshake::new(&self.socks, &key.1, key.2));
+                let tls = if key.0 == "https" {
+                    Some((self.ssl.clone(), key.1.clone()))
+                } else {
+                    None
+                };
+                Ok(tunnel::handshaking(http, Box::new(handshake), tls))
+            });
+            (key, res)
+        })
+    }
+
+    fn register(&mut self, reg: Registration) {
+        self.http.register(reg);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    Greeting,
+    Auth,
+    Connect,
+}
+
+/// The client side of a SOCKS5 handshake, as defined in
+/// [RFC1928](https://tools.ietf.org/html/rfc1928).
+///
+/// Each step writes a request and then reads exactly the proxy's reply, so
+/// that no bytes from the target server are consumed.
+struct Socks5Handshake {
+    step: Step,
+    auth: Option<(String, String)>,
+    connect: Vec<u8>,
+    out: Vec<u8>,
+    pos: usize,
+    reply: Vec<u8>,
+    blocked: Option<Blocked>,
+}
+
+impl Socks5Handshake {
+    fn new(socks: &Socks5, host: &str, port: u16) -> io::Result<Socks5Handshake> {
+        Ok(Socks5Handshake {
+            step: Step::Greeting,
+            auth: socks.auth.clone(),
+            connect: try!(connect_request(host, port)),
+            out: greeting(socks.auth.is_some()),
+            pos: 0,
+            reply: Vec::new(),
+            blocked: None,
+        })
+    }
+
+    /// The number of bytes of the current reply needed so far.
+    fn needed(&self) -> usize {
+        match self.step {
+            Step::Greeting | Step::Auth => 2,
+            Step::Connect => connect_reply_len(&self.reply),
+        }
+    }
+
+    /// Acts on a complete reply, returning whether the handshake is done.
+    fn next(&mut self) -> io::Result<bool> {
+        let reply = ::std::mem::replace(&mut self.reply, Vec::new());
+        match self.step {
+            Step::Greeting => {
+                if reply[0] != VERSION {
+                    return Err(invalid("invalid socks version"));
+                }
+                match (reply[1], self.auth.take()) {
+                    (METHOD_NO_AUTH, _) => self.send_connect(),
+                    (METHOD_USERNAME_PASSWORD, Some((user, pass))) => {
+                        self.out = try!(auth_request(&user, &pass));
+                        self.pos = 0;
+                        self.step = Step::Auth;
+                    },
+                    (METHOD_NONE_ACCEPTABLE, _) => {
+                        return Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                                                  "socks proxy accepted no authentication methods"));
+                    },
+                    (method, _) => {
+                        debug!("socks proxy chose unoffered method: {}", method);
+                        return Err(invalid("socks proxy chose unoffered method"));
+                    }
+                }
+                Ok(false)
+            },
+            Step::Auth => {
+                if reply[1] != 0x00 {
+                    return Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                                              "socks proxy rejected credentials"));
+                }
+                self.send_connect();
+                Ok(false)
+            },
+            Step::Connect => {
+                if reply[0] != VERSION {
+                    return Err(invalid("invalid socks version"));
+                }
+                match reply[1] {
+                    0x00 if ![ATYP_IPV4, ATYP_DOMAIN, ATYP_IPV6].contains(&reply[3]) => {
+                        Err(invalid("invalid socks address type"))
+                    },
+                    0x00 => Ok(true),
+                    code => {
+                        debug!("socks proxy refused connect: {}", code);
+                        Err(io::Error::new(io::ErrorKind::ConnectionRefused, reply_message(code)))
+                    }
+                }
+            }
+        }
+    }
+
+    fn send_connect(&mut self) {
+        self.out = ::std::mem::replace(&mut self.connect, Vec::new());
+        self.pos = 0;
+        self.step = Step::Connect;
+    }
+}
+
+impl Handshake for Socks5Handshake {
+    fn poll(&mut self, stream: &mut HttpStream) -> io::Result<()> {
+        self.blocked = None;
+        loop {
+            while self.pos < self.out.len() {
+                match stream.write(&self.out[self.pos..]) {
+                    Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero, "socks proxy closed connection")),
+                    Ok(n) => self.pos += n,
+                    Err(e) => {
+                        if e.kind() == io::ErrorKind::WouldBlock {
+                            self.blocked = Some(Blocked::Write);
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+
+            let mut buf = [0; 262];
+            loop {
+                let needed = self.needed();
+                if self.reply.len() >= needed {
+                    break;
+                }
+                let want = needed - self.reply.len();
+                match stream.read(&mut buf[..want]) {
+                    Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "socks proxy closed connection")),
+                    Ok(n) => self.reply.extend_from_slice(&buf[..n]),
+                    Err(e) => {
+                        if e.kind() == io::ErrorKind::WouldBlock {
+                            self.blocked = Some(Blocked::Read);
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+
+            if try!(self.next()) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn blocked(&self) -> Option<Blocked> {
+        self.blocked
+    }
+}
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn greeting(auth: bool) -> Vec<u8> {
+    if auth {
+        vec![VERSION, 2, METHOD_NO_AUTH, METHOD_USERNAME_PASSWORD]
+    } else {
+        vec![VERSION, 1, METHOD_NO_AUTH]
+    }
+}
+
+fn auth_request(user: &str, pass: &str) -> io::Result<Vec<u8>> {
+    if user.len() > 255 || pass.len() > 255 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "socks credentials too long"));
+    }
+    let mut req = Vec::with_capacity(3 + user.len() + pass.len());
+    req.push(AUTH_VERSION);
+    req.push(user.len() as u8);
+    req.extend_from_slice(user.as_bytes());
+    req.push(pass.len() as u8);
+    req.extend_from_slice(pass.as_bytes());
+    Ok(req)
+}
+
+/// The `CONNECT` request for `host:port`.
+///
+/// Host names are sent as-is, so that the proxy resolves them. Those longer
+/// than 255 bytes cannot be sent, and are an `InvalidInput` error.
+fn connect_request(host: &str, port: u16) -> io::Result<Vec<u8>> {
+    let mut req = vec![VERSION, CMD_CONNECT, 0x00];
+    let bracketed = host.starts_with('[') && host.ends_with(']');
+    if let Ok(ip) = host.parse::<Ipv4Addr>() {
+        req.push(ATYP_IPV4);
+        req.extend_from_slice(&ip.octets());
+    } else if let (true, Ok(ip)) = (bracketed, host.trim_matches(&['[', ']'][..]).parse::<Ipv6Addr>()) {
+        req.push(ATYP_IPV6);
+        for segment in &ip.segments() {
+            req.push((segment >> 8) as u8);
+            req.push(*segment as u8);
+        }
+    } else {
+        if host.len() > 255 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "socks host name too long"));
+        }
+        req.push(ATYP_DOMAIN);
+        req.push(host.len() as u8);
+        req.extend_from_slice(host.as_bytes());
+    }
+    req.push((port >> 8) as u8);
+    req.push(port as u8);
+    Ok(req)
+}
+
+/// The length of a `CONNECT` reply, from as much of it as has been read.
+fn connect_reply_len(reply: &[u8]) -> usize {
+    if reply.len() < 5 {
+        return 5;
+    }
+    match reply[3] {
+        ATYP_IPV4 => 4 + 4 + 2,
+        ATYP_IPV6 => 4 + 16 + 2,
+        ATYP_DOMAIN => 4 + 1 + reply[4] as usize + 2,
+        // unknown address types are reported once the reply is processed
+        _ => 5,
+    }
+}
+
+fn reply_message(code: u8) -> &'static str {
+    match code {
+        0x01 => "socks: general server failure",
+        0x02 => "socks: connection not allowed by ruleset",
+        0x03 => "socks: network unreachable",
+        0x04 => "socks: host unreachable",
+        0x05 => "socks: connection refused",
+        0x06 => "socks: TTL expired",
+        0x07 => "socks: command not supported",
+        0x08 => "socks: address type not supported",
+        _ => "socks: unknown failure",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    use rotor::mio::tcp::TcpStream;
+
+    use net::HttpStream;
+    use super::super::tunnel::Handshake;
+    use super::{Socks5, Socks5Handshake, connect_request, connect_reply_len};
+
+    #[test]
+    fn test_connect_request() {
+        assert_eq!(connect_request("hyper.rs", 443).unwrap(),
+                   b"\x05\x01\x00\x03\x08hyper.rs\x01\xbb".to_vec());
+        assert_eq!(connect_request("127.0.0.1", 80).unwrap(),
+                   b"\x05\x01\x00\x01\x7f\x00\x00\x01\x00\x50".to_vec());
+        assert_eq!(connect_request("[::1]", 80).unwrap(),
+                   b"\x05\x01\x00\x04\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\x01\x00\x50".to_vec());
+        let long = ::std::iter::repeat("a").take(256).collect::<String>();
+        assert_eq!(connect_request(&long, 80).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_connect_reply_len() {
+        assert_eq!(connect_reply_len(b"\x05\x00"), 5);
+        assert_eq!(connect_reply_len(b"\x05\x00\x00\x01\x7f"), 10);
+        assert_eq!(connect_reply_len(b"\x05\x00\x00\x04\x00"), 22);
+        assert_eq!(connect_reply_len(b"\x05\x00\x00\x03\x08"), 15);
+    }
+
+    /// Runs the handshake against a stand-in SOCKS server, which expects each
+    /// request of `exchanges` in turn, answering it with the paired reply.
+    fn handshake(socks: Socks5, exchanges: Vec<(Vec<u8>, Vec<u8>)>) -> io::Result<Vec<u8>> {
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let expected = exchanges.iter().map(|&(ref req, _)| req.clone()).collect::<Vec<_>>();
+        let stand_in = thread::spawn(move || {
+            let mut sock = server.accept().unwrap().0;
+            let mut received = Vec::new();
+            for (req, reply) in exchanges {
+                let mut buf = vec![0; req.len()];
+                sock.read_exact(&mut buf).unwrap();
+                received.push(buf);
+                sock.write_all(&reply).unwrap();
+            }
+            sock.write_all(b"HTTP/1.1 200 OK\r\n").unwrap();
+            received
+        });
+
+        let mut stream = HttpStream(TcpStream::connect(&addr).unwrap());
+        let mut hs = Socks5Handshake::new(&socks, "hyper.rs", 80).unwrap();
+        let mut result;
+        loop {
+            result = hs.poll(&mut stream);
+            match result {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    assert!(hs.blocked.is_some());
+                    thread::sleep(Duration::from_millis(5));
+                },
+                _ => break
+            }
+        }
+        assert_eq!(stand_in.join().unwrap(), expected);
+        result.map(|_| {
+            let mut rest = [0; 17];
+            let mut read = 0;
+            while read < rest.len() {
+                match stream.read(&mut rest[read..]) {
+                    Ok(n) => read += n,
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(Duration::from_millis(5));
+                    },
+                    Err(e) => panic!("read error: {}", e)
+                }
+            }
+            rest.to_vec()
+        })
+    }
+
+    #[test]
+    fn test_handshake_no_auth() {
+        let rest = handshake(Socks5::new("localhost", 1080), vec![
+            (b"\x05\x01\x00".to_vec(), b"\x05\x00".to_vec()),
+            (connect_request("hyper.rs", 80).unwrap(), b"\x05\x00\x00\x01\x7f\x00\x00\x01\x1f\x90".to_vec()),
+        ]).unwrap();
+        assert_eq!(rest, b"HTTP/1.1 200 OK\r\n");
+    }
+
+    #[test]
+    fn test_handshake_auth() {
+        let rest = handshake(Socks5::new("localhost", 1080).auth("foo", "bar"), vec![
+            (b"\x05\x02\x00\x02".to_vec(), b"\x05\x02".to_vec()),
+            (b"\x01\x03foo\x03bar".to_vec(), b"\x01\x00".to_vec()),
+            (connect_request("hyper.rs", 80).unwrap(), b"\x05\x00\x00\x03\x08hyper.rs\x00\x50".to_vec()),
+        ]).unwrap();
+        assert_eq!(rest, b"HTTP/1.1 200 OK\r\n");
+    }
+
+    #[test]
+    fn test_handshake_refused() {
+        let err = handshake(Socks5::new("localhost", 1080), vec![
+            (b"\x05\x01\x00".to_vec(), b"\x05\x00".to_vec()),
+            (connect_request("hyper.rs", 80).unwrap(), b"\x05\x05\x00\x01\x00\x00\x00\x00\x00\x00".to_vec()),
+        ]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+    }
+
+    #[test]
+    fn test_handshake_auth_rejected() {
+        let err = handshake(Socks5::new("localhost", 1080).auth("foo", "bar"), vec![
+            (b"\x05\x02\x00\x02".to_vec(), b"\x05\x02".to_vec()),
+            (b"\x01\x03foo\x03bar".to_vec(), b"\x01\x01".to_vec()),
+        ]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
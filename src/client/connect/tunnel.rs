@@ -0,0 +1,216 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::sync::Arc;
+
+use rotor::mio::{Selector, Token, Evented, EventSet, PollOpt};
+
+use net::{HttpStream, Transport, SslClient, Blocked};
+
+/// A handshake with a proxy, run over a new connection before it can carry
+/// any HTTP.
+pub trait Handshake {
+    /// Advances the handshake, returning `Ok(())` once it is complete.
+    ///
+    /// While incomplete, a `WouldBlock` error is returned, and `blocked()`
+    /// reports which event the handshake is waiting on.
+    fn poll(&mut self, stream: &mut HttpStream) -> io::Result<()>;
+    /// Returns what the handshake is blocked on, if anything.
+    fn blocked(&self) -> Option<Blocked>;
+}
+
+/// A stream opened through a proxy, possibly protected by TLS.
+///
+/// While the proxy handshake is in progress, reads and writes drive the
+/// handshake, returning `WouldBlock` until the stream is ready. Streams
+/// protected with TLS are wrapped using the `SslClient` once the proxy has
+/// opened the tunnel.
+pub struct Tunnel<S: SslClient> {
+    state: State<S>,
+}
+
+enum State<S: SslClient> {
+    Handshaking {
+        stream: HttpStream,
+        handshake: Box<Handshake>,
+        tls: Option<(Arc<S>, String)>,
+    },
+    Http(HttpStream),
+    Https(S::Stream),
+    Closed,
+}
+
+/// A `Tunnel` that must complete `handshake` before it is usable.
+///
+/// If `tls` is set, the stream is then wrapped with the `SslClient` for the
+/// host name.
+pub fn handshaking<S: SslClient>(stream: HttpStream, handshake: Box<Handshake>, tls: Option<(Arc<S>, String)>) -> Tunnel<S> {
+    Tunnel {
+        state: State::Handshaking {
+            stream: stream,
+            handshake: handshake,
+            tls: tls,
+        }
+    }
+}
+
+/// A `Tunnel` that is a plain stream, ready to use.
+pub fn http<S: SslClient>(stream: HttpStream) -> Tunnel<S> {
+    Tunnel {
+        state: State::Http(stream)
+    }
+}
+
+/// A `Tunnel` that is already protected by TLS.
+pub fn https<S: SslClient>(stream: S::Stream) -> Tunnel<S> {
+    Tunnel {
+        state: State::Https(stream)
+    }
+}
+
+pub fn into_io(err: ::Error) -> io::Error {
+    match err {
+        ::Error::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::Other, e)
+    }
+}
+
+fn closed() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "tunnel closed")
+}
+
+impl<S: SslClient> fmt::Debug for Tunnel<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = match self.state {
+            State::Handshaking { .. } => "Handshaking",
+            State::Http(..) => "Http",
+            State::Https(..) => "Https",
+            State::Closed => "Closed",
+        };
+        f.debug_struct("Tunnel")
+            .field("state", &state)
+            .finish()
+    }
+}
+
+impl<S: SslClient> Tunnel<S> {
+    fn advance(&mut self) -> io::Result<()> {
+        match self.state {
+            State::Handshaking { ref mut stream, ref mut handshake, .. } => try!(handshake.poll(stream)),
+            State::Closed => return Err(closed()),
+            _ => return Ok(())
+        }
+        trace!("tunnel established");
+        self.state = match mem::replace(&mut self.state, State::Closed) {
+            State::Handshaking { stream, tls: Some((ssl, host)), .. } => {
+                State::Https(try!(ssl.wrap_client(stream, &host).map_err(into_io)))
+            },
+            State::Handshaking { stream, tls: None, .. } => State::Http(stream),
+            _ => unreachable!("tunnel must be handshaking")
+        };
+        Ok(())
+    }
+}
+
+impl<S: SslClient> Read for Tunnel<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        try!(self.advance());
+        match self.state {
+            State::Http(ref mut s) => s.read(buf),
+            State::Https(ref mut s) => s.read(buf),
+            _ => Err(closed())
+        }
+    }
+}
+
+impl<S: SslClient> Write for Tunnel<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        try!(self.advance());
+        match self.state {
+            State::Http(ref mut s) => s.write(buf),
+            State::Https(ref mut s) => s.write(buf),
+            _ => Err(closed())
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.state {
+            State::Handshaking { ref mut stream, .. } => stream.flush(),
+            State::Http(ref mut s) => s.flush(),
+            State::Https(ref mut s) => s.flush(),
+            State::Closed => Err(closed())
+        }
+    }
+}
+
+#[cfg(not(windows))]
+impl<S: SslClient> ::vecio::Writev for Tunnel<S> {
+    fn writev(&mut self, bufs: &[&[u8]]) -> io::Result<usize> {
+        try!(self.advance());
+        match self.state {
+            State::Http(ref mut s) => s.writev(bufs),
+            State::Https(ref mut s) => s.writev(bufs),
+            _ => Err(closed())
+        }
+    }
+}
+
+impl<S: SslClient> Evented for Tunnel<S> {
+    #[inline]
+    fn register(&self, selector: &mut Selector, token: Token, interest: EventSet, opts: PollOpt) -> io::Result<()> {
+        match self.state {
+            State::Handshaking { ref stream, .. } => stream.register(selector, token, interest, opts),
+            State::Http(ref s) => s.register(selector, token, interest, opts),
+            State::Https(ref s) => s.register(selector, token, interest, opts),
+            State::Closed => Err(closed())
+        }
+    }
+
+    #[inline]
+    fn reregister(&self, selector: &mut Selector, token: Token, interest: EventSet, opts: PollOpt) -> io::Result<()> {
+        match self.state {
+            State::Handshaking { ref stream, .. } => stream.reregister(selector, token, interest, opts),
+            State::Http(ref s) => s.reregister(selector, token, interest, opts),
+            State::Https(ref s) => s.reregister(selector, token, interest, opts),
+            State::Closed => Err(closed())
+        }
+    }
+
+    #[inline]
+    fn deregister(&self, selector: &mut Selector) -> io::Result<()> {
+        match self.state {
+            State::Handshaking { ref stream, .. } => stream.deregister(selector),
+            State::Http(ref s) => s.deregister(selector),
+            State::Https(ref s) => s.deregister(selector),
+            State::Closed => Err(closed())
+        }
+    }
+}
+
+impl<S: SslClient> Transport for Tunnel<S> {
+    fn take_socket_error(&mut self) -> io::Result<()> {
+        match self.state {
+            State::Handshaking { ref mut stream, .. } => stream.take_socket_error(),
+            State::Http(ref mut s) => s.take_socket_error(),
+            State::Https(ref mut s) => s.take_socket_error(),
+            State::Closed => Err(closed())
+        }
+    }
+
+    fn blocked(&self) -> Option<Blocked> {
+        match self.state {
+            State::Handshaking { ref handshake, .. } => handshake.blocked(),
+            State::Http(ref s) => s.blocked(),
+            State::Https(ref s) => s.blocked(),
+            State::Closed => None
+        }
+    }
+
+    fn handshake(&mut self) -> io::Result<()> {
+        try!(self.advance());
+        match self.state {
+            State::Https(ref mut s) => s.handshake(),
+            _ => Ok(())
+        }
+    }
+}
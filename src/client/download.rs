@@ -0,0 +1,273 @@
+//! Resumable downloads
+//!
+//! A `Download` copies a resource into a `Write + Seek` sink with a
+//! blocking `Client`. If the connection fails part way, the download is
+//! resumed from the last byte received, with a `Range` request guarded by
+//! `IfRange`, so that the rest is only appended if the resource has not
+//! changed in between.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use hyper::client::blocking::Client;
+//!
+//! let client = Client::new().unwrap();
+//! let mut file = File::create("archive.tar").unwrap();
+//! let len = client.download("http://example.domain/archive.tar".parse().unwrap())
+//!     .retries(5)
+//!     .to(&mut file)
+//!     .unwrap();
+//! println!("downloaded {} bytes", len);
+//! ```
+
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::time::Duration;
+
+use header::{ByteRangeSpec, ContentLength, ContentRange, ContentRangeSpec, ETag, Header,
+             Headers, IfRange, LastModified, Range};
+use status::StatusCode;
+use Url;
+use super::blocking::{Client, Response};
+
+/// The default number of times a failed download is resumed.
+pub const DEFAULT_RETRIES: usize = 3;
+
+impl Client {
+    /// Build a `Download` of `url`.
+    pub fn download(&self, url: Url) -> Download {
+        Download {
+            client: self,
+            url: url,
+            headers: Headers::new(),
+            retries: DEFAULT_RETRIES,
+            timeout: None,
+        }
+    }
+}
+
+/// A resumable `GET` request, copying the response body into a sink.
+pub struct Download<'a> {
+    client: &'a Client,
+    url: Url,
+    headers: Headers,
+    retries: usize,
+    timeout: Option<Duration>,
+}
+
+/// What a failed attempt leaves to do.
+enum Failed {
+    /// The connection failed, and the download may be resumed.
+    Resume(::Error),
+    /// The download can't continue.
+    Fatal(::Error),
+}
+
+impl<'a> Download<'a> {
+    /// Set a header on every request of the download.
+    pub fn header<H: Header>(mut self, header: H) -> Download<'a> {
+        self.headers.set(header);
+        self
+    }
+
+    /// Set how many times the download is resumed after a failure.
+    ///
+    /// Default is `DEFAULT_RETRIES`.
+    pub fn retries(mut self, retries: usize) -> Download<'a> {
+        self.retries = retries;
+        self
+    }
+
+    /// Set a timeout for each wait on the connection, reading or writing.
+    pub fn timeout(mut self, timeout: Duration) -> Download<'a> {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Download the resource into `sink`, starting at its current position.
+    ///
+    /// Returns the length of the resource. If the resource changes while a
+    /// download is resumed, it starts over at the same position, so a sink
+    /// that already held more bytes than that should be truncated to it.
+    pub fn to<W: Write + Seek>(self, sink: &mut W) -> ::Result<u64> {
+        let start = try!(sink.seek(SeekFrom::Current(0)));
+        let mut state = State {
+            start: start,
+            received: 0,
+            total: None,
+            validator: None,
+        };
+        let mut retries = self.retries;
+        loop {
+            match self.attempt(sink, &mut state) {
+                Ok(()) => return Ok(state.received),
+                Err(Failed::Resume(e)) => {
+                    if retries == 0 {
+                        return Err(e);
+                    }
+                    retries -= 1;
+                    debug!("download of {} failed at byte {}, resuming: {:?}", self.url, state.received, e);
+                },
+                Err(Failed::Fatal(e)) => return Err(e)
+            }
+        }
+    }
+
+    /// Sends one request, copying its response body into `sink`.
+    fn attempt<W: Write + Seek>(&self, sink: &mut W, state: &mut State) -> Result<(), Failed> {
+        let mut req = self.client.get(self.url.clone()).headers(self.headers.clone());
+        if let Some(timeout) = self.timeout {
+            req = req.timeout(timeout);
+        }
+        let resuming = state.received > 0 && state.validator.is_some();
+        if resuming {
+            req = req.header(Range::Bytes(vec![ByteRangeSpec::AllFrom(state.received)]));
+            req = req.header(state.validator.clone().expect("resuming requires a validator"));
+        }
+        let mut res = try!(req.send().map_err(Failed::Resume));
+
+        match *res.status() {
+            StatusCode::PartialContent if resuming => {
+                let checked = match res.headers().get::<ContentRange>() {
+                    Some(range) => state.check(range),
+                    None => Err(invalid("partial content without a Content-Range".to_owned()))
+                };
+                try!(checked.map_err(Failed::Fatal));
+            },
+            StatusCode::Ok => {
+                if state.received > 0 {
+                    debug!("download of {} starting over", self.url);
+                    state.received = 0;
+                }
+                state.total = res.headers().get::<ContentLength>().map(|len| len.0);
+                state.validator = validator(res.headers());
+            },
+            ref status => {
+                return Err(Failed::Fatal(invalid(format!("download failed with status {}", status))));
+            }
+        }
+
+        try!(sink.seek(SeekFrom::Start(state.start + state.received))
+            .map_err(|e| Failed::Fatal(::Error::Io(e))));
+        try!(copy(&mut res, sink, state));
+        match state.total {
+            Some(total) if state.received < total => {
+                Err(Failed::Resume(::Error::Io(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                                               "download ended early"))))
+            },
+            _ => Ok(())
+        }
+    }
+}
+
+impl<'a> fmt::Debug for Download<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Download")
+            .field("url", &self.url)
+            .field("headers", &self.headers)
+            .field("retries", &self.retries)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// The progress of a `Download`.
+struct State {
+    start: u64,
+    received: u64,
+    total: Option<u64>,
+    validator: Option<IfRange>,
+}
+
+impl State {
+    /// Checks that the `ContentRange` of a `206 Partial Content` continues
+    /// where the download stopped.
+    fn check(&mut self, range: &ContentRange) -> ::Result<()> {
+        match *range {
+            ContentRange(ContentRangeSpec::Bytes { range: Some((first, last)), instance_length }) => {
+                if first != self.received || last < first {
+                    return Err(invalid(format!("expected range from byte {}, got {}-{}",
+                                               self.received, first, last)));
+                }
+                if let (Some(total), Some(length)) = (self.total, instance_length) {
+                    if total != length {
+                        return Err(invalid(format!("resource length changed from {} to {}", total, length)));
+                    }
+                }
+                self.total = instance_length.or(Some(last + 1));
+                Ok(())
+            },
+            _ => Err(invalid(format!("unexpected Content-Range: {}", range)))
+        }
+    }
+}
+
+/// The `IfRange` to resume a download with, if the response has a validator
+/// strong enough.
+fn validator(headers: &Headers) -> Option<IfRange> {
+    match headers.get::<ETag>() {
+        Some(&ETag(ref tag)) if !tag.weak => Some(IfRange::EntityTag(tag.clone())),
+        _ => headers.get::<LastModified>().map(|date| IfRange::Date(date.0))
+    }
+}
+
+fn copy<W: Write>(res: &mut Response, sink: &mut W, state: &mut State) -> Result<(), Failed> {
+    let mut buf = [0; 8192];
+    loop {
+        let n = match res.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(Failed::Resume(::Error::Io(e)))
+        };
+        try!(sink.write_all(&buf[..n]).map_err(|e| Failed::Fatal(::Error::Io(e))));
+        state.received += n as u64;
+    }
+}
+
+fn invalid(msg: String) -> ::Error {
+    ::Error::Io(io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use header::{ContentRange, ContentRangeSpec, EntityTag, ETag, Headers, HttpDate, IfRange,
+                 LastModified};
+    use time;
+    use super::{State, validator};
+
+    #[test]
+    fn test_download_validator() {
+        let date = HttpDate(time::at_utc(time::Timespec::new(1_000_000, 0)));
+        let mut headers = Headers::new();
+        assert_eq!(validator(&headers), None);
+        headers.set(LastModified(date));
+        assert_eq!(validator(&headers), Some(IfRange::Date(date)));
+        headers.set(ETag(EntityTag::weak("v1".to_owned())));
+        assert_eq!(validator(&headers), Some(IfRange::Date(date)));
+        headers.set(ETag(EntityTag::strong("v1".to_owned())));
+        assert_eq!(validator(&headers), Some(IfRange::EntityTag(EntityTag::strong("v1".to_owned()))));
+    }
+
+    #[test]
+    fn test_download_content_range() {
+        let state = |received| State {
+            start: 0,
+            received: received,
+            total: Some(100),
+            validator: None,
+        };
+        let range = |first, last, length| ContentRange(ContentRangeSpec::Bytes {
+            range: Some((first, last)),
+            instance_length: length,
+        });
+        assert!(state(40).check(&range(40, 99, Some(100))).is_ok());
+        assert!(state(40).check(&range(30, 99, Some(100))).is_err());
+        assert!(state(40).check(&range(40, 119, Some(120))).is_err());
+        let mut unknown = state(40);
+        unknown.total = None;
+        assert!(unknown.check(&range(40, 99, None)).is_ok());
+        assert_eq!(unknown.total, Some(100));
+    }
+}
@@ -0,0 +1,365 @@
+//! Buffered requests
+//!
+//! A `Fetch` is a `Handler` that writes a whole request body, and buffers
+//! the whole response body, up to a limit. It completes once, either by
+//! resolving a `ResponseFuture` or by calling a callback, which makes
+//! sending many requests at once and waiting for them all straightforward.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use hyper::Client;
+//! use hyper::client::fetch::Fetch;
+//!
+//! let client = Client::<Fetch>::new().unwrap();
+//! let pending = vec![
+//!     client.get("http://example.domain/a".parse().unwrap()),
+//!     client.get("http://example.domain/b".parse().unwrap()),
+//! ];
+//! for future in pending {
+//!     let (res, body) = future.wait().unwrap();
+//!     println!("{}: {} bytes", res.status(), body.len());
+//! }
+//! ```
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use form::Form;
+use header::{Header, Headers, ContentLength};
+use http::{Decoder, Encoder, Next};
+use method::Method;
+use net::Transport;
+use status::StatusCode;
+use Url;
+use super::{Cache, Client, Credentials, Handler, Request, Response};
+use super::cache::{self, Lookup, Pending};
+
+/// The default limit of a buffered response body, 10MB.
+pub const DEFAULT_LIMIT: usize = 10 * 1024 * 1024;
+
+/// The result of a `Fetch`: the response head, and its whole body.
+pub type FetchResult = ::Result<(Response, Vec<u8>)>;
+
+impl Client<Fetch> {
+    /// Send a `GET` request, buffering the response.
+    pub fn get(&self, url: Url) -> ResponseFuture {
+        self.fetch(url, Fetch::new(Method::Get))
+    }
+
+    /// Send a `Fetch` request, resolving the returned `ResponseFuture` once
+    /// the response body has been buffered.
+    pub fn fetch(&self, url: Url, fetch: Fetch) -> ResponseFuture {
+        let (tx, rx) = mpsc::channel();
+        self.fetch_with(url, fetch, move |res| {
+            let _ = tx.send(res);
+        });
+        ResponseFuture {
+            rx: rx
+        }
+    }
+
+    /// Send a `Fetch` request, calling `callback` once the response body has
+    /// been buffered, or an error occurs.
+    ///
+    /// The callback is called on the event loop thread, so should not block.
+    pub fn fetch_with<F>(&self, url: Url, mut fetch: Fetch, callback: F)
+    where F: FnOnce(FetchResult) + Send + 'static {
+        if let Some(credentials) = fetch.credentials.take() {
+            // the Fetch is used up by sending it, so keep a copy to send
+            // again with the answer to a challenge
+            let mut again = fetch.again();
+            let client = self.clone();
+            let again_url = url.clone();
+            return self.fetch_with(url, fetch, move |res| {
+                if let Ok((ref res, _)) = res {
+                    if credentials.answer(res, &again.method, &again_url, &mut again.headers) {
+                        debug!("fetch answering {} challenge", res.status());
+                        return client.fetch_with(again_url, again, callback);
+                    }
+                }
+                callback(res)
+            });
+        }
+        let mut callback = Some(callback);
+        fetch.complete = Some(Box::new(move |res| {
+            if let Some(callback) = callback.take() {
+                callback(res);
+            }
+        }));
+        if let Some(cache) = fetch.cache.take() {
+            match cache::lookup(&cache, &fetch.method, &url, &mut fetch.headers) {
+                Lookup::Hit(res, body) => return fetch.complete(Ok((res, body))),
+                Lookup::Send(pending) => fetch.pending = Some(pending)
+            }
+        }
+        if let Err(e) = self.request(url, fetch) {
+            // dropping the recovered Fetch completes it with an error
+            debug!("fetch could not be queued: {}", e);
+        }
+    }
+}
+
+/// A request whose response is buffered in full.
+pub struct Fetch {
+    method: Method,
+    headers: Headers,
+    body: Vec<u8>,
+    pos: usize,
+    limit: usize,
+    timeout: Option<Duration>,
+    response: Option<Response>,
+    buf: Vec<u8>,
+    cache: Option<Cache>,
+    credentials: Option<Credentials>,
+    pending: Option<Pending>,
+    complete: Option<Box<FnMut(FetchResult) + Send>>,
+}
+
+impl Fetch {
+    /// Create a `Fetch` with the `Method`, and no body.
+    pub fn new(method: Method) -> Fetch {
+        Fetch {
+            method: method,
+            headers: Headers::new(),
+            body: Vec::new(),
+            pos: 0,
+            limit: DEFAULT_LIMIT,
+            timeout: None,
+            response: None,
+            buf: Vec::new(),
+            cache: None,
+            credentials: None,
+            pending: None,
+            complete: None,
+        }
+    }
+
+    /// Set a header on the request.
+    pub fn header<H: Header>(mut self, header: H) -> Fetch {
+        self.headers.set(header);
+        self
+    }
+
+    /// Set the request body.
+    ///
+    /// A `ContentLength` header is set to its length.
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Fetch {
+        self.body = body.into();
+        self.headers.set(ContentLength(self.body.len() as u64));
+        self
+    }
+
+    /// Set the request body to a urlencoded `Form`.
+    ///
+    /// The `ContentType` and `ContentLength` headers are set to match.
+    pub fn form(mut self, form: &Form) -> Fetch {
+        self.body = form.to_body(&mut self.headers);
+        self
+    }
+
+    /// Set the largest response body to buffer.
+    ///
+    /// A longer body fails the request with `Error::TooLarge`.
+    ///
+    /// Default is `DEFAULT_LIMIT`.
+    pub fn limit(mut self, limit: usize) -> Fetch {
+        self.limit = limit;
+        self
+    }
+
+    /// Set a timeout for each wait on the connection, reading or writing.
+    pub fn timeout(mut self, timeout: Duration) -> Fetch {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Use a `Cache` for this request.
+    ///
+    /// A fresh stored response completes the request without sending it,
+    /// and a stale one is revalidated.
+    pub fn cache(mut self, cache: &Cache) -> Fetch {
+        self.cache = Some(cache.clone());
+        self
+    }
+
+    /// Answer a 401 or 407 response by sending the request once more, with
+    /// `Credentials`.
+    ///
+    /// If the response can't be answered, it completes the request as
+    /// usual.
+    pub fn credentials(mut self, credentials: &Credentials) -> Fetch {
+        self.credentials = Some(credentials.clone());
+        self
+    }
+
+    /// A copy of the request, to send again.
+    fn again(&self) -> Fetch {
+        let mut fetch = Fetch::new(self.method.clone());
+        fetch.headers = self.headers.clone();
+        fetch.body = self.body.clone();
+        fetch.limit = self.limit;
+        fetch.timeout = self.timeout;
+        fetch.cache = self.cache.clone();
+        fetch
+    }
+
+    fn next(&self, next: Next) -> Next {
+        match self.timeout {
+            Some(timeout) => next.timeout(timeout),
+            None => next
+        }
+    }
+
+    fn complete(&mut self, res: FetchResult) {
+        if let (Some(pending), &Ok((ref res, ref body))) = (self.pending.as_mut(), &res) {
+            pending.store(res, body);
+        }
+        if let Some(mut complete) = self.complete.take() {
+            complete(res);
+        }
+    }
+
+    fn fail(&mut self, err: ::Error) -> Next {
+        self.complete(Err(err));
+        Next::remove()
+    }
+}
+
+impl Drop for Fetch {
+    fn drop(&mut self) {
+        // a Fetch can be dropped without an error, such as when the event
+        // loop shuts down
+        self.complete(Err(aborted()));
+    }
+}
+
+impl fmt::Debug for Fetch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Fetch")
+            .field("method", &self.method)
+            .field("headers", &self.headers)
+            .field("body", &self.body.len())
+            .field("limit", &self.limit)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl<T: Transport> Handler<T> for Fetch {
+    fn on_request(&mut self, req: &mut Request) -> Next {
+        req.set_method(self.method.clone());
+        req.headers_mut().extend(self.headers.iter());
+        if let Some(ref mut pending) = self.pending {
+            pending.start();
+        }
+        if self.body.is_empty() {
+            self.next(Next::read())
+        } else {
+            self.next(Next::write())
+        }
+    }
+
+    fn on_request_writable(&mut self, encoder: &mut Encoder<T>) -> Next {
+        while self.pos < self.body.len() {
+            match encoder.write(&self.body[self.pos..]) {
+                Ok(n) => self.pos += n,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return self.next(Next::write()),
+                Err(e) => return self.fail(::Error::Io(e))
+            }
+        }
+        encoder.close();
+        self.next(Next::read())
+    }
+
+    fn on_response(&mut self, res: Response) -> Next {
+        if let Some(revalidated) = self.pending.as_mut().and_then(|pending| pending.not_modified(&res)) {
+            self.pending = None;
+            self.complete(Ok(revalidated));
+            return Next::end();
+        }
+        let len = res.headers().get::<ContentLength>().map(|len| **len);
+        let has_body = match *res.status() {
+            StatusCode::NoContent | StatusCode::NotModified => false,
+            _ => self.method != Method::Head && len != Some(0)
+        };
+        if !has_body {
+            self.complete(Ok((res, Vec::new())));
+            return Next::end();
+        }
+        if let Some(len) = len {
+            if len > self.limit as u64 {
+                debug!("response body of {} bytes is over the limit of {}", len, self.limit);
+                return self.fail(::Error::TooLarge);
+            }
+            self.buf.reserve(len as usize);
+        }
+        self.response = Some(res);
+        self.next(Next::read())
+    }
+
+    fn on_response_readable(&mut self, decoder: &mut Decoder<T>) -> Next {
+        let mut chunk = [0; 4096];
+        loop {
+            match decoder.read(&mut chunk) {
+                Ok(0) => {
+                    let res = self.response.take().expect("response head must be received first");
+                    let body = mem::replace(&mut self.buf, Vec::new());
+                    self.complete(Ok((res, body)));
+                    return Next::end();
+                },
+                Ok(n) => {
+                    if self.buf.len() + n > self.limit {
+                        debug!("response body is over the limit of {}", self.limit);
+                        return self.fail(::Error::TooLarge);
+                    }
+                    self.buf.extend_from_slice(&chunk[..n]);
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return self.next(Next::read()),
+                Err(e) => return self.fail(::Error::Io(e))
+            }
+        }
+    }
+
+    fn on_error(&mut self, err: ::Error) -> Next {
+        self.fail(err)
+    }
+
+    fn on_retry(&mut self) -> bool {
+        // the whole body is kept, so it can be written again
+        self.pos = 0;
+        true
+    }
+}
+
+/// The pending result of a `Fetch`.
+#[derive(Debug)]
+pub struct ResponseFuture {
+    rx: mpsc::Receiver<FetchResult>,
+}
+
+impl ResponseFuture {
+    /// Block until the `Fetch` completes.
+    pub fn wait(self) -> FetchResult {
+        self.rx.recv().unwrap_or_else(|_| Err(aborted()))
+    }
+
+    /// Check if the `Fetch` has completed, without blocking.
+    ///
+    /// Returns `Some` once, when the result is ready. After that, it returns
+    /// an error.
+    pub fn poll(&mut self) -> Option<FetchResult> {
+        match self.rx.try_recv() {
+            Ok(res) => Some(res),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err(aborted()))
+        }
+    }
+}
+
+fn aborted() -> ::Error {
+    ::Error::Io(io::Error::new(io::ErrorKind::ConnectionAborted, "request aborted"))
+}
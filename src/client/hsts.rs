@@ -0,0 +1,259 @@
+//! HTTP Strict Transport Security
+//!
+//! An `Hsts` store keeps the hosts that asked, with a
+//! `StrictTransportSecurity` header over HTTPS, to only be reached over
+//! HTTPS. A `Client` configured with one sends requests for `http://` URLs
+//! of those hosts to `https://` instead, as described in
+//! [RFC6797](https://tools.ietf.org/html/rfc6797).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+use time;
+
+use header::StrictTransportSecurity;
+use Url;
+
+/// A host known to require HTTPS.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HstsEntry {
+    /// Whether the policy covers subdomains of the host too.
+    pub include_subdomains: bool,
+    /// When the policy ends, in seconds since the epoch, or `None` for an
+    /// entry that was seeded, which doesn't end.
+    pub expires: Option<i64>,
+}
+
+/// A store of hosts known to require HTTPS, shared by clones.
+#[derive(Clone)]
+pub struct Hsts {
+    hosts: Arc<Mutex<HashMap<String, HstsEntry>>>,
+}
+
+impl Hsts {
+    /// Create an empty `Hsts` store.
+    pub fn new() -> Hsts {
+        Hsts {
+            hosts: Arc::new(Mutex::new(HashMap::new()))
+        }
+    }
+
+    /// Add a host that always requires HTTPS, such as from a preload list.
+    pub fn seed(&self, host: &str, include_subdomains: bool) {
+        self.insert(host, HstsEntry {
+            include_subdomains: include_subdomains,
+            expires: None,
+        });
+    }
+
+    /// Record the `StrictTransportSecurity` header of a response from
+    /// `host`, received over HTTPS.
+    ///
+    /// A `max-age` of 0 removes the host. Hosts that are IP addresses are
+    /// ignored.
+    pub fn record(&self, host: &str, sts: &StrictTransportSecurity) {
+        if host.parse::<IpAddr>().is_ok() || host.starts_with('[') {
+            return;
+        }
+        if sts.max_age == 0 {
+            trace!("hsts removing {}", host);
+            self.lock().remove(&normalize(host));
+            return;
+        }
+        let max_age = if sts.max_age > i64::max_value() as u64 { i64::max_value() } else { sts.max_age as i64 };
+        self.insert(host, HstsEntry {
+            include_subdomains: sts.include_subdomains,
+            expires: Some(now().saturating_add(max_age)),
+        });
+    }
+
+    /// Whether `host` must be reached over HTTPS.
+    pub fn is_known(&self, host: &str) -> bool {
+        let host = normalize(host);
+        let now = now();
+        let mut hosts = self.lock();
+        let mut domain = &host[..];
+        let mut exact = true;
+        loop {
+            let known = match hosts.get(domain) {
+                Some(entry) if entry.expires.map_or(true, |at| at > now) => {
+                    Some(exact || entry.include_subdomains)
+                },
+                Some(_) => None,
+                None => Some(false)
+            };
+            match known {
+                Some(true) => return true,
+                Some(false) => (),
+                None => {
+                    let expired = domain.to_owned();
+                    hosts.remove(&expired);
+                }
+            }
+            match domain.find('.') {
+                Some(dot) => {
+                    domain = &domain[dot + 1..];
+                    exact = false;
+                },
+                None => return false
+            }
+        }
+    }
+
+    /// Change an `http://` URL of a known host to `https://`, returning
+    /// whether it was changed.
+    ///
+    /// An explicit port 80 becomes the default port of HTTPS, as `Url`
+    /// already leaves out the default port of HTTP.
+    pub fn upgrade(&self, url: &mut Url) -> bool {
+        if url.scheme() != "http" {
+            return false;
+        }
+        let known = url.host_str().map_or(false, |host| self.is_known(host));
+        if !known {
+            return false;
+        }
+        if url.set_scheme("https").is_err() {
+            return false;
+        }
+        debug!("hsts upgraded request to {}", url);
+        true
+    }
+
+    /// Write the entries that have not expired, one per line, to be read
+    /// back with `Hsts::load`.
+    pub fn save<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let now = now();
+        for (host, entry) in self.lock().iter() {
+            if entry.expires.map_or(false, |at| at <= now) {
+                continue;
+            }
+            let expires = entry.expires.map_or("-".to_owned(), |at| at.to_string());
+            let subdomains = if entry.include_subdomains { "includeSubDomains" } else { "-" };
+            try!(writeln!(w, "{} {} {}", host, expires, subdomains));
+        }
+        Ok(())
+    }
+
+    /// Read entries written by `Hsts::save`.
+    pub fn load<R: Read>(r: R) -> io::Result<Hsts> {
+        let hsts = Hsts::new();
+        for line in BufReader::new(r).lines() {
+            let line = try!(line);
+            let parts = line.split_whitespace().collect::<Vec<_>>();
+            if parts.is_empty() {
+                continue;
+            }
+            if parts.len() != 3 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid hsts line: {:?}", line)));
+            }
+            let expires = match parts[1] {
+                "-" => None,
+                at => Some(try!(at.parse().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("invalid hsts expiry: {:?}", at))
+                })))
+            };
+            hsts.insert(parts[0], HstsEntry {
+                include_subdomains: parts[2] == "includeSubDomains",
+                expires: expires,
+            });
+        }
+        Ok(hsts)
+    }
+
+    fn insert(&self, host: &str, entry: HstsEntry) {
+        trace!("hsts recording {}: {:?}", host, entry);
+        self.lock().insert(normalize(host), entry);
+    }
+
+    fn lock(&self) -> ::std::sync::MutexGuard<HashMap<String, HstsEntry>> {
+        match self.hosts.lock() {
+            Ok(hosts) => hosts,
+            Err(poisoned) => poisoned.into_inner()
+        }
+    }
+}
+
+impl Default for Hsts {
+    fn default() -> Hsts {
+        Hsts::new()
+    }
+}
+
+impl fmt::Debug for Hsts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Hsts")
+            .field("hosts", &self.lock().len())
+            .finish()
+    }
+}
+
+fn normalize(host: &str) -> String {
+    host.trim_right_matches('.').to_lowercase()
+}
+
+fn now() -> i64 {
+    time::get_time().sec
+}
+
+#[cfg(test)]
+mod tests {
+    use header::StrictTransportSecurity;
+    use Url;
+    use super::Hsts;
+
+    fn sts(max_age: u64, include_subdomains: bool) -> StrictTransportSecurity {
+        StrictTransportSecurity {
+            include_subdomains: include_subdomains,
+            max_age: max_age,
+        }
+    }
+
+    #[test]
+    fn test_hsts_known() {
+        let hsts = Hsts::new();
+        hsts.record("Example.domain", &sts(60, false));
+        hsts.record("sub.other.domain", &sts(60, true));
+        hsts.record("127.0.0.1", &sts(60, true));
+        assert!(hsts.is_known("example.domain"));
+        assert!(!hsts.is_known("www.example.domain"));
+        assert!(hsts.is_known("deep.sub.other.domain"));
+        assert!(!hsts.is_known("other.domain"));
+        assert!(!hsts.is_known("127.0.0.1"));
+
+        hsts.record("example.domain", &sts(0, false));
+        assert!(!hsts.is_known("example.domain"));
+    }
+
+    #[test]
+    fn test_hsts_upgrade() {
+        let hsts = Hsts::new();
+        hsts.seed("example.domain", true);
+        let mut url = Url::parse("http://www.example.domain:80/path?q").unwrap();
+        assert!(hsts.upgrade(&mut url));
+        assert_eq!(url.as_str(), "https://www.example.domain/path?q");
+        let mut url = Url::parse("http://example.domain:8080/").unwrap();
+        assert!(hsts.upgrade(&mut url));
+        assert_eq!(url.as_str(), "https://example.domain:8080/");
+        let mut url = Url::parse("http://other.domain/").unwrap();
+        assert!(!hsts.upgrade(&mut url));
+    }
+
+    #[test]
+    fn test_hsts_save_load() {
+        let hsts = Hsts::new();
+        hsts.seed("seeded.domain", true);
+        hsts.record("recorded.domain", &sts(3600, false));
+        let mut saved = Vec::new();
+        hsts.save(&mut saved).unwrap();
+
+        let loaded = Hsts::load(&saved[..]).unwrap();
+        assert!(loaded.is_known("www.seeded.domain"));
+        assert!(loaded.is_known("recorded.domain"));
+        assert!(!loaded.is_known("www.recorded.domain"));
+        assert!(Hsts::load(&b"bad line"[..]).is_err());
+    }
+}
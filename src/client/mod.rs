@@ -9,34 +9,49 @@ use std::io;
 use std::marker::PhantomData;
 use std::sync::mpsc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use rotor::{self, Scope, EventSet, PollOpt};
 
-use header::Host;
+use header::{Headers, Host, AcceptEncoding, ContentEncoding, ContentLength, Encoding, qitem,
+             StrictTransportSecurity};
 use http::{self, Next, RequestHead};
-use net::Transport;
+use method::Method;
+use net::{Transport, Blocked};
 use uri::RequestUri;
 use {Url};
 
+pub use self::auth::Credentials;
+pub use self::cache::{Cache, Entry, MemoryStore, Store};
+pub use self::hsts::{Hsts, HstsEntry};
 pub use self::connect::{Connect, DefaultConnector, HttpConnector, HttpsConnector, DefaultTransport};
+pub use self::connect::{Proxy, ProxyConnector, Socks5, SocksConnector, Tunnel};
 pub use self::request::Request;
 pub use self::response::Response;
 
+pub mod blocking;
+pub mod download;
+pub mod fetch;
+
+mod auth;
+mod cache;
 mod connect;
 mod dns;
+mod hsts;
 mod request;
 mod response;
 
 /// A Client to make outgoing HTTP requests.
 pub struct Client<H> {
     tx: http::channel::Sender<Notify<H>>,
+    hsts: Option<Hsts>,
 }
 
 impl<H> Clone for Client<H> {
     fn clone(&self) -> Client<H> {
         Client {
-            tx: self.tx.clone()
+            tx: self.tx.clone(),
+            hsts: self.hsts.clone(),
         }
     }
 }
@@ -83,15 +98,28 @@ impl<H: Send> Client<H> {
         rotor_config.slab_capacity(config.max_sockets);
         rotor_config.mio().notify_capacity(config.max_sockets);
         let keep_alive = config.keep_alive;
-        let connect_timeout = config.connect_timeout;
+        let decompress = config.decompress;
+        let hsts = config.hsts;
+        let timeouts = Timeouts {
+            dns: config.dns_timeout,
+            connect: config.connect_timeout,
+            handshake: config.handshake_timeout,
+            first_byte: config.first_byte_timeout,
+            read: config.read_timeout,
+            request: config.request_timeout,
+        };
+        let max_retries = config.max_retries;
         let mut loop_ = try!(rotor::Loop::new(&rotor_config));
         let mut notifier = None;
+        let mut retry = None;
         let mut connector = config.connector;
         {
             let not = &mut notifier;
+            let retry = &mut retry;
             loop_.add_machine_with(move |scope| {
                 let (tx, rx) = http::channel::new(scope.notifier());
                 let (dns_tx, dns_rx) = http::channel::share(&tx);
+                *retry = Some(http::channel::share(&tx));
                 *not = Some(tx);
                 connector.register(Registration {
                     notify: (dns_tx, dns_rx),
@@ -101,10 +129,15 @@ impl<H: Send> Client<H> {
         }
 
         let notifier = notifier.expect("loop.add_machine_with failed");
+        let retry = retry.expect("loop.add_machine_with failed");
         let _handle = try!(thread::Builder::new().name("hyper-client".to_owned()).spawn(move || {
             loop_.run(Context {
-                connect_timeout: connect_timeout,
                 keep_alive: keep_alive,
+                decompress: decompress,
+                hsts: hsts.clone(),
+                timeouts: timeouts,
+                max_retries: max_retries,
+                retry: retry,
                 idle_conns: HashMap::new(),
                 queue: HashMap::new(),
                 awaiting_slot: VecDeque::new(),
@@ -114,6 +147,7 @@ impl<H: Send> Client<H> {
         Ok(Client {
             //handle: Some(handle),
             tx: notifier,
+            hsts: hsts,
         })
     }
 
@@ -123,7 +157,10 @@ impl<H: Send> Client<H> {
     ///
     /// If the event loop thread has died, or the queue is full, a `ClientError`
     /// will be returned.
-    pub fn request(&self, url: Url, handler: H) -> Result<(), ClientError<H>> {
+    pub fn request(&self, mut url: Url, handler: H) -> Result<(), ClientError<H>> {
+        if let Some(ref hsts) = self.hsts {
+            hsts.upgrade(&mut url);
+        }
         self.tx.send(Notify::Connect(url, handler)).map_err(|e| {
             match e.0 {
                 Some(Notify::Connect(url, handler)) => ClientError(Some((url, handler))),
@@ -145,11 +182,19 @@ impl<H: Send> Client<H> {
 pub struct Config<C> {
     connect_timeout: Duration,
     connector: C,
+    decompress: bool,
+    dns_timeout: Duration,
+    first_byte_timeout: Option<Duration>,
+    handshake_timeout: Duration,
+    hsts: Option<Hsts>,
     keep_alive: bool,
     keep_alive_timeout: Option<Duration>,
     //TODO: make use of max_idle config
     max_idle: usize,
+    max_retries: usize,
     max_sockets: usize,
+    read_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
 }
 
 impl<C> Config<C> where C: Connect + Send + 'static {
@@ -159,10 +204,18 @@ impl<C> Config<C> where C: Connect + Send + 'static {
         Config {
             connect_timeout: self.connect_timeout,
             connector: val,
+            decompress: self.decompress,
+            dns_timeout: self.dns_timeout,
+            first_byte_timeout: self.first_byte_timeout,
+            handshake_timeout: self.handshake_timeout,
+            hsts: self.hsts,
             keep_alive: self.keep_alive,
             keep_alive_timeout: Some(Duration::from_secs(60 * 2)),
             max_idle: self.max_idle,
+            max_retries: self.max_retries,
             max_sockets: self.max_sockets,
+            read_timeout: self.read_timeout,
+            request_timeout: self.request_timeout,
         }
     }
 
@@ -195,7 +248,22 @@ impl<C> Config<C> where C: Connect + Send + 'static {
         self
     }
 
-    /// Set the timeout for connecting to a URL.
+    /// Set how many times a request may be retried on a new connection, after
+    /// a kept-alive connection is found closed before any response arrived.
+    ///
+    /// Only requests with idempotent methods are retried. See
+    /// `Handler::on_retry` for requests with a body.
+    ///
+    /// Default is 1. Pass 0 to disable retries.
+    #[inline]
+    pub fn max_retries(mut self, val: usize) -> Config<C> {
+        self.max_retries = val;
+        self
+    }
+
+    /// Set the timeout for connecting to a URL, once its host is resolved.
+    ///
+    /// Exceeding it is reported as `Error::ConnectTimeout`.
     ///
     /// Default is 10 seconds.
     #[inline]
@@ -204,6 +272,93 @@ impl<C> Config<C> where C: Connect + Send + 'static {
         self
     }
 
+    /// Enable transparent decoding of gzip and deflate response bodies.
+    ///
+    /// Requests are sent with `Accept-Encoding: gzip, deflate`, unless the
+    /// `Handler` sets its own `AcceptEncoding`. Responses using either
+    /// coding are inflated as the `Handler` reads them from the `Decoder`,
+    /// and their `ContentEncoding` and `ContentLength` headers are removed.
+    ///
+    /// Default is disabled.
+    #[inline]
+    pub fn decompress(mut self, val: bool) -> Config<C> {
+        self.decompress = val;
+        self
+    }
+
+    /// Set the timeout for resolving the host of a URL.
+    ///
+    /// Exceeding it is reported as `Error::DnsTimeout`.
+    ///
+    /// Default is 10 seconds.
+    #[inline]
+    pub fn dns_timeout(mut self, val: Duration) -> Config<C> {
+        self.dns_timeout = val;
+        self
+    }
+
+    /// Set the timeout for the handshake of a new connection, such as TLS,
+    /// before a request can be written to it.
+    ///
+    /// Exceeding it is reported as `Error::HandshakeTimeout`.
+    ///
+    /// Default is 10 seconds.
+    #[inline]
+    pub fn handshake_timeout(mut self, val: Duration) -> Config<C> {
+        self.handshake_timeout = val;
+        self
+    }
+
+    /// Set an `Hsts` store, to send requests for `http://` URLs of hosts
+    /// known to require HTTPS over `https://` instead.
+    ///
+    /// `StrictTransportSecurity` headers of HTTPS responses are recorded in
+    /// the store. Keep a clone of it to seed or persist the entries.
+    ///
+    /// Default is `None`.
+    #[inline]
+    pub fn hsts(mut self, val: Hsts) -> Config<C> {
+        self.hsts = Some(val);
+        self
+    }
+
+    /// Set an optional timeout for the first byte of a response to arrive,
+    /// counted from when the `Handler` starts waiting to read.
+    ///
+    /// Exceeding it is reported as `Error::FirstByteTimeout`.
+    ///
+    /// Default is `None`.
+    #[inline]
+    pub fn first_byte_timeout(mut self, val: Option<Duration>) -> Config<C> {
+        self.first_byte_timeout = val;
+        self
+    }
+
+    /// Set an optional timeout for a response body to stay idle between
+    /// reads.
+    ///
+    /// Exceeding it is reported as `Error::ReadTimeout`.
+    ///
+    /// Default is `None`.
+    #[inline]
+    pub fn read_timeout(mut self, val: Option<Duration>) -> Config<C> {
+        self.read_timeout = val;
+        self
+    }
+
+    /// Set an optional deadline for each request as a whole, from when it is
+    /// passed to `Client::request` until its response ends.
+    ///
+    /// Exceeding it is reported as `Error::Deadline`, whichever phase the
+    /// request is in.
+    ///
+    /// Default is `None`.
+    #[inline]
+    pub fn request_timeout(mut self, val: Option<Duration>) -> Config<C> {
+        self.request_timeout = val;
+        self
+    }
+
     /// Construct the Client with this configuration.
     #[inline]
     pub fn build<H: Handler<C::Output>>(self) -> ::Result<Client<H>> {
@@ -216,10 +371,18 @@ impl Default for Config<DefaultConnector> {
         Config {
             connect_timeout: Duration::from_secs(10),
             connector: DefaultConnector::default(),
+            decompress: false,
+            dns_timeout: Duration::from_secs(10),
+            first_byte_timeout: None,
+            handshake_timeout: Duration::from_secs(10),
+            hsts: None,
             keep_alive: true,
             keep_alive_timeout: Some(Duration::from_secs(60 * 2)),
             max_idle: 5,
+            max_retries: 1,
             max_sockets: 1024,
+            read_timeout: None,
+            request_timeout: None,
         }
     }
 }
@@ -279,59 +442,278 @@ pub trait Handler<T: Transport>: Send + 'static {
     fn on_control(&mut self, _: http::Control) {
         debug!("default Handler.on_control()");
     }
+
+    /// This event occurs before a request is retried on a new connection,
+    /// because a kept-alive connection was closed before any response arrived.
+    ///
+    /// It is only asked of requests that already wrote some of their body.
+    /// Return `true` if the body will be written again from the start, once
+    /// `on_request` occurs again. Requests that wrote no body are retried
+    /// without asking.
+    ///
+    /// Default is `false`, reporting the error with `on_error` instead.
+    fn on_retry(&mut self) -> bool {
+        debug!("default Handler.on_retry()");
+        false
+    }
 }
 
 struct Message<H: Handler<T>, T: Transport> {
-    handler: H,
-    url: Option<Url>,
+    handler: Option<H>,
+    url: Url,
+    proxy: Option<Headers>,
+    retry: Retry<H>,
+    timer: Timer,
+    decompress: bool,
+    inflate: Option<http::Inflate>,
+    head: bool,
+    hsts: Option<Hsts>,
     _marker: PhantomData<T>,
 }
 
+/// What a `Message` needs to know to decide if it can be retried.
+struct Retry<H> {
+    tx: http::channel::Sender<Notify<H>>,
+    started: Instant,
+    remaining: usize,
+    reused: bool,
+    idempotent: bool,
+    encoded: bool,
+    received: bool,
+}
+
+impl<H: Handler<T>, T: Transport> Message<H, T> {
+    fn handler(&mut self) -> &mut H {
+        self.handler.as_mut().expect("Message.handler is missing")
+    }
+
+    /// Sends the handler back to the Connector if this request can be
+    /// retried on a new connection, returning whether it was.
+    fn retry(&mut self) -> bool {
+        let can_retry = {
+            let retry = &self.retry;
+            retry.reused && retry.idempotent && !retry.received && retry.remaining > 0
+        };
+        if !can_retry || (self.retry.encoded && !self.handler().on_retry()) {
+            return false;
+        }
+        let handler = self.handler.take().expect("Message.handler is missing");
+        debug!("retrying request on a new connection: {}", self.url);
+        let retry = Notify::Retry(self.url.clone(), handler, self.retry.remaining - 1, self.retry.started);
+        match self.retry.tx.send(retry) {
+            Ok(()) => true,
+            Err(e) => {
+                if let Some(Notify::Retry(_, handler, _, _)) = e.0 {
+                    self.handler = Some(handler);
+                }
+                false
+            }
+        }
+    }
+
+    fn arm(&mut self, next: Next) -> Next {
+        let received = self.retry.received;
+        self.timer.arm(next, received)
+    }
+}
+
+/// The timeouts of a `Message` that its `Handler` doesn't ask for itself.
+struct Timer {
+    first_byte: Option<Duration>,
+    read: Option<Duration>,
+    deadline: Option<Instant>,
+    first_byte_at: Option<Instant>,
+    armed: Armed,
+}
+
+/// Which timeout was given to the last `Next`.
+#[derive(Debug, Clone, Copy)]
+enum Armed {
+    Handler,
+    FirstByte,
+    Read,
+    Deadline,
+}
+
+impl Timer {
+    /// Gives `next` the timeout that would end first: the one the `Handler`
+    /// asked for, the one for the current phase of the response, or the
+    /// request deadline.
+    fn arm(&mut self, next: Next, received: bool) -> Next {
+        let now = Instant::now();
+        let reading = http::next_is_read(&next);
+        if !received && reading && self.first_byte_at.is_none() {
+            self.first_byte_at = self.first_byte.map(|dur| now + dur);
+        }
+        let phase = if received {
+            if reading {
+                self.read.map(|dur| (now + dur, Armed::Read))
+            } else {
+                None
+            }
+        } else {
+            self.first_byte_at.map(|at| (at, Armed::FirstByte))
+        };
+        let deadline = self.deadline.map(|at| (at, Armed::Deadline));
+
+        let mut earliest = http::next_timeout(&next).map(|dur| (now + dur, Armed::Handler));
+        for candidate in phase.into_iter().chain(deadline) {
+            earliest = match earliest {
+                Some(current) if current.0 <= candidate.0 => Some(current),
+                _ => Some(candidate)
+            };
+        }
+        match earliest {
+            Some((at, armed)) => {
+                self.armed = armed;
+                let dur = if at > now { at - now } else { Duration::from_millis(0) };
+                next.timeout(dur)
+            },
+            None => {
+                self.armed = Armed::Handler;
+                next
+            }
+        }
+    }
+
+    /// The error to report when the last `Next` timed out.
+    fn error(&self) -> ::Error {
+        match self.armed {
+            Armed::Handler => ::Error::Timeout,
+            Armed::FirstByte => ::Error::FirstByteTimeout,
+            Armed::Read => ::Error::ReadTimeout,
+            Armed::Deadline => ::Error::Deadline,
+        }
+    }
+}
+
 impl<H: Handler<T>, T: Transport> http::MessageHandler<T> for Message<H, T> {
     type Message = http::ClientMessage;
 
     fn on_outgoing(&mut self, head: &mut RequestHead) -> Next {
-        let url = self.url.take().expect("Message.url is missing");
+        let url = self.url.clone();
         if let Some(host) = url.host_str() {
             head.headers.set(Host {
                 hostname: host.to_owned(),
                 port: url.port(),
             });
         }
-        head.subject.1 = RequestUri::AbsolutePath {
-            path: url.path().to_owned(),
-            query: url.query().map(|q| q.to_owned()),
+        head.subject.1 = match self.proxy.take() {
+            Some(headers) => {
+                head.headers.extend(headers.iter());
+                let mut url = url;
+                url.set_fragment(None);
+                RequestUri::AbsoluteUri(url)
+            },
+            None => RequestUri::AbsolutePath {
+                path: url.path().to_owned(),
+                query: url.query().map(|q| q.to_owned()),
+            }
+        };
+        let next = {
+            let mut req = self::request::new(head);
+            self.handler().on_request(&mut req)
         };
-        let mut req = self::request::new(head);
-        self.handler.on_request(&mut req)
+        if self.decompress {
+            if head.headers.has::<AcceptEncoding>() {
+                // the Handler negotiates, so decodes, for itself
+                self.decompress = false;
+            } else {
+                head.headers.set(AcceptEncoding(vec![qitem(Encoding::Gzip), qitem(Encoding::Deflate)]));
+            }
+        }
+        self.retry.idempotent = head.subject.0.idempotent();
+        self.head = head.subject.0 == Method::Head;
+        self.arm(next)
     }
 
     fn on_encode(&mut self, transport: &mut http::Encoder<T>) -> Next {
-        self.handler.on_request_writable(transport)
+        self.retry.encoded = true;
+        let next = self.handler().on_request_writable(transport);
+        self.arm(next)
     }
 
-    fn on_incoming(&mut self, head: http::ResponseHead, _: &T) -> Next {
+    fn on_incoming(&mut self, mut head: http::ResponseHead, _: &T) -> Next {
         trace!("on_incoming {:?}", head);
+        self.retry.received = true;
+        if let Some(ref hsts) = self.hsts {
+            if self.url.scheme() == "https" {
+                if let (Some(host), Some(sts)) = (self.url.host_str(), head.headers.get::<StrictTransportSecurity>()) {
+                    hsts.record(host, sts);
+                }
+            }
+        }
+        if self.decompress {
+            // an empty body has nothing to inflate, whatever its coding
+            let empty = self.head || match head.subject.0 {
+                100...199 | 204 | 304 => true,
+                _ => head.headers.get::<ContentLength>() == Some(&ContentLength(0))
+            };
+            self.inflate = match head.headers.get::<ContentEncoding>() {
+                Some(codings) if codings.len() == 1 && !empty => http::Inflate::new(&codings[0]),
+                _ => None
+            };
+            if self.inflate.is_some() {
+                // the length and coding of the inflated body are unknown
+                head.headers.remove::<ContentEncoding>();
+                head.headers.remove::<ContentLength>();
+            }
+        }
         let resp = response::new(head);
-        self.handler.on_response(resp)
+        let next = self.handler().on_response(resp);
+        self.arm(next)
     }
 
     fn on_decode(&mut self, transport: &mut http::Decoder<T>) -> Next {
-        self.handler.on_response_readable(transport)
+        let next = {
+            let handler = self.handler.as_mut().expect("Message.handler is missing");
+            match self.inflate {
+                Some(ref mut inflate) => handler.on_response_readable(&mut http::inflate(transport, inflate)),
+                None => handler.on_response_readable(transport)
+            }
+        };
+        self.arm(next)
     }
 
     fn on_error(&mut self, error: ::Error) -> Next {
-        self.handler.on_error(error)
+        let error = match error {
+            ::Error::Io(e) => {
+                if self.retry() {
+                    return Next::remove();
+                }
+                ::Error::Io(e)
+            },
+            ::Error::Timeout => self.timer.error(),
+            error => error
+        };
+        let next = self.handler().on_error(error);
+        self.arm(next)
+    }
+
+    fn on_incoming_error(&mut self, err: io::Error, partial: bool) {
+        // the response may never come, such as when a kept-alive
+        // connection was closed, and the request can be retried; but once
+        // any of it has arrived, the server has seen the request
+        if partial {
+            self.retry.received = true;
+        }
+        let _ = self.on_error(::Error::Io(err));
     }
 
     fn on_remove(self, transport: T) {
-        self.handler.on_remove(transport);
+        if let Some(handler) = self.handler {
+            handler.on_remove(transport);
+        }
     }
 }
 
 struct Context<K, H, C: Connect> {
-    connect_timeout: Duration,
     keep_alive: bool,
+    decompress: bool,
+    hsts: Option<Hsts>,
+    timeouts: Timeouts,
+    max_retries: usize,
+    retry: (http::channel::Sender<Notify<H>>, http::channel::Receiver<Notify<H>>),
     idle_conns: HashMap<K, VecDeque<http::Control>>,
     queue: HashMap<K, VecDeque<Queued<H>>>,
     awaiting_slot: VecDeque<(C::Key, C::Output)>,
@@ -387,6 +769,27 @@ impl<K: http::Key, H, C: Connect> Context<K, H, C> {
 
         queued
     }
+
+    /// Queues a request, in `phase`, until a connection for `key` is ready.
+    fn push_queue(&mut self, key: K, mut queued: Queued<H>, phase: Phase, now: rotor::Time) {
+        queued.expires = self.timeouts.request.map(|timeout| {
+            let elapsed = queued.started.elapsed();
+            now + if elapsed < timeout { timeout - elapsed } else { Duration::from_millis(0) }
+        });
+        queued.enter(phase, now, &self.timeouts);
+        self.queue.entry(key).or_insert_with(VecDeque::new).push_back(queued);
+    }
+
+    /// Moves the first request for `key` that is in phase `from` on to phase
+    /// `to`.
+    fn advance(&mut self, key: &K, from: Phase, to: Phase, now: rotor::Time) {
+        let timeouts = self.timeouts;
+        if let Some(vec) = self.queue.get_mut(key) {
+            if let Some(queued) = vec.iter_mut().find(|queued| queued.phase == from) {
+                queued.enter(to, now, &timeouts);
+            }
+        }
+    }
 }
 
 impl<K, H, T, C> http::MessageHandlerFactory<K, T> for Context<K, H, C>
@@ -404,8 +807,29 @@ impl<K, H, T, C> http::MessageHandlerFactory<K, T> for Context<K, H, C>
             handler.on_control(seed.control());
 
             Message {
-                handler: handler,
-                url: Some(url),
+                handler: Some(handler),
+                url: url,
+                proxy: queued.proxy,
+                retry: Retry {
+                    tx: self.retry.0.clone(),
+                    started: queued.started,
+                    remaining: queued.retries,
+                    reused: seed.is_reused(),
+                    idempotent: false,
+                    encoded: false,
+                    received: false,
+                },
+                timer: Timer {
+                    first_byte: self.timeouts.first_byte,
+                    read: self.timeouts.read,
+                    deadline: self.timeouts.request.map(|dur| queued.started + dur),
+                    first_byte_at: None,
+                    armed: Armed::Handler,
+                },
+                decompress: self.decompress,
+                inflate: None,
+                head: false,
+                hsts: self.hsts.clone(),
                 _marker: PhantomData,
             }
         })
@@ -418,6 +842,9 @@ impl<K, H, T, C> http::MessageHandlerFactory<K, T> for Context<K, H, C>
 
 enum Notify<T> {
     Connect(Url, T),
+    /// Retry a request on a new connection, with the remaining retries and
+    /// when the request was first made.
+    Retry(Url, T, usize, Instant),
     Shutdown,
 }
 
@@ -427,6 +854,7 @@ where C: Connect,
       H: Handler<C::Output> {
     Connector(C, http::channel::Receiver<Notify<H>>),
     Connecting((C::Key, C::Output)),
+    Handshaking((C::Key, C::Output)),
     Socket(http::Conn<C::Key, C::Output, Message<H, C::Output>>)
 }
 
@@ -471,12 +899,9 @@ where C: Connect,
                 } else if events.is_writable() {
                     if scope.queue.contains_key(&seed.0) {
                         trace!("connected and writable {:?}", seed.0);
-                        rotor::Response::ok(
-                            ClientFsm::Socket(
-                                http::Conn::new(seed.0, seed.1, Next::write().timeout(scope.connect_timeout), scope.notifier())
-                                    .keep_alive(scope.keep_alive)
-                            )
-                        )
+                        let now = scope.now();
+                        scope.advance(&seed.0, Phase::Connecting, Phase::Handshaking, now);
+                        ClientFsm::handshake(seed, scope)
                     } else {
                         trace!("connected, but queued handler is gone: {:?}", seed.0); // probably took too long connecting
                         rotor::Response::done()
@@ -486,6 +911,21 @@ where C: Connect,
                     rotor::Response::ok(ClientFsm::Connecting(seed))
                 }
             }
+            ClientFsm::Handshaking(mut seed) => {
+                if events.is_error() || events.is_hup() {
+                    if let Some(err) = seed.1.take_socket_error().err() {
+                        debug!("error while handshaking: {:?}", err);
+                        scope.pop_queue(&seed.0).map(move |mut queued| queued.handler.on_error(::Error::Io(err)));
+                        return rotor::Response::done();
+                    }
+                }
+                if scope.queue.contains_key(&seed.0) {
+                    ClientFsm::handshake(seed, scope)
+                } else {
+                    trace!("handshaking, but queued handler is gone: {:?}", seed.0); // probably took too long
+                    rotor::Response::done()
+                }
+            }
             ClientFsm::Connector(..) => {
                 unreachable!("Connector can never be ready")
             },
@@ -571,9 +1011,17 @@ where C: Connect,
                 let mut empty_keys = Vec::new();
                 {
                     for (key, mut vec) in &mut scope.queue {
-                        while !vec.is_empty() && vec[0].deadline <= now {
-                            vec.pop_front()
-                               .map(|mut queued| queued.handler.on_error(::Error::Timeout));
+                        // requests in different phases have unordered deadlines
+                        let mut i = 0;
+                        while i < vec.len() {
+                            if vec[i].deadline <= now {
+                                vec.remove(i).map(|mut queued| {
+                                    let err = queued.timeout_error(now);
+                                    queued.handler.on_error(err)
+                                });
+                            } else {
+                                i += 1;
+                            }
                         }
                         if vec.is_empty() {
                             empty_keys.push(key.clone());
@@ -590,7 +1038,8 @@ where C: Connect,
                     None => rotor::Response::ok(self)
                 }
             }
-            ClientFsm::Connecting(..) => unreachable!(),
+            ClientFsm::Connecting(..) |
+            ClientFsm::Handshaking(..) => unreachable!(),
             ClientFsm::Socket(conn) => {
                 let res = conn.timeout(scope);
                 let now = scope.now();
@@ -609,7 +1058,8 @@ where C: Connect,
                 let now = scope.now();
                 conn_response!(scope, res, now)
             },
-            ClientFsm::Connecting(..) => unreachable!("connecting sockets should not be woken up")
+            ClientFsm::Connecting(..) |
+            ClientFsm::Handshaking(..) => unreachable!("connecting sockets should not be woken up")
         }
     }
 }
@@ -626,6 +1076,8 @@ where C: Connect,
                     match res {
                         Ok(socket) => {
                             trace!("connecting {:?}", key);
+                            let now = scope.now();
+                            scope.advance(&key, Phase::Resolving, Phase::Connecting, now);
                             return rotor::Response::spawn(ClientFsm::Connector(connector, rx), (key, socket));
                         },
                         Err(e) => {
@@ -634,6 +1086,14 @@ where C: Connect,
                         }
                     }
                 }
+                while let Ok(notify) = scope.retry.1.try_recv() {
+                    if let Notify::Retry(url, handler, retries, started) = notify {
+                        // the idle conn was stale, so skip the pool
+                        trace!("retrying '{}', {} retries left", url, retries);
+                        let queued = Queued::new(url, handler, retries, started, scope.now());
+                        connect(&mut connector, scope, queued);
+                    }
+                }
                 loop {
                     match rx.try_recv() {
                         Ok(Notify::Connect(url, mut handler)) => {
@@ -657,15 +1117,10 @@ where C: Connect,
 
                                 if woke_up {
                                     trace!("woke up idle conn for '{}'", url);
-                                    let deadline = scope.now() + scope.connect_timeout;
-                                    scope.queue
-                                        .entry(key)
-                                        .or_insert_with(VecDeque::new)
-                                        .push_back(Queued {
-                                            deadline: deadline,
-                                            handler: handler,
-                                            url: url
-                                        });
+                                    let now = scope.now();
+                                    let mut queued = Queued::new(url, handler, scope.max_retries, Instant::now(), now);
+                                    queued.proxy = connector.proxy(&queued.url);
+                                    scope.push_queue(key, queued, Phase::Connecting, now);
                                     continue;
                                 }
                             } else {
@@ -674,25 +1129,10 @@ where C: Connect,
                                 continue;
                             }
                             // no exist connection, call connector
-                            match connector.connect(&url) {
-                                Ok(key) => {
-                                    let deadline = scope.now() + scope.connect_timeout;
-                                    scope.queue
-                                        .entry(key)
-                                        .or_insert_with(VecDeque::new)
-                                        .push_back(Queued {
-                                            deadline: deadline,
-                                            handler: handler,
-                                            url: url
-                                        });
-                                }
-                                Err(e) => {
-                                    let _todo = handler.on_error(e.into());
-                                    trace!("Connect error, next={:?}", _todo);
-                                    continue;
-                                }
-                            }
+                            let queued = Queued::new(url, handler, scope.max_retries, Instant::now(), scope.now());
+                            connect(&mut connector, scope, queued);
                         }
+                        Ok(Notify::Retry(..)) => unreachable!("retries have their own channel"),
                         Ok(Notify::Shutdown) => {
                             scope.shutdown_loop();
                             return rotor::Response::done()
@@ -721,6 +1161,37 @@ where C: Connect,
         }
     }
 
+    /// Drives any handshake of a newly connected transport, such as TLS or a
+    /// proxy tunnel, before a request is written to it.
+    fn handshake(mut seed: <Self as rotor::Machine>::Seed, scope: &mut rotor::Scope<<Self as rotor::Machine>::Context>) -> rotor::Response<Self, <Self as rotor::Machine>::Seed> {
+        match seed.1.handshake() {
+            Ok(_) => {
+                trace!("handshake complete {:?}", seed.0);
+                rotor_try!(scope.reregister(&seed.1, EventSet::writable(), PollOpt::level()));
+                rotor::Response::ok(
+                    ClientFsm::Socket(
+                        http::Conn::new(seed.0, seed.1, Next::write().timeout(scope.timeouts.connect), scope.notifier())
+                            .keep_alive(scope.keep_alive)
+                    )
+                )
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                let events = match seed.1.blocked() {
+                    Some(Blocked::Read) => EventSet::readable(),
+                    _ => EventSet::writable(),
+                };
+                trace!("handshaking {:?}, waiting for {:?}", seed.0, events);
+                rotor_try!(scope.reregister(&seed.1, events, PollOpt::level()));
+                rotor::Response::ok(ClientFsm::Handshaking(seed))
+            },
+            Err(e) => {
+                debug!("error while handshaking: {:?}", e);
+                scope.pop_queue(&seed.0).map(move |mut queued| queued.handler.on_error(::Error::Io(e)));
+                rotor::Response::done()
+            }
+        }
+    }
+
     fn deadline(&self, scope: &mut rotor::Scope<<Self as rotor::Machine>::Context>) -> Option<rotor::Time> {
         match *self {
             ClientFsm::Connector(..) => {
@@ -745,12 +1216,95 @@ where C: Connect,
     }
 }
 
+/// Starts connecting a fresh socket for a request, queueing it to use the
+/// socket once connected.
+fn connect<K, H, C>(connector: &mut C, scope: &mut Scope<Context<K, H, C>>, mut queued: Queued<H>)
+where K: http::Key,
+      H: Handler<C::Output>,
+      C: Connect<Key=K> {
+    match connector.connect(&queued.url) {
+        Ok(key) => {
+            let now = scope.now();
+            queued.proxy = connector.proxy(&queued.url);
+            scope.push_queue(key, queued, Phase::Resolving, now);
+        }
+        Err(e) => {
+            let _todo = queued.handler.on_error(e.into());
+            trace!("Connect error, next={:?}", _todo);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Timeouts {
+    dns: Duration,
+    connect: Duration,
+    handshake: Duration,
+    first_byte: Option<Duration>,
+    read: Option<Duration>,
+    request: Option<Duration>,
+}
+
+/// Where a queued request is in getting a connection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    Resolving,
+    Connecting,
+    Handshaking,
+}
+
 struct Queued<H> {
     deadline: rotor::Time,
+    expires: Option<rotor::Time>,
     handler: H,
+    phase: Phase,
+    proxy: Option<Headers>,
+    retries: usize,
+    started: Instant,
     url: Url,
 }
 
+impl<H> Queued<H> {
+    fn new(url: Url, handler: H, retries: usize, started: Instant, now: rotor::Time) -> Queued<H> {
+        Queued {
+            deadline: now,
+            expires: None,
+            handler: handler,
+            phase: Phase::Resolving,
+            proxy: None,
+            retries: retries,
+            started: started,
+            url: url,
+        }
+    }
+
+    /// Moves the request on to `phase`, restarting its timeout.
+    fn enter(&mut self, phase: Phase, now: rotor::Time, timeouts: &Timeouts) {
+        let timeout = match phase {
+            Phase::Resolving => timeouts.dns,
+            Phase::Connecting => timeouts.connect,
+            Phase::Handshaking => timeouts.handshake,
+        };
+        self.phase = phase;
+        self.deadline = match self.expires {
+            Some(expires) if expires < now + timeout => expires,
+            _ => now + timeout
+        };
+    }
+
+    /// The error to report when this request's deadline has passed.
+    fn timeout_error(&self, now: rotor::Time) -> ::Error {
+        match self.expires {
+            Some(expires) if expires <= now => ::Error::Deadline,
+            _ => match self.phase {
+                Phase::Resolving => ::Error::DnsTimeout,
+                Phase::Connecting => ::Error::ConnectTimeout,
+                Phase::Handshaking => ::Error::HandshakeTimeout,
+            }
+        }
+    }
+}
+
 #[doc(hidden)]
 #[allow(missing_debug_implementations)]
 pub struct Registration {
@@ -1,5 +1,6 @@
 //! Client Requests
 
+use form::Form;
 use header::Headers;
 use http::RequestHead;
 use method::Method;
@@ -43,6 +44,18 @@ impl<'a> Request<'a> {
     #[inline]
     pub fn set_uri(&mut self, uri: RequestUri) { self.head.subject.1 = uri; }
 
+    /// Set the query of the `RequestUri` to the pairs of a `Form`.
+    ///
+    /// An empty `Form` removes the query.
+    pub fn set_query(&mut self, form: &Form) {
+        let query = if form.is_empty() { None } else { Some(form.to_string()) };
+        match self.head.subject.1 {
+            RequestUri::AbsolutePath { query: ref mut q, .. } => *q = query,
+            RequestUri::AbsoluteUri(ref mut url) => url.set_query(query.as_ref().map(|q| &q[..])),
+            _ => ()
+        }
+    }
+
     /// Set the `HttpVersion` of this request.
     #[inline]
     pub fn set_version(&mut self, version: HttpVersion) { self.head.version = version; }
@@ -41,7 +41,8 @@ pub enum Error {
     Version,
     /// An invalid `Header`.
     Header,
-    /// A message head is too large to be reasonable.
+    /// A message head is too large to be reasonable, or a body is over its
+    /// limit.
     TooLarge,
     /// A message reached EOF, but is not complete.
     Incomplete,
@@ -49,6 +50,18 @@ pub enum Error {
     Status,
     /// A timeout occurred waiting for an IO event.
     Timeout,
+    /// Resolving the host name of a URL took too long.
+    DnsTimeout,
+    /// Connecting to the remote address took too long.
+    ConnectTimeout,
+    /// A TLS handshake, or the handshake of a proxy tunnel, took too long.
+    HandshakeTimeout,
+    /// The first byte of a response took too long to arrive.
+    FirstByteTimeout,
+    /// A response stalled for too long between reads.
+    ReadTimeout,
+    /// A request did not complete before its deadline.
+    Deadline,
     /// Event loop is full and cannot process request
     Full,
     /// An `io::Error` that occurred while trying to read or write to a network stream.
@@ -89,10 +102,16 @@ impl StdError for Error {
             Method => "Invalid Method specified",
             Version => "Invalid HTTP version specified",
             Header => "Invalid Header provided",
-            TooLarge => "Message head is too large",
+            TooLarge => "Message is too large",
             Status => "Invalid Status provided",
             Incomplete => "Message is incomplete",
             Timeout => "Timeout",
+            Error::DnsTimeout => "Timeout resolving host name",
+            Error::ConnectTimeout => "Timeout connecting to remote address",
+            Error::HandshakeTimeout => "Timeout during handshake",
+            Error::FirstByteTimeout => "Timeout waiting for response",
+            Error::ReadTimeout => "Timeout reading response",
+            Error::Deadline => "Request deadline exceeded",
             Error::Full => "Event loop is full",
             Uri(ref e) => e.description(),
             Io(ref e) => e.description(),
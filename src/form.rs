@@ -0,0 +1,192 @@
+//! URL encoded forms
+//!
+//! A `Form` is an ordered list of name and value pairs, as found in a query
+//! string, or in an `application/x-www-form-urlencoded` body. Names may be
+//! repeated, and pairs keep the order they were parsed or added in.
+//!
+//! # Example
+//!
+//! ```
+//! use hyper::form::Form;
+//!
+//! let form = Form::parse(b"q=rust+http&tag=a&tag=b%26c");
+//! assert_eq!(form.get("q"), Some("rust http"));
+//! assert_eq!(form.get_all("tag"), vec!["a", "b&c"]);
+//! assert_eq!(form.to_string(), "q=rust+http&tag=a&tag=b%26c");
+//! ```
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::slice;
+
+use url::form_urlencoded;
+
+use header::{ContentLength, ContentType, Headers};
+use mime::{Mime, SubLevel, TopLevel};
+use uri::RequestUri;
+
+/// Ordered name and value pairs of a query string or urlencoded body.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Form {
+    pairs: Vec<(String, String)>,
+}
+
+impl Form {
+    /// Create an empty `Form`.
+    pub fn new() -> Form {
+        Form::default()
+    }
+
+    /// Parse a query string or urlencoded body, percent-decoding names and
+    /// values.
+    pub fn parse(bytes: &[u8]) -> Form {
+        form_urlencoded::parse(bytes)
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect()
+    }
+
+    /// Add a pair, after any with the same name.
+    pub fn append<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) {
+        self.pairs.push((name.into(), value.into()));
+    }
+
+    /// Add a pair, returning the `Form` to allow chaining.
+    pub fn with<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Form {
+        self.append(name, value);
+        self
+    }
+
+    /// The first value with this name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.pairs.iter().find(|pair| pair.0 == name).map(|pair| &pair.1[..])
+    }
+
+    /// All values with this name, in order.
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.pairs.iter().filter(|pair| pair.0 == name).map(|pair| &pair.1[..]).collect()
+    }
+
+    /// Iterate over the pairs, in order.
+    pub fn iter(&self) -> Iter {
+        Iter(self.pairs.iter())
+    }
+
+    /// The number of pairs.
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    /// Whether there are no pairs.
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// The `ContentType` of a urlencoded body.
+    pub fn content_type() -> ContentType {
+        ContentType(Mime(TopLevel::Application, SubLevel::WwwFormUrlEncoded, vec![]))
+    }
+
+    /// Serialize the pairs as a request body, setting the `ContentType` and
+    /// `ContentLength` to send it with.
+    pub fn to_body(&self, headers: &mut Headers) -> Vec<u8> {
+        let body = self.to_string().into_bytes();
+        headers.set(Form::content_type());
+        headers.set(ContentLength(body.len() as u64));
+        body
+    }
+
+    /// A `RequestUri` of `path`, with the pairs as its query.
+    pub fn to_uri<P: Into<String>>(&self, path: P) -> RequestUri {
+        RequestUri::AbsolutePath {
+            path: path.into(),
+            query: if self.is_empty() { None } else { Some(self.to_string()) },
+        }
+    }
+}
+
+impl fmt::Display for Form {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut serializer = form_urlencoded::Serializer::new(String::new());
+        for &(ref name, ref value) in &self.pairs {
+            serializer.append_pair(name, value);
+        }
+        f.write_str(&serializer.finish())
+    }
+}
+
+impl<N: Into<String>, V: Into<String>> FromIterator<(N, V)> for Form {
+    fn from_iter<I: IntoIterator<Item=(N, V)>>(iter: I) -> Form {
+        Form {
+            pairs: iter.into_iter().map(|(name, value)| (name.into(), value.into())).collect()
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Form {
+    type Item = (&'a str, &'a str);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+/// An iterator over the pairs of a `Form`.
+#[derive(Debug)]
+pub struct Iter<'a>(slice::Iter<'a, (String, String)>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<(&'a str, &'a str)> {
+        self.0.next().map(|pair| (&pair.0[..], &pair.1[..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::{ContentLength, ContentType, Headers};
+    use uri::RequestUri;
+    use super::Form;
+
+    #[test]
+    fn test_form_parse() {
+        let form = Form::parse(b"a=1&b=%C3%A9t%C3%A9&a=2&empty=&flag");
+        assert_eq!(form.len(), 5);
+        assert_eq!(form.get("a"), Some("1"));
+        assert_eq!(form.get_all("a"), vec!["1", "2"]);
+        assert_eq!(form.get("b"), Some("été"));
+        assert_eq!(form.get("empty"), Some(""));
+        assert_eq!(form.get("flag"), Some(""));
+        assert_eq!(form.get("missing"), None);
+        assert_eq!(form.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+                   vec!["a", "b", "a", "empty", "flag"]);
+        assert!(Form::parse(b"").is_empty());
+    }
+
+    #[test]
+    fn test_form_serialize() {
+        let form = Form::new().with("q", "a b").with("x", "1&2=3").with("q", "é");
+        assert_eq!(form.to_string(), "q=a+b&x=1%262%3D3&q=%C3%A9");
+        assert_eq!(Form::parse(form.to_string().as_bytes()), form);
+    }
+
+    #[test]
+    fn test_form_to_body() {
+        let mut headers = Headers::new();
+        let body = Form::new().with("q", "value").to_body(&mut headers);
+        assert_eq!(body, b"q=value");
+        assert_eq!(headers.get::<ContentLength>(), Some(&ContentLength(7)));
+        assert_eq!(headers.get::<ContentType>(), Some(&Form::content_type()));
+    }
+
+    #[test]
+    fn test_form_to_uri() {
+        let form = vec![("page", "2"), ("sort", "new")].into_iter().collect::<Form>();
+        assert_eq!(form.to_uri("/posts"), RequestUri::AbsolutePath {
+            path: "/posts".to_owned(),
+            query: Some("page=2&sort=new".to_owned()),
+        });
+        assert_eq!(Form::new().to_uri("/").to_string(), "/");
+    }
+}
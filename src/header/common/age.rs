@@ -0,0 +1,29 @@
+header! {
+    /// `Age` header, defined in
+    /// [RFC7234](https://tools.ietf.org/html/rfc7234#section-5.1)
+    ///
+    /// The `Age` header field conveys the sender's estimate of the amount of
+    /// time since the response was generated or successfully validated at
+    /// the origin server.
+    ///
+    /// # ABNF
+    /// ```plain
+    /// Age = delta-seconds
+    /// ```
+    ///
+    /// # Example values
+    /// * `60`
+    ///
+    /// # Examples
+    /// ```
+    /// use hyper::header::{Headers, Age};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.set(Age(60u32));
+    /// ```
+    (Age, "Age") => [u32]
+
+    test_age {
+        test_header!(test1, vec![b"60"]);
+    }
+}
@@ -0,0 +1,304 @@
+use std::fmt;
+use std::str::FromStr;
+
+use unicase::UniCase;
+
+use hash;
+use header::{Challenge, Scheme};
+use method::Method;
+use super::www_authenticate::{fmt_quoted, parse_params};
+
+/// Credential holder for Digest Authentication, defined in
+/// [RFC7616](https://tools.ietf.org/html/rfc7616)
+///
+/// A `Digest` proves knowledge of a password without sending it, by hashing
+/// it with the `nonce` of a `Challenge`. Build one with `Digest::answer`,
+/// and check one with `Digest::verify`.
+///
+/// # Examples
+/// ```
+/// use hyper::header::{Authorization, Challenge, Digest, Headers};
+/// use hyper::Get;
+///
+/// let challenge = Challenge::new("Digest")
+///     .with_param("realm", "http-auth@example.org")
+///     .with_param("qop", "auth")
+///     .with_param("nonce", "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v");
+/// let digest = Digest::answer(&challenge, "Mufasa", "Circle of Life", &Get,
+///                             "/dir/index.html", 1, "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ")
+///     .unwrap();
+/// assert!(digest.verify("Circle of Life", &Get));
+///
+/// let mut headers = Headers::new();
+/// headers.set(Authorization(digest));
+/// ```
+#[derive(Clone, PartialEq, Debug)]
+pub struct Digest {
+    /// The user's name.
+    pub username: String,
+    /// The realm of the challenge.
+    pub realm: String,
+    /// The nonce of the challenge.
+    pub nonce: String,
+    /// The request-target of the request.
+    pub uri: String,
+    /// The hex encoded hash proving the password.
+    pub response: String,
+    /// The hash algorithm, MD5 if `None`.
+    pub algorithm: Option<DigestAlgorithm>,
+    /// The opaque value of the challenge, returned unchanged.
+    pub opaque: Option<String>,
+    /// The quality of protection, `auth` if the challenge offered it.
+    pub qop: Option<String>,
+    /// How many requests have been sent with this nonce, including this
+    /// one. Only sent with a `qop`.
+    pub nc: Option<u32>,
+    /// The nonce chosen by the client. Only sent with a `qop`.
+    pub cnonce: Option<String>,
+}
+
+/// The hash algorithm of a `Digest`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DigestAlgorithm {
+    /// `MD5`
+    Md5,
+    /// `SHA-256`
+    Sha256,
+}
+
+impl DigestAlgorithm {
+    fn hex(&self, data: &str) -> String {
+        let hash = match *self {
+            DigestAlgorithm::Md5 => hash::md5(data.as_bytes()).to_vec(),
+            DigestAlgorithm::Sha256 => hash::sha256(data.as_bytes()).to_vec(),
+        };
+        let mut hex = String::with_capacity(hash.len() * 2);
+        for b in hash {
+            hex.push_str(&format!("{:02x}", b));
+        }
+        hex
+    }
+}
+
+impl fmt::Display for DigestAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            DigestAlgorithm::Md5 => "MD5",
+            DigestAlgorithm::Sha256 => "SHA-256",
+        })
+    }
+}
+
+impl FromStr for DigestAlgorithm {
+    type Err = ::Error;
+    fn from_str(s: &str) -> ::Result<DigestAlgorithm> {
+        if UniCase(s) == UniCase("MD5") {
+            Ok(DigestAlgorithm::Md5)
+        } else if UniCase(s) == UniCase("SHA-256") {
+            Ok(DigestAlgorithm::Sha256)
+        } else {
+            Err(::Error::Header)
+        }
+    }
+}
+
+impl Digest {
+    /// Answer a `Digest` challenge for a request of `method` and `uri`.
+    ///
+    /// `nc` counts the requests answered with the same nonce, starting at
+    /// 1, and `cnonce` is a fresh random value. Returns `None` if the
+    /// challenge isn't a `Digest` one, or asks for an algorithm or quality
+    /// of protection that isn't supported.
+    pub fn answer(challenge: &Challenge, username: &str, password: &str, method: &Method,
+                  uri: &str, nc: u32, cnonce: &str) -> Option<Digest> {
+        if !challenge.is_scheme("Digest") {
+            return None;
+        }
+        let (realm, nonce) = match (challenge.realm(), challenge.param("nonce")) {
+            (Some(realm), Some(nonce)) => (realm, nonce),
+            _ => return None
+        };
+        let algorithm = match challenge.param("algorithm") {
+            Some(algorithm) => match algorithm.parse() {
+                Ok(algorithm) => Some(algorithm),
+                Err(_) => return None
+            },
+            None => None
+        };
+        let qop = match challenge.param("qop") {
+            Some(qop) => {
+                if !qop.split(',').any(|qop| qop.trim() == "auth") {
+                    return None;
+                }
+                Some("auth".to_owned())
+            },
+            None => None
+        };
+        let mut digest = Digest {
+            username: username.to_owned(),
+            realm: realm.to_owned(),
+            nonce: nonce.to_owned(),
+            uri: uri.to_owned(),
+            response: String::new(),
+            algorithm: algorithm,
+            opaque: challenge.param("opaque").map(ToOwned::to_owned),
+            nc: qop.as_ref().map(|_| nc),
+            cnonce: qop.as_ref().map(|_| cnonce.to_owned()),
+            qop: qop,
+        };
+        digest.response = digest.compute(password, method);
+        Some(digest)
+    }
+
+    /// Whether the `response` proves knowledge of `password`, for a request
+    /// of `method`.
+    pub fn verify(&self, password: &str, method: &Method) -> bool {
+        let expected = self.compute(password, method);
+        // compare in constant time, to not reveal how much matched
+        expected.len() == self.response.len() &&
+            expected.bytes().zip(self.response.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+
+    fn compute(&self, password: &str, method: &Method) -> String {
+        let algorithm = self.algorithm.unwrap_or(DigestAlgorithm::Md5);
+        let ha1 = algorithm.hex(&format!("{}:{}:{}", self.username, self.realm, password));
+        let ha2 = algorithm.hex(&format!("{}:{}", method, self.uri));
+        match (self.qop.as_ref(), self.nc, self.cnonce.as_ref()) {
+            (Some(qop), Some(nc), Some(cnonce)) => {
+                algorithm.hex(&format!("{}:{}:{:08x}:{}:{}:{}", ha1, self.nonce, nc, cnonce, qop, ha2))
+            },
+            _ => algorithm.hex(&format!("{}:{}:{}", ha1, self.nonce, ha2))
+        }
+    }
+}
+
+impl Scheme for Digest {
+    fn scheme() -> Option<&'static str> {
+        Some("Digest")
+    }
+
+    fn fmt_scheme(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(f.write_str("username="));
+        try!(fmt_quoted(&self.username, f));
+        try!(f.write_str(", realm="));
+        try!(fmt_quoted(&self.realm, f));
+        try!(f.write_str(", nonce="));
+        try!(fmt_quoted(&self.nonce, f));
+        try!(f.write_str(", uri="));
+        try!(fmt_quoted(&self.uri, f));
+        try!(f.write_str(", response="));
+        try!(fmt_quoted(&self.response, f));
+        if let Some(ref algorithm) = self.algorithm {
+            try!(write!(f, ", algorithm={}", algorithm));
+        }
+        if let Some(ref opaque) = self.opaque {
+            try!(f.write_str(", opaque="));
+            try!(fmt_quoted(opaque, f));
+        }
+        if let Some(ref qop) = self.qop {
+            try!(write!(f, ", qop={}", qop));
+        }
+        if let Some(nc) = self.nc {
+            try!(write!(f, ", nc={:08x}", nc));
+        }
+        if let Some(ref cnonce) = self.cnonce {
+            try!(f.write_str(", cnonce="));
+            try!(fmt_quoted(cnonce, f));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Digest {
+    type Err = ::Error;
+    fn from_str(s: &str) -> ::Result<Digest> {
+        let params = try!(parse_params(s).ok_or(::Error::Header));
+        let challenge = Challenge {
+            scheme: "Digest".to_owned(),
+            token: None,
+            params: params,
+        };
+        let required = |name| challenge.param(name).map(ToOwned::to_owned).ok_or(::Error::Header);
+        let nc = match challenge.param("nc") {
+            Some(nc) => Some(try!(u32::from_str_radix(nc, 16).map_err(|_| ::Error::Header))),
+            None => None
+        };
+        let algorithm = match challenge.param("algorithm") {
+            Some(algorithm) => Some(try!(algorithm.parse())),
+            None => None
+        };
+        Ok(Digest {
+            username: try!(required("username")),
+            realm: try!(required("realm")),
+            nonce: try!(required("nonce")),
+            uri: try!(required("uri")),
+            response: try!(required("response")),
+            algorithm: algorithm,
+            opaque: challenge.param("opaque").map(ToOwned::to_owned),
+            qop: challenge.param("qop").map(ToOwned::to_owned),
+            nc: nc,
+            cnonce: challenge.param("cnonce").map(ToOwned::to_owned),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::{Authorization, Challenge, Header, Headers};
+    use method::Method;
+    use super::{Digest, DigestAlgorithm};
+
+    // the examples of RFC7616, section 3.9.1
+    fn challenge(algorithm: &str) -> Challenge {
+        Challenge::new("Digest")
+            .with_param("realm", "http-auth@example.org")
+            .with_param("qop", "auth, auth-int")
+            .with_param("algorithm", algorithm)
+            .with_param("nonce", "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v")
+            .with_param("opaque", "FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS")
+    }
+
+    fn answer(algorithm: &str) -> Digest {
+        Digest::answer(&challenge(algorithm), "Mufasa", "Circle of Life", &Method::Get,
+                       "/dir/index.html", 1, "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ").unwrap()
+    }
+
+    #[test]
+    fn test_digest_answer() {
+        let digest = answer("MD5");
+        assert_eq!(digest.algorithm, Some(DigestAlgorithm::Md5));
+        assert_eq!(digest.qop, Some("auth".to_owned()));
+        assert_eq!(digest.response, "8ca523f5e9506fed4657c9700eebdbec");
+        assert_eq!(answer("SHA-256").response,
+                   "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1");
+
+        assert!(digest.verify("Circle of Life", &Method::Get));
+        assert!(!digest.verify("Circle of Death", &Method::Get));
+        assert!(!digest.verify("Circle of Life", &Method::Post));
+
+        assert!(Digest::answer(&challenge("SHA-512-256"), "Mufasa", "", &Method::Get, "/", 1, "x").is_none());
+        assert!(Digest::answer(&Challenge::new("Basic").with_param("realm", "x"), "Mufasa", "",
+                               &Method::Get, "/", 1, "x").is_none());
+    }
+
+    #[test]
+    fn test_digest_auth() {
+        let mut headers = Headers::new();
+        headers.set(Authorization(answer("MD5")));
+        assert_eq!(headers.to_string(), "Authorization: Digest username=\"Mufasa\", \
+            realm=\"http-auth@example.org\", nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+            uri=\"/dir/index.html\", response=\"8ca523f5e9506fed4657c9700eebdbec\", algorithm=MD5, \
+            opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\", qop=auth, nc=00000001, \
+            cnonce=\"f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ\"\r\n");
+    }
+
+    #[test]
+    fn test_digest_auth_parse() {
+        let mut headers = Headers::new();
+        headers.set(Authorization(answer("SHA-256")));
+        let auth: Authorization<Digest> = Header::parse_header(headers.get_raw("Authorization").unwrap()).unwrap();
+        assert_eq!(auth.0, answer("SHA-256"));
+        assert!(auth.verify("Circle of Life", &Method::Get));
+    }
+}
+
@@ -19,6 +19,7 @@ pub use self::accept_charset::AcceptCharset;
 pub use self::accept_encoding::AcceptEncoding;
 pub use self::accept_language::AcceptLanguage;
 pub use self::accept_ranges::{AcceptRanges, RangeUnit};
+pub use self::age::Age;
 pub use self::allow::Allow;
 pub use self::authorization::{Authorization, Scheme, Basic, Bearer};
 pub use self::cache_control::{CacheControl, CacheDirective};
@@ -32,6 +33,7 @@ pub use self::content_range::{ContentRange, ContentRangeSpec};
 pub use self::content_type::ContentType;
 pub use self::cookie::Cookie;
 pub use self::date::Date;
+pub use self::digest::{Digest, DigestAlgorithm};
 pub use self::etag::ETag;
 pub use self::expect::Expect;
 pub use self::expires::Expires;
@@ -42,12 +44,15 @@ pub use self::if_modified_since::IfModifiedSince;
 pub use self::if_none_match::IfNoneMatch;
 pub use self::if_unmodified_since::IfUnmodifiedSince;
 pub use self::if_range::IfRange;
+pub use self::last_event_id::LastEventID;
 pub use self::last_modified::LastModified;
 pub use self::location::Location;
 pub use self::origin::Origin;
 pub use self::pragma::Pragma;
 pub use self::prefer::{Prefer, Preference};
 pub use self::preference_applied::PreferenceApplied;
+pub use self::proxy_authenticate::ProxyAuthenticate;
+pub use self::proxy_authorization::ProxyAuthorization;
 pub use self::range::{Range, ByteRangeSpec};
 pub use self::referer::Referer;
 pub use self::referrer_policy::ReferrerPolicy;
@@ -59,6 +64,7 @@ pub use self::upgrade::{Upgrade, Protocol, ProtocolName};
 pub use self::user_agent::UserAgent;
 pub use self::vary::Vary;
 pub use self::warning::Warning;
+pub use self::www_authenticate::{WwwAuthenticate, Challenge};
 
 #[doc(hidden)]
 #[macro_export]
@@ -382,6 +388,7 @@ mod accept_charset;
 mod accept_encoding;
 mod accept_language;
 mod accept_ranges;
+mod age;
 mod allow;
 mod authorization;
 mod cache_control;
@@ -395,6 +402,7 @@ mod content_location;
 mod content_range;
 mod content_type;
 mod date;
+mod digest;
 mod etag;
 mod expect;
 mod expires;
@@ -405,12 +413,15 @@ mod if_modified_since;
 mod if_none_match;
 mod if_range;
 mod if_unmodified_since;
+mod last_event_id;
 mod last_modified;
 mod location;
 mod origin;
 mod pragma;
 mod prefer;
 mod preference_applied;
+mod proxy_authenticate;
+mod proxy_authorization;
 mod range;
 mod referer;
 mod referrer_policy;
@@ -421,4 +432,5 @@ mod transfer_encoding;
 mod upgrade;
 mod user_agent;
 mod vary;
-mod warning;
\ No newline at end of file
+mod warning;
+mod www_authenticate;
\ No newline at end of file
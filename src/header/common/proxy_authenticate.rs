@@ -0,0 +1,68 @@
+use std::fmt;
+
+use header::{Challenge, Header, Raw};
+use super::www_authenticate::{fmt_challenges, parse_challenges};
+
+/// `Proxy-Authenticate` header, defined in [RFC7235](https://tools.ietf.org/html/rfc7235#section-4.3)
+///
+/// The `Proxy-Authenticate` header field consists of at least one
+/// challenge that indicates the authentication scheme(s) and parameters
+/// applicable to the proxy for this effective request URI. It is sent with
+/// a 407 (Proxy Authentication Required) response.
+///
+/// # ABNF
+/// ```plain
+/// Proxy-Authenticate = 1#challenge
+/// ```
+///
+/// # Example values
+/// * `Basic realm="proxy"`
+///
+/// # Examples
+/// ```
+/// use hyper::header::{Headers, ProxyAuthenticate, Challenge};
+///
+/// let mut headers = Headers::new();
+/// headers.set(
+///     ProxyAuthenticate(vec![
+///         Challenge::new("Basic").with_param("realm", "proxy")
+///     ])
+/// );
+/// ```
+#[derive(Clone, PartialEq, Debug)]
+pub struct ProxyAuthenticate(pub Vec<Challenge>);
+
+__hyper__deref!(ProxyAuthenticate => Vec<Challenge>);
+
+impl Header for ProxyAuthenticate {
+    fn header_name() -> &'static str {
+        static NAME: &'static str = "Proxy-Authenticate";
+        NAME
+    }
+
+    fn parse_header(raw: &Raw) -> ::Result<ProxyAuthenticate> {
+        parse_challenges(raw).map(ProxyAuthenticate)
+    }
+
+    fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_challenges(&self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::{Challenge, Header, Headers};
+    use super::ProxyAuthenticate;
+
+    #[test]
+    fn test_proxy_authenticate() {
+        let header: ProxyAuthenticate = Header::parse_header(
+            &b"Basic realm=\"proxy\"".as_ref().into()).unwrap();
+        assert_eq!(header.0, vec![Challenge::new("Basic").with_param("realm", "proxy")]);
+        let mut headers = Headers::new();
+        headers.set(header);
+        assert_eq!(headers.to_string(), "Proxy-Authenticate: Basic realm=\"proxy\"\r\n");
+    }
+}
+
+bench_header!(basic, ProxyAuthenticate, { vec![b"Basic realm=\"proxy\"".to_vec()] });
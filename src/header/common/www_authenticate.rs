@@ -0,0 +1,382 @@
+use std::fmt;
+use std::str::from_utf8;
+
+use unicase::UniCase;
+
+use header::{Header, Raw};
+
+/// `WWW-Authenticate` header, defined in [RFC7235](https://tools.ietf.org/html/rfc7235#section-4.1)
+///
+/// The `WWW-Authenticate` header field indicates the authentication
+/// scheme(s) and parameters applicable to the target resource. It is sent
+/// with a 401 (Unauthorized) response.
+///
+/// # ABNF
+/// ```plain
+/// WWW-Authenticate = 1#challenge
+/// challenge        = auth-scheme [ 1*SP ( token68 / #auth-param ) ]
+/// auth-param       = token BWS "=" BWS ( token / quoted-string )
+/// ```
+///
+/// # Example values
+/// * `Basic realm="simple"`
+/// * `Digest realm="http-auth@example.org", qop="auth", algorithm=SHA-256,
+///    nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v"`
+/// * `Newauth realm="apps", type=1, title="Login to \"apps\"", Basic realm="simple"`
+///
+/// # Examples
+/// ```
+/// use hyper::header::{Headers, WwwAuthenticate, Challenge};
+///
+/// let mut headers = Headers::new();
+/// headers.set(
+///     WwwAuthenticate(vec![
+///         Challenge::new("Basic").with_param("realm", "simple")
+///     ])
+/// );
+/// ```
+#[derive(Clone, PartialEq, Debug)]
+pub struct WwwAuthenticate(pub Vec<Challenge>);
+
+__hyper__deref!(WwwAuthenticate => Vec<Challenge>);
+
+impl Header for WwwAuthenticate {
+    fn header_name() -> &'static str {
+        static NAME: &'static str = "WWW-Authenticate";
+        NAME
+    }
+
+    fn parse_header(raw: &Raw) -> ::Result<WwwAuthenticate> {
+        parse_challenges(raw).map(WwwAuthenticate)
+    }
+
+    fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_challenges(&self.0, f)
+    }
+}
+
+/// An authentication challenge, of the `WwwAuthenticate` and
+/// `ProxyAuthenticate` headers.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Challenge {
+    /// The authentication scheme, such as `Basic` or `Digest`.
+    pub scheme: String,
+    /// A token68 given instead of parameters.
+    pub token: Option<String>,
+    /// The parameters, in order.
+    pub params: Vec<(String, String)>,
+}
+
+impl Challenge {
+    /// Create a `Challenge` of the scheme, without parameters.
+    pub fn new<S: Into<String>>(scheme: S) -> Challenge {
+        Challenge {
+            scheme: scheme.into(),
+            token: None,
+            params: Vec::new(),
+        }
+    }
+
+    /// Add a parameter, returning the `Challenge` to allow chaining.
+    pub fn with_param<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Challenge {
+        self.params.push((name.into(), value.into()));
+        self
+    }
+
+    /// Whether the challenge is of this scheme, ignoring case.
+    pub fn is_scheme(&self, scheme: &str) -> bool {
+        UniCase(&self.scheme[..]) == UniCase(scheme)
+    }
+
+    /// The value of a parameter, matching its name ignoring case.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter()
+            .find(|param| UniCase(&param.0[..]) == UniCase(name))
+            .map(|param| &param.1[..])
+    }
+
+    /// The `realm` parameter.
+    pub fn realm(&self) -> Option<&str> {
+        self.param("realm")
+    }
+}
+
+impl fmt::Display for Challenge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(f.write_str(&self.scheme));
+        if let Some(ref token) = self.token {
+            return write!(f, " {}", token);
+        }
+        for (i, &(ref name, ref value)) in self.params.iter().enumerate() {
+            try!(f.write_str(if i == 0 { " " } else { ", " }));
+            try!(write!(f, "{}=", name));
+            try!(fmt_quoted(value, f));
+        }
+        Ok(())
+    }
+}
+
+/// Parse the challenges of all the lines of a header.
+pub fn parse_challenges(raw: &Raw) -> ::Result<Vec<Challenge>> {
+    let mut challenges = Vec::new();
+    for line in raw {
+        let line = try!(from_utf8(line));
+        let mut parser = Parser { s: line.as_bytes(), pos: 0 };
+        loop {
+            parser.skip_separators();
+            if parser.done() {
+                break;
+            }
+            let scheme = try!(parser.token().ok_or(::Error::Header));
+            let mut challenge = Challenge::new(scheme);
+            if parser.eat_space() {
+                if let Some(token) = parser.token68() {
+                    challenge.token = Some(token);
+                } else {
+                    challenge.params = try!(parser.params().ok_or(::Error::Header));
+                }
+            }
+            challenges.push(challenge);
+        }
+    }
+    if challenges.is_empty() {
+        return Err(::Error::Header);
+    }
+    Ok(challenges)
+}
+
+/// Parse a comma separated list of `auth-param`s, such as the credentials
+/// of an `Authorization` header.
+pub fn parse_params(s: &str) -> Option<Vec<(String, String)>> {
+    let mut parser = Parser { s: s.as_bytes(), pos: 0 };
+    parser.skip_separators();
+    let params = match parser.params() {
+        Some(params) => params,
+        None => return None
+    };
+    parser.skip_separators();
+    if parser.done() { Some(params) } else { None }
+}
+
+/// Format challenges as a comma separated list.
+pub fn fmt_challenges(challenges: &[Challenge], f: &mut fmt::Formatter) -> fmt::Result {
+    for (i, challenge) in challenges.iter().enumerate() {
+        if i > 0 {
+            try!(f.write_str(", "));
+        }
+        try!(fmt::Display::fmt(challenge, f));
+    }
+    Ok(())
+}
+
+/// Format a value as a quoted-string.
+pub fn fmt_quoted(value: &str, f: &mut fmt::Formatter) -> fmt::Result {
+    try!(f.write_str("\""));
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            try!(f.write_str("\\"));
+        }
+        try!(write!(f, "{}", c));
+    }
+    f.write_str("\"")
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn done(&self) -> bool {
+        self.pos >= self.s.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).cloned()
+    }
+
+    fn skip_space(&mut self) {
+        while self.peek() == Some(b' ') || self.peek() == Some(b'\t') {
+            self.pos += 1;
+        }
+    }
+
+    /// Skips at least one space, returning whether there was any.
+    fn eat_space(&mut self) -> bool {
+        let start = self.pos;
+        self.skip_space();
+        self.pos > start
+    }
+
+    fn skip_separators(&mut self) {
+        while self.peek().map_or(false, |b| b == b',' || b == b' ' || b == b'\t') {
+            self.pos += 1;
+        }
+    }
+
+    fn take_while<F: Fn(u8) -> bool>(&mut self, f: F) -> &'a str {
+        let start = self.pos;
+        while self.peek().map_or(false, |b| f(b)) {
+            self.pos += 1;
+        }
+        // only ASCII bytes are taken, so this can't split a character
+        from_utf8(&self.s[start..self.pos]).unwrap_or("")
+    }
+
+    fn token(&mut self) -> Option<String> {
+        let token = self.take_while(is_tchar);
+        if token.is_empty() { None } else { Some(token.to_owned()) }
+    }
+
+    /// A token68 ends the challenge, so is only taken if followed by the
+    /// end or a comma.
+    fn token68(&mut self) -> Option<String> {
+        let start = self.pos;
+        let mut len = self.take_while(is_token68_char).len();
+        len += self.take_while(|b| b == b'=').len();
+        let end = self.pos;
+        self.skip_space();
+        if len > 0 && (self.done() || self.peek() == Some(b',')) {
+            Some(from_utf8(&self.s[start..end]).unwrap_or("").to_owned())
+        } else {
+            self.pos = start;
+            None
+        }
+    }
+
+    fn quoted(&mut self) -> Option<String> {
+        let mut value = Vec::new();
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                None => return None,
+                Some(b'"') => {
+                    self.pos += 1;
+                    return String::from_utf8(value).ok();
+                },
+                Some(b'\\') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(b) => value.push(b),
+                        None => return None
+                    }
+                },
+                Some(b) => value.push(b)
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Parses `auth-param`s, stopping before anything that isn't one, such
+    /// as the scheme of the next challenge.
+    fn params(&mut self) -> Option<Vec<(String, String)>> {
+        let mut params = Vec::new();
+        loop {
+            let start = self.pos;
+            let name = match self.token() {
+                Some(name) => name,
+                None => break
+            };
+            self.skip_space();
+            if self.peek() != Some(b'=') {
+                self.pos = start;
+                break;
+            }
+            self.pos += 1;
+            self.skip_space();
+            let value = if self.peek() == Some(b'"') {
+                self.quoted()
+            } else {
+                self.token()
+            };
+            let value = match value {
+                Some(value) => value,
+                None => return None
+            };
+            params.push((name, value));
+            self.skip_space();
+            if self.peek() != Some(b',') {
+                break;
+            }
+            self.skip_separators();
+        }
+        if params.is_empty() { None } else { Some(params) }
+    }
+}
+
+fn is_tchar(b: u8) -> bool {
+    match b {
+        b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' |
+        b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'*' | b'+' | b'-' | b'.' |
+        b'^' | b'_' | b'`' | b'|' | b'~' => true,
+        _ => false
+    }
+}
+
+fn is_token68_char(b: u8) -> bool {
+    match b {
+        b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' |
+        b'-' | b'.' | b'_' | b'~' | b'+' | b'/' => true,
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::{Header, Headers};
+    use super::{Challenge, WwwAuthenticate, parse_params};
+
+    #[test]
+    fn test_www_authenticate_parse() {
+        let header: WwwAuthenticate = Header::parse_header(&b"Newauth realm=\"apps\", type=1, \
+            title=\"Login to \\\"apps\\\"\", Basic realm=\"simple\"".as_ref().into()).unwrap();
+        assert_eq!(header.0, vec![
+            Challenge::new("Newauth")
+                .with_param("realm", "apps")
+                .with_param("type", "1")
+                .with_param("title", "Login to \"apps\""),
+            Challenge::new("Basic").with_param("realm", "simple"),
+        ]);
+        assert_eq!(header[0].realm(), Some("apps"));
+        assert!(header[1].is_scheme("basic"));
+    }
+
+    #[test]
+    fn test_www_authenticate_parse_token68() {
+        let header: WwwAuthenticate = Header::parse_header(
+            &b"Negotiate abc/+==, Bearer, Basic realm=x".as_ref().into()).unwrap();
+        let mut negotiate = Challenge::new("Negotiate");
+        negotiate.token = Some("abc/+==".to_owned());
+        assert_eq!(header.0, vec![
+            negotiate,
+            Challenge::new("Bearer"),
+            Challenge::new("Basic").with_param("realm", "x"),
+        ]);
+    }
+
+    #[test]
+    fn test_www_authenticate_parse_invalid() {
+        assert!(WwwAuthenticate::parse_header(&b"".as_ref().into()).is_err());
+        assert!(WwwAuthenticate::parse_header(&b"Basic realm=\"open".as_ref().into()).is_err());
+    }
+
+    #[test]
+    fn test_www_authenticate_fmt() {
+        let mut headers = Headers::new();
+        headers.set(WwwAuthenticate(vec![
+            Challenge::new("Digest").with_param("realm", "a \"b\"").with_param("qop", "auth"),
+            Challenge::new("Basic").with_param("realm", "c"),
+        ]));
+        assert_eq!(headers.to_string(),
+                   "WWW-Authenticate: Digest realm=\"a \\\"b\\\"\", qop=\"auth\", Basic realm=\"c\"\r\n");
+    }
+
+    #[test]
+    fn test_parse_params() {
+        assert_eq!(parse_params("a=1, b=\"2, 3\""),
+                   Some(vec![("a".to_owned(), "1".to_owned()), ("b".to_owned(), "2, 3".to_owned())]));
+        assert_eq!(parse_params("a=1 b"), None);
+    }
+}
+
+bench_header!(basic, WwwAuthenticate, { vec![b"Basic realm=\"simple\"".to_vec()] });
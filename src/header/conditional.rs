@@ -0,0 +1,221 @@
+//! Evaluating conditional requests, following the precedence of
+//! [RFC7232](https://tools.ietf.org/html/rfc7232#section-6).
+
+use method::Method;
+use status::StatusCode;
+use super::{EntityTag, Headers, HttpDate, IfMatch, IfModifiedSince, IfNoneMatch, IfRange,
+            IfUnmodifiedSince};
+
+/// The outcome of evaluating the preconditions of a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precondition {
+    /// The preconditions hold, or there are none, so the request should be
+    /// performed.
+    Proceed,
+    /// The client's copy is current, and should be answered with
+    /// `304 Not Modified`.
+    NotModified,
+    /// A precondition failed, and should be answered with
+    /// `412 Precondition Failed`.
+    Failed,
+}
+
+impl Precondition {
+    /// The status to answer with instead of performing the request, if any.
+    pub fn status(&self) -> Option<StatusCode> {
+        match *self {
+            Precondition::Proceed => None,
+            Precondition::NotModified => Some(StatusCode::NotModified),
+            Precondition::Failed => Some(StatusCode::PreconditionFailed),
+        }
+    }
+}
+
+impl Headers {
+    /// Evaluate the `IfMatch`, `IfUnmodifiedSince`, `IfNoneMatch` and
+    /// `IfModifiedSince` headers of a request of `method`, against the
+    /// current `etag` and `last_modified` date of the resource.
+    ///
+    /// Either validator is `None` if the resource doesn't have one, and both
+    /// are `None` if it doesn't exist.
+    ///
+    /// # Example
+    /// ```
+    /// use hyper::header::{EntityTag, Headers, IfNoneMatch, Precondition};
+    /// use hyper::method::Method;
+    ///
+    /// let etag = EntityTag::strong("xyzzy".to_owned());
+    /// let mut headers = Headers::new();
+    /// headers.set(IfNoneMatch::Items(vec![EntityTag::weak("xyzzy".to_owned())]));
+    ///
+    /// assert_eq!(headers.evaluate_preconditions(&Method::Get, Some(&etag), None),
+    ///            Precondition::NotModified);
+    /// assert_eq!(headers.evaluate_preconditions(&Method::Put, Some(&etag), None),
+    ///            Precondition::Failed);
+    /// ```
+    pub fn evaluate_preconditions(&self, method: &Method, etag: Option<&EntityTag>,
+                                  last_modified: Option<&HttpDate>) -> Precondition {
+        let exists = etag.is_some() || last_modified.is_some();
+        let safe = *method == Method::Get || *method == Method::Head;
+
+        if let Some(if_match) = self.get::<IfMatch>() {
+            let matches = match *if_match {
+                IfMatch::Any => exists,
+                IfMatch::Items(ref tags) => etag.map_or(false, |etag| tags.iter().any(|tag| tag.strong_eq(etag))),
+            };
+            if !matches {
+                return Precondition::Failed;
+            }
+        } else if let (Some(&IfUnmodifiedSince(ref since)), Some(modified)) =
+                (self.get::<IfUnmodifiedSince>(), last_modified) {
+            if secs(modified) > secs(since) {
+                return Precondition::Failed;
+            }
+        }
+
+        if let Some(if_none_match) = self.get::<IfNoneMatch>() {
+            let matches = match *if_none_match {
+                IfNoneMatch::Any => exists,
+                IfNoneMatch::Items(ref tags) => etag.map_or(false, |etag| tags.iter().any(|tag| tag.weak_eq(etag))),
+            };
+            if matches {
+                return if safe { Precondition::NotModified } else { Precondition::Failed };
+            }
+        } else if safe {
+            if let (Some(&IfModifiedSince(ref since)), Some(modified)) =
+                    (self.get::<IfModifiedSince>(), last_modified) {
+                if secs(modified) <= secs(since) {
+                    return Precondition::NotModified;
+                }
+            }
+        }
+
+        Precondition::Proceed
+    }
+
+    /// Whether the `Range` of a request should be served, given its
+    /// `IfRange` and the current `etag` and `last_modified` date of the
+    /// resource.
+    ///
+    /// Without an `IfRange`, this is always `true`. An entity-tag must match
+    /// strongly, and a date exactly.
+    pub fn range_applies(&self, etag: Option<&EntityTag>, last_modified: Option<&HttpDate>) -> bool {
+        match self.get::<IfRange>() {
+            Some(&IfRange::EntityTag(ref tag)) => etag.map_or(false, |etag| tag.strong_eq(etag)),
+            Some(&IfRange::Date(ref date)) => last_modified.map_or(false, |modified| secs(modified) == secs(date)),
+            None => true
+        }
+    }
+}
+
+/// HTTP dates only have a precision of seconds.
+fn secs(date: &HttpDate) -> i64 {
+    date.0.to_timespec().sec
+}
+
+#[cfg(test)]
+mod tests {
+    use time;
+
+    use header::{EntityTag, Headers, HttpDate, IfMatch, IfModifiedSince, IfNoneMatch, IfRange,
+                 IfUnmodifiedSince};
+    use method::Method;
+    use super::Precondition;
+
+    fn date(secs: i64) -> HttpDate {
+        HttpDate(time::at_utc(time::Timespec::new(secs, 0)))
+    }
+
+    fn tag(tag: &str) -> EntityTag {
+        EntityTag::strong(tag.to_owned())
+    }
+
+    #[test]
+    fn test_no_preconditions() {
+        let headers = Headers::new();
+        assert_eq!(headers.evaluate_preconditions(&Method::Get, Some(&tag("a")), Some(&date(10))),
+                   Precondition::Proceed);
+        assert_eq!(Precondition::Proceed.status(), None);
+    }
+
+    #[test]
+    fn test_if_match() {
+        let mut headers = Headers::new();
+        headers.set(IfMatch::Items(vec![tag("a"), tag("b")]));
+        assert_eq!(headers.evaluate_preconditions(&Method::Put, Some(&tag("b")), None), Precondition::Proceed);
+        assert_eq!(headers.evaluate_preconditions(&Method::Put, Some(&tag("c")), None), Precondition::Failed);
+        // If-Match uses the strong comparison
+        let weak = EntityTag::weak("a".to_owned());
+        assert_eq!(headers.evaluate_preconditions(&Method::Put, Some(&weak), None), Precondition::Failed);
+
+        headers.set(IfMatch::Any);
+        assert_eq!(headers.evaluate_preconditions(&Method::Put, None, Some(&date(10))), Precondition::Proceed);
+        assert_eq!(headers.evaluate_preconditions(&Method::Put, None, None), Precondition::Failed);
+        assert_eq!(Precondition::Failed.status(), Some(::status::StatusCode::PreconditionFailed));
+    }
+
+    #[test]
+    fn test_if_unmodified_since() {
+        let mut headers = Headers::new();
+        headers.set(IfUnmodifiedSince(date(10)));
+        assert_eq!(headers.evaluate_preconditions(&Method::Delete, None, Some(&date(10))), Precondition::Proceed);
+        assert_eq!(headers.evaluate_preconditions(&Method::Delete, None, Some(&date(11))), Precondition::Failed);
+        // ignored without a date to compare
+        assert_eq!(headers.evaluate_preconditions(&Method::Delete, Some(&tag("a")), None), Precondition::Proceed);
+        // and when If-Match is present
+        headers.set(IfMatch::Items(vec![tag("a")]));
+        assert_eq!(headers.evaluate_preconditions(&Method::Delete, Some(&tag("a")), Some(&date(11))),
+                   Precondition::Proceed);
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let mut headers = Headers::new();
+        headers.set(IfNoneMatch::Items(vec![EntityTag::weak("a".to_owned())]));
+        assert_eq!(headers.evaluate_preconditions(&Method::Get, Some(&tag("a")), None), Precondition::NotModified);
+        assert_eq!(headers.evaluate_preconditions(&Method::Head, Some(&tag("a")), None), Precondition::NotModified);
+        assert_eq!(headers.evaluate_preconditions(&Method::Post, Some(&tag("a")), None), Precondition::Failed);
+        assert_eq!(headers.evaluate_preconditions(&Method::Get, Some(&tag("b")), None), Precondition::Proceed);
+
+        // If-None-Match takes precedence over If-Modified-Since
+        headers.set(IfModifiedSince(date(10)));
+        assert_eq!(headers.evaluate_preconditions(&Method::Get, Some(&tag("b")), Some(&date(5))),
+                   Precondition::Proceed);
+
+        headers.set(IfNoneMatch::Any);
+        assert_eq!(headers.evaluate_preconditions(&Method::Put, None, None), Precondition::Proceed);
+        assert_eq!(headers.evaluate_preconditions(&Method::Put, Some(&tag("b")), None), Precondition::Failed);
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let mut headers = Headers::new();
+        headers.set(IfModifiedSince(date(10)));
+        assert_eq!(headers.evaluate_preconditions(&Method::Get, None, Some(&date(10))), Precondition::NotModified);
+        assert_eq!(headers.evaluate_preconditions(&Method::Get, None, Some(&date(11))), Precondition::Proceed);
+        // only for GET and HEAD
+        assert_eq!(headers.evaluate_preconditions(&Method::Put, None, Some(&date(10))), Precondition::Proceed);
+        assert_eq!(Precondition::NotModified.status(), Some(::status::StatusCode::NotModified));
+    }
+
+    #[test]
+    fn test_if_modified_since_after_failed_if_match() {
+        let mut headers = Headers::new();
+        headers.set(IfMatch::Items(vec![tag("a")]));
+        headers.set(IfModifiedSince(date(10)));
+        assert_eq!(headers.evaluate_preconditions(&Method::Get, Some(&tag("b")), Some(&date(5))),
+                   Precondition::Failed);
+    }
+
+    #[test]
+    fn test_range_applies() {
+        let mut headers = Headers::new();
+        assert!(headers.range_applies(None, None));
+        headers.set(IfRange::EntityTag(tag("a")));
+        assert!(headers.range_applies(Some(&tag("a")), None));
+        assert!(!headers.range_applies(Some(&EntityTag::weak("a".to_owned())), None));
+        headers.set(IfRange::Date(date(10)));
+        assert!(headers.range_applies(None, Some(&date(10))));
+        assert!(!headers.range_applies(None, Some(&date(11))));
+    }
+}
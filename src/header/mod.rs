@@ -92,10 +92,13 @@ use serde::de;
 
 pub use self::shared::*;
 pub use self::common::*;
+pub use self::conditional::Precondition;
 pub use self::raw::Raw;
 
 mod common;
+mod conditional;
 mod internals;
+mod negotiation;
 mod raw;
 mod shared;
 pub mod parsing;
@@ -0,0 +1,224 @@
+//! Proactive content negotiation, as described in
+//! [RFC7231](https://tools.ietf.org/html/rfc7231#section-5.3).
+//!
+//! Each of the `Accept` headers can pick the best of the representations a
+//! server offers. Every offer is given the quality of the most specific
+//! item of the header matching it, and the offer of the highest quality
+//! wins, with ties going to the offer listed first. An offer of quality `0`
+//! is never picked.
+
+use language_tags::LanguageTag;
+use mime::{Mime, TopLevel, SubLevel};
+use unicase::UniCase;
+
+use super::{Accept, AcceptCharset, AcceptEncoding, AcceptLanguage, Charset, Encoding, QualityItem};
+
+impl Accept {
+    /// Pick the best of the `offered` media types.
+    ///
+    /// Ranges such as `text/*` and `*/*` match the types they cover, and
+    /// a range with parameters only matches types with the same parameters.
+    ///
+    /// # Example
+    /// ```
+    /// use hyper::header::{Accept, qitem, Quality, QualityItem};
+    /// use hyper::mime::Mime;
+    ///
+    /// let accept = Accept(vec![
+    ///     QualityItem::new("text/*".parse().unwrap(), Quality(500)),
+    ///     qitem("application/json".parse().unwrap()),
+    /// ]);
+    /// let offered: Vec<Mime> = vec!["text/html".parse().unwrap(), "application/json".parse().unwrap()];
+    /// assert_eq!(accept.negotiate(&offered), Some(&offered[1]));
+    /// ```
+    pub fn negotiate<'a>(&self, offered: &'a [Mime]) -> Option<&'a Mime> {
+        best(&self.0, offered, media_range, |_| 0)
+    }
+}
+
+impl AcceptLanguage {
+    /// Pick the best of the `offered` language tags.
+    ///
+    /// Ranges match the tags they are a prefix of, so `en` matches `en-GB`.
+    pub fn negotiate<'a>(&self, offered: &'a [LanguageTag]) -> Option<&'a LanguageTag> {
+        best(&self.0, offered, language_range, |_| 0)
+    }
+}
+
+impl AcceptCharset {
+    /// Pick the best of the `offered` charsets.
+    ///
+    /// A `*` matches any charset not listed.
+    pub fn negotiate<'a>(&self, offered: &'a [Charset]) -> Option<&'a Charset> {
+        best(&self.0, offered, |range, charset| {
+            match *range {
+                Charset::Ext(ref s) if s == "*" => Some(0),
+                _ if UniCase(range.to_string()) == UniCase(charset.to_string()) => Some(1),
+                _ => None
+            }
+        }, |_| 0)
+    }
+}
+
+impl AcceptEncoding {
+    /// Pick the best of the `offered` content codings.
+    ///
+    /// A `*` matches any coding not listed, and `identity` is acceptable
+    /// unless it, or `*`, is given a quality of `0`.
+    pub fn negotiate<'a>(&self, offered: &'a [Encoding]) -> Option<&'a Encoding> {
+        best(&self.0, offered, |range, coding| {
+            match *range {
+                Encoding::EncodingExt(ref s) if s == "*" => Some(0),
+                _ if range == coding => Some(1),
+                _ => None
+            }
+        }, |coding| if *coding == Encoding::Identity { 1000 } else { 0 })
+    }
+}
+
+/// Picks the offer of the highest quality.
+///
+/// `specificity` gives how specifically a range matches an offer, if it
+/// does, and `unlisted` the quality of an offer no range matches.
+fn best<'a, T, U, F, G>(accepted: &[QualityItem<T>], offered: &'a [U], specificity: F, unlisted: G)
+                        -> Option<&'a U>
+where F: Fn(&T, &U) -> Option<usize>, G: Fn(&U) -> u16 {
+    let mut best = None;
+    let mut best_quality = 0;
+    for offer in offered {
+        let mut matched: Option<(usize, u16)> = None;
+        for item in accepted {
+            if let Some(specificity) = specificity(&item.item, offer) {
+                if matched.map_or(true, |(most, _)| specificity > most) {
+                    matched = Some((specificity, item.quality.0));
+                }
+            }
+        }
+        let quality = matched.map_or_else(|| unlisted(offer), |(_, quality)| quality);
+        if quality > best_quality {
+            best = Some(offer);
+            best_quality = quality;
+        }
+    }
+    best
+}
+
+/// `*/*` is the least specific, then `type/*`, then `type/subtype`, and
+/// then by the number of parameters.
+fn media_range(range: &Mime, mime: &Mime) -> Option<usize> {
+    let specificity = match (&range.0, &range.1) {
+        (&TopLevel::Star, &SubLevel::Star) => 0,
+        (top, &SubLevel::Star) if *top == mime.0 => 1,
+        (top, sub) if *top == mime.0 && *sub == mime.1 => 2,
+        _ => return None
+    };
+    for &(ref attr, ref value) in range.2.iter() {
+        if mime.get_param(attr.clone()) != Some(value) {
+            return None;
+        }
+    }
+    Some(specificity + range.2.len())
+}
+
+/// Longer ranges are more specific.
+fn language_range(range: &LanguageTag, tag: &LanguageTag) -> Option<usize> {
+    if range.is_language_range() && range.matches(tag) {
+        Some(range.to_string().len())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use language_tags::LanguageTag;
+    use mime::Mime;
+
+    use header::{Accept, AcceptCharset, AcceptEncoding, AcceptLanguage, Charset, Encoding, Quality,
+                 QualityItem, qitem};
+
+    fn mimes(s: &[&str]) -> Vec<Mime> {
+        s.iter().map(|s| s.parse().unwrap()).collect()
+    }
+
+    fn q<T>(item: T, quality: u16) -> QualityItem<T> {
+        QualityItem::new(item, Quality(quality))
+    }
+
+    #[test]
+    fn test_accept_negotiate() {
+        let offered = mimes(&["text/html", "application/json", "image/png"]);
+        let accept = Accept(vec![
+            q("text/*".parse().unwrap(), 500),
+            qitem("application/json".parse().unwrap()),
+        ]);
+        assert_eq!(accept.negotiate(&offered), Some(&offered[1]));
+
+        // the most specific range gives the quality
+        let accept = Accept(vec![
+            qitem("*/*".parse().unwrap()),
+            q("image/*".parse().unwrap(), 0),
+            q("text/html".parse().unwrap(), 100),
+        ]);
+        assert_eq!(accept.negotiate(&offered), Some(&offered[1]));
+        assert_eq!(accept.negotiate(&offered[2..]), None);
+
+        // ties go to the first offer
+        let accept = Accept(vec![qitem("*/*".parse().unwrap())]);
+        assert_eq!(accept.negotiate(&offered), Some(&offered[0]));
+        assert_eq!(accept.negotiate(&[]), None);
+    }
+
+    #[test]
+    fn test_accept_negotiate_params() {
+        let offered = mimes(&["text/plain; charset=utf-8", "text/plain"]);
+        let accept = Accept(vec![
+            q("text/plain".parse().unwrap(), 300),
+            qitem("text/plain; charset=utf-8".parse().unwrap()),
+        ]);
+        assert_eq!(accept.negotiate(&offered), Some(&offered[0]));
+        assert_eq!(accept.negotiate(&offered[1..]), Some(&offered[1]));
+
+        let accept = Accept(vec![qitem("text/plain; charset=utf-8".parse().unwrap())]);
+        assert_eq!(accept.negotiate(&offered[1..]), None);
+    }
+
+    #[test]
+    fn test_accept_language_negotiate() {
+        let tags = |s: &[&str]| s.iter().map(|s| s.parse().unwrap()).collect::<Vec<LanguageTag>>();
+        let offered = tags(&["de", "en-US", "en-GB"]);
+        let accept = AcceptLanguage(vec![
+            q("en".parse().unwrap(), 500),
+            q("en-GB".parse().unwrap(), 800),
+            q("de".parse().unwrap(), 100),
+        ]);
+        assert_eq!(accept.negotiate(&offered), Some(&offered[2]));
+        assert_eq!(accept.negotiate(&offered[..2]), Some(&offered[1]));
+        assert_eq!(accept.negotiate(&tags(&["fr"])), None);
+    }
+
+    #[test]
+    fn test_accept_charset_negotiate() {
+        let offered = vec![Charset::Iso_8859_1, Charset::Ext("utf-8".to_owned())];
+        let accept = AcceptCharset(vec![qitem(Charset::Ext("UTF-8".to_owned())), q(Charset::Iso_8859_1, 500)]);
+        assert_eq!(accept.negotiate(&offered), Some(&offered[1]));
+        let accept = AcceptCharset(vec![q(Charset::Ext("*".to_owned()), 200), q(Charset::Iso_8859_1, 0)]);
+        assert_eq!(accept.negotiate(&offered), Some(&offered[1]));
+        assert_eq!(accept.negotiate(&offered[..1]), None);
+    }
+
+    #[test]
+    fn test_accept_encoding_negotiate() {
+        let offered = vec![Encoding::Gzip, Encoding::Deflate, Encoding::Identity];
+        let accept = AcceptEncoding(vec![q(Encoding::Gzip, 500), qitem(Encoding::Deflate)]);
+        assert_eq!(accept.negotiate(&offered), Some(&offered[1]));
+
+        // identity is acceptable unless excluded
+        let accept = AcceptEncoding(vec![q(Encoding::Gzip, 0)]);
+        assert_eq!(accept.negotiate(&offered), Some(&offered[2]));
+        let accept = AcceptEncoding(vec![q(Encoding::EncodingExt("*".to_owned()), 0)]);
+        assert_eq!(accept.negotiate(&offered), None);
+        let accept = AcceptEncoding(vec![]);
+        assert_eq!(accept.negotiate(&offered), Some(&offered[2]));
+    }
+}
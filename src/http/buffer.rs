@@ -98,6 +98,13 @@ impl<'a, R: io::Read + 'a> BufReader<'a, R> {
     pub fn get_ref(&self) -> &R {
         self.reader
     }
+
+    pub fn reborrow(&mut self) -> BufReader<R> {
+        BufReader {
+            buf: &mut *self.buf,
+            reader: &mut *self.reader
+        }
+    }
 }
 
 impl<'a, R: io::Read> Read for BufReader<'a, R> {
@@ -4,7 +4,7 @@ use std::hash::Hash;
 use std::io;
 use std::marker::PhantomData;
 use std::mem;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use rotor::{self, EventSet, PollOpt, Scope};
 
@@ -35,6 +35,7 @@ struct ConnInner<K: Key, T: Transport, H: MessageHandler<T>> {
     ctrl: (channel::Sender<Next>, channel::Receiver<Next>),
     keep_alive_enabled: bool,
     key: K,
+    reused: bool,
     state: State<H, T>,
     transport: T,
 }
@@ -165,14 +166,16 @@ impl<K: Key, T: Transport, H: MessageHandler<T>> ConnInner<K, T, H> {
                         return State::Closed;
                     }
                 };
-                let mut handler = match scope.create(Seed(&self.key, &self.ctrl.0)) {
+                let mut handler = match scope.create(Seed(&self.key, &self.ctrl.0, self.reused)) {
                     Some(handler) => handler,
                     None => unreachable!()
                 };
+                self.reused = true;
                 match H::Message::decoder(&head) {
                     Ok(decoder) => {
                         trace!("decoder = {:?}", decoder);
                         let keep_alive = self.keep_alive_enabled && head.should_keep_alive();
+                        let started = Instant::now();
                         let next = handler.on_incoming(head, &self.transport);
                         trace!("handler.on_incoming() -> {:?}", next);
 
@@ -183,6 +186,10 @@ impl<K: Key, T: Transport, H: MessageHandler<T>> ConnInner<K, T, H> {
                                 writing: Writing::Init,
                                 keep_alive: keep_alive,
                                 timeout: next.timeout,
+                                read: 0,
+                                written: 0,
+                                started: started,
+                                completed: false,
                                 _marker: PhantomData,
                             })),
                             Next_::Write => State::Http1(Http1 {
@@ -199,6 +206,10 @@ impl<K: Key, T: Transport, H: MessageHandler<T>> ConnInner<K, T, H> {
                                 writing: Writing::Head,
                                 keep_alive: keep_alive,
                                 timeout: next.timeout,
+                                read: 0,
+                                written: 0,
+                                started: started,
+                                completed: false,
                                 _marker: PhantomData,
                             }),
                             Next_::ReadWrite => self.read(scope, State::Http1(Http1 {
@@ -207,6 +218,10 @@ impl<K: Key, T: Transport, H: MessageHandler<T>> ConnInner<K, T, H> {
                                 writing: Writing::Head,
                                 keep_alive: keep_alive,
                                 timeout: next.timeout,
+                                read: 0,
+                                written: 0,
+                                started: started,
+                                completed: false,
                                 _marker: PhantomData,
                             })),
                             Next_::Wait => State::Http1(Http1 {
@@ -215,6 +230,10 @@ impl<K: Key, T: Transport, H: MessageHandler<T>> ConnInner<K, T, H> {
                                 writing: Writing::Init,
                                 keep_alive: keep_alive,
                                 timeout: next.timeout,
+                                read: 0,
+                                written: 0,
+                                started: started,
+                                completed: false,
                                 _marker: PhantomData,
                             }),
                             Next_::End |
@@ -263,6 +282,7 @@ impl<K: Key, T: Transport, H: MessageHandler<T>> ConnInner<K, T, H> {
                             io::ErrorKind::Interrupted => None,
                             _ => {
                                 debug!("io error trying to parse {:?}", e);
+                                http1.handler.on_incoming_error(e, !self.buf.is_empty());
                                 return State::Closed;
                             }
                         },
@@ -279,7 +299,10 @@ impl<K: Key, T: Transport, H: MessageHandler<T>> ConnInner<K, T, H> {
                             super::Trans::Port(&mut self.transport)
                         };
 
-                        Some(http1.handler.on_decode(&mut Decoder::h1(decoder, wrapped)))
+                        let before = decoder.bytes_read();
+                        let next = http1.handler.on_decode(&mut Decoder::h1(decoder, wrapped));
+                        http1.read += decoder.bytes_read() - before;
+                        Some(next)
                     },
                     _ => {
                         trace!("Conn.on_readable State::Http1(reading = {:?})", http1.reading);
@@ -316,13 +339,15 @@ impl<K: Key, T: Transport, H: MessageHandler<T>> ConnInner<K, T, H> {
                 // this is a Client request, which writes first, so pay
                 // attention to the version written here, which will adjust
                 // our internal state to Http1 or Http2
-                let mut handler = match scope.create(Seed(&self.key, &self.ctrl.0)) {
+                let mut handler = match scope.create(Seed(&self.key, &self.ctrl.0, self.reused)) {
                     Some(handler) => handler,
                     None => {
                         trace!("could not create handler {:?}", self.key);
                         return State::Closed;
                     }
                 };
+                self.reused = true;
+                let started = Instant::now();
                 let mut head = http::MessageHead::default();
                 let mut interest = handler.on_outgoing(&mut head);
                 if head.version == HttpVersion::Http11 {
@@ -348,12 +373,20 @@ impl<K: Key, T: Transport, H: MessageHandler<T>> ConnInner<K, T, H> {
                             next: (encoder, interest.clone())
                         })
                     };
+                    let written = match writing {
+                        Writing::Ready(ref encoder) => encoder.bytes_written(),
+                        _ => 0
+                    };
                     state = State::Http1(Http1 {
                         reading: Reading::Init,
                         writing: writing,
                         handler: handler,
                         keep_alive: keep_alive,
                         timeout: interest.timeout,
+                        read: 0,
+                        written: written,
+                        started: started,
+                        completed: false,
                         _marker: PhantomData,
                     })
                 }
@@ -363,7 +396,7 @@ impl<K: Key, T: Transport, H: MessageHandler<T>> ConnInner<K, T, H> {
                 trace!("Conn.on_writable State::{:?}", state);
                 None
             }
-            State::Http1(Http1 { ref mut handler, ref mut writing, ref mut keep_alive, .. }) => {
+            State::Http1(Http1 { ref mut handler, ref mut writing, ref mut keep_alive, ref mut written, .. }) => {
                 match *writing {
                     Writing::Init => {
                         trace!("Conn.on_writable Http1::Writing::Init");
@@ -391,6 +424,7 @@ impl<K: Key, T: Transport, H: MessageHandler<T>> ConnInner<K, T, H> {
                                     pos: 0
                                 });
                                 interest = handler.on_encode(&mut Encoder::h1(&mut encoder, &mut self.transport));
+                                *written += encoder.bytes_written();
                                 Writing::Ready(encoder)
                             },
                             _ => Writing::Chunk(Chunk {
@@ -424,7 +458,10 @@ impl<K: Key, T: Transport, H: MessageHandler<T>> ConnInner<K, T, H> {
                     },
                     Writing::Ready(ref mut encoder) => {
                         trace!("Http1.Ready on_writable");
-                        Some(handler.on_encode(&mut Encoder::h1(encoder, &mut self.transport)))
+                        let before = encoder.bytes_written();
+                        let next = handler.on_encode(&mut Encoder::h1(encoder, &mut self.transport));
+                        *written += encoder.bytes_written() - before;
+                        Some(next)
                     },
                     Writing::Wait(..) => {
                         trace!("Conn.on_writable Http1::Writing::Wait");
@@ -490,7 +527,10 @@ impl<K: Key, T: Transport, H: MessageHandler<T>> ConnInner<K, T, H> {
         debug!("on_remove");
         match self.state {
             State::Init { .. } | State::Closed => (),
-            State::Http1(http1) => http1.handler.on_remove(self.transport),
+            State::Http1(mut http1) => {
+                http1.complete();
+                http1.handler.on_remove(self.transport)
+            }
         }
     }
 
@@ -503,6 +543,7 @@ impl<K: Key, T: Transport, H: MessageHandler<T>> Conn<K, T, H> {
             ctrl: channel::new(notify),
             keep_alive_enabled: true,
             key: key,
+            reused: false,
             state: State::Init {
                 interest: next.interest,
                 timeout: next.timeout,
@@ -692,6 +733,7 @@ impl<H: MessageHandler<T>, T: Transport> State<H, T> {
             let timeout = next.timeout;
             let state = mem::replace(self, State::Closed);
             match (state, next.interest) {
+                (State::Http1(mut http1), Next_::Remove) => http1.complete(),
                 (_, Next_::Remove) |
                 (State::Closed, _) => return, // Keep State::Closed.
                 (State::Init { .. }, e) => {
@@ -705,6 +747,7 @@ impl<H: MessageHandler<T>, T: Transport> State<H, T> {
                     match next_ {
                         Next_::Remove => unreachable!(), // Covered in (_, Next_::Remove) case above.
                         Next_::End => {
+                            http1.complete();
                             let reading = match http1.reading {
                                 Reading::Body(ref decoder) |
                                 Reading::Wait(ref decoder) if decoder.is_eof() => {
@@ -884,9 +927,27 @@ struct Http1<H, T> {
     writing: Writing,
     keep_alive: bool,
     timeout: Option<Duration>,
+    read: u64,
+    written: u64,
+    started: Instant,
+    completed: bool,
     _marker: PhantomData<T>,
 }
 
+impl<H: MessageHandler<T>, T: Transport> Http1<H, T> {
+    /// Tells the handler the message is done, the first time it is.
+    fn complete(&mut self) {
+        if !self.completed {
+            self.completed = true;
+            self.handler.on_complete(MessageStats {
+                read: self.read,
+                written: self.written,
+                elapsed: self.started.elapsed(),
+            });
+        }
+    }
+}
+
 impl<H, T> fmt::Debug for Http1<H, T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Http1")
@@ -894,6 +955,8 @@ impl<H, T> fmt::Debug for Http1<H, T> {
             .field("writing", &self.writing)
             .field("keep_alive", &self.keep_alive)
             .field("timeout", &self.timeout)
+            .field("read", &self.read)
+            .field("written", &self.written)
             .finish()
     }
 }
@@ -940,10 +1003,31 @@ pub trait MessageHandler<T: Transport> {
     fn on_encode(&mut self, &mut http::Encoder<T>) -> Next;
     fn on_error(&mut self, err: ::Error) -> Next;
 
+    /// Called when the connection fails while waiting for the incoming head,
+    /// just before it is closed. `partial` is whether some of the head had
+    /// already arrived.
+    ///
+    /// By default, this is ignored.
+    fn on_incoming_error(&mut self, _err: io::Error, _partial: bool) {}
+
+    /// Called once the message is done, whether it ended or was aborted.
+    fn on_complete(&mut self, _stats: MessageStats) {}
+
     fn on_remove(self, T) where Self: Sized;
 }
 
-pub struct Seed<'a, K: Key + 'a>(&'a K, &'a channel::Sender<Next>);
+/// What a `Conn` saw of a message, once it is done.
+#[derive(Debug, Clone, Copy)]
+pub struct MessageStats {
+    /// The body bytes read.
+    pub read: u64,
+    /// The body bytes written.
+    pub written: u64,
+    /// The time since the message began.
+    pub elapsed: Duration,
+}
+
+pub struct Seed<'a, K: Key + 'a>(&'a K, &'a channel::Sender<Next>, bool);
 
 impl<'a, K: Key + 'a> Seed<'a, K> {
     pub fn control(&self) -> Control {
@@ -955,6 +1039,11 @@ impl<'a, K: Key + 'a> Seed<'a, K> {
     pub fn key(&self) -> &K {
         self.0
     }
+
+    /// Whether the connection has already carried an earlier message.
+    pub fn is_reused(&self) -> bool {
+        self.2
+    }
 }
 
 
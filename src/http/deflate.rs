@@ -0,0 +1,211 @@
+use std::cmp;
+use std::fmt;
+use std::io::{self, Write};
+
+use flate2::{Compress, Compression, Crc, Flush, Status};
+
+use header::Encoding;
+
+/// The gzip header written before the deflated body: no flags, no
+/// modification time, and an unknown OS.
+const GZIP_HEADER: [u8; 10] = [0x1f, 0x8b, 0x08, 0, 0, 0, 0, 0, 0, 0xff];
+
+/// Compresses a body with `Content-Encoding: gzip` or `deflate`, as it is
+/// written to an `Encoder`.
+///
+/// Compressed bytes that could not be written yet are kept until the next
+/// writable event.
+pub struct Deflate {
+    gzip: bool,
+    compress: Compress,
+    crc: Crc,
+    pending: Vec<u8>,
+    pos: usize,
+    finished: bool,
+}
+
+impl Deflate {
+    /// Returns a `Deflate` for the `Encoding`, if it is one that can be
+    /// compressed.
+    pub fn new(encoding: &Encoding) -> Option<Deflate> {
+        let (gzip, pending) = match *encoding {
+            Encoding::Gzip => (true, GZIP_HEADER.to_vec()),
+            Encoding::Deflate => (false, Vec::new()),
+            _ => return None
+        };
+        Some(Deflate {
+            gzip: gzip,
+            // `deflate` is the zlib format, gzip wraps the raw format
+            compress: Compress::new(Compression::Default, !gzip),
+            crc: Crc::new(),
+            pending: pending,
+            pos: 0,
+            finished: false,
+        })
+    }
+
+    /// Compresses all of `buf`, and writes as much as possible to `inner`.
+    ///
+    /// If compressed bytes from a previous write are still pending, and
+    /// writing them would block, nothing of `buf` is taken.
+    pub fn write<W: Write>(&mut self, inner: &mut W, buf: &[u8]) -> io::Result<usize> {
+        try!(self.flush_pending(inner));
+        if buf.is_empty() || self.finished {
+            return Ok(0);
+        }
+        if self.gzip {
+            self.crc.update(buf);
+        }
+        self.compress(buf, false);
+        match self.flush_pending(inner) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+            Err(e) => return Err(e),
+            Ok(()) => ()
+        }
+        Ok(buf.len())
+    }
+
+    /// Ends the compressed stream, and writes as much of it as possible to
+    /// `inner`.
+    ///
+    /// Returns `WouldBlock` until all of the stream has been written.
+    pub fn finish<W: Write>(&mut self, inner: &mut W) -> io::Result<()> {
+        if !self.finished {
+            self.finished = true;
+            self.compress(&[], true);
+            if self.gzip {
+                let (crc, len) = (self.crc.sum(), self.crc.amount());
+                self.pending.extend_from_slice(&le_bytes(crc));
+                self.pending.extend_from_slice(&le_bytes(len));
+            }
+        }
+        self.flush_pending(inner)
+    }
+
+    /// Whether the stream has been finished and fully written.
+    pub fn is_done(&self) -> bool {
+        self.finished && self.pos == self.pending.len()
+    }
+
+    /// Writes pending compressed bytes to `inner`.
+    pub fn flush_pending<W: Write>(&mut self, inner: &mut W) -> io::Result<()> {
+        while self.pos < self.pending.len() {
+            match try!(inner.write(&self.pending[self.pos..])) {
+                0 => return Err(io::Error::new(io::ErrorKind::WriteZero, "encoder closed")),
+                n => self.pos += n
+            }
+        }
+        self.pending.clear();
+        self.pos = 0;
+        Ok(())
+    }
+
+    fn compress(&mut self, mut input: &[u8], finish: bool) {
+        let flush = if finish { Flush::Finish } else { Flush::None };
+        loop {
+            let room = cmp::max(input.len() / 2, 4096);
+            self.pending.reserve(room);
+            let before = self.compress.total_in();
+            let status = self.compress.compress_vec(input, &mut self.pending, flush);
+            input = &input[(self.compress.total_in() - before) as usize..];
+            if let Status::StreamEnd = status {
+                return;
+            }
+            // without input left, and with output room to spare, all that
+            // can be compressed for now has been
+            let done = input.is_empty() && self.pending.len() < self.pending.capacity();
+            if done && !finish {
+                return;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Deflate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Deflate")
+            .field("gzip", &self.gzip)
+            .field("pending", &(self.pending.len() - self.pos))
+            .field("finished", &self.finished)
+            .finish()
+    }
+}
+
+fn le_bytes(n: u32) -> [u8; 4] {
+    [n as u8, (n >> 8) as u8, (n >> 16) as u8, (n >> 24) as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+
+    use header::Encoding;
+    use http::Inflate;
+    use super::Deflate;
+
+    /// Accepts a few bytes at a time, blocking in between.
+    struct Trickle {
+        bytes: Vec<u8>,
+        block: bool,
+    }
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.block = !self.block;
+            if self.block {
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "blocked"));
+            }
+            let n = ::std::cmp::min(5, buf.len());
+            self.bytes.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn round_trip(encoding: Encoding) {
+        let body = (0..150).flat_map(|_| b"hello world, ".iter().cloned()).collect::<Vec<u8>>();
+        let mut deflate = Deflate::new(&encoding).unwrap();
+        let mut out = Trickle { bytes: Vec::new(), block: false };
+        let mut pos = 0;
+        while pos < body.len() {
+            match deflate.write(&mut out, &body[pos..::std::cmp::min(pos + 100, body.len())]) {
+                Ok(n) => pos += n,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+                Err(e) => panic!("write error: {:?}", e)
+            }
+        }
+        while !deflate.is_done() {
+            match deflate.finish(&mut out) {
+                Ok(()) => (),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+                Err(e) => panic!("finish error: {:?}", e)
+            }
+        }
+        assert!(out.bytes.len() < body.len());
+
+        let mut inflate = Inflate::new(&encoding).unwrap();
+        let mut compressed = &out.bytes[..];
+        let mut inflated = Vec::new();
+        let mut buf = [0; 256];
+        loop {
+            match inflate.read(&mut compressed, &mut buf).unwrap() {
+                0 => break,
+                n => inflated.extend_from_slice(&buf[..n])
+            }
+        }
+        assert_eq!(inflated, body);
+    }
+
+    #[test]
+    fn test_deflate_gzip() {
+        round_trip(Encoding::Gzip);
+    }
+
+    #[test]
+    fn test_deflate_zlib() {
+        round_trip(Encoding::Deflate);
+    }
+}
@@ -10,24 +10,32 @@ use self::Kind::{Length, Chunked, Eof};
 #[derive(Debug, Clone)]
 pub struct Decoder {
     kind: Kind,
+    read: u64,
+    limit: Option<u64>,
 }
 
 impl Decoder {
     pub fn length(x: u64) -> Decoder {
         Decoder {
-            kind: Kind::Length(x)
+            kind: Kind::Length(x),
+            read: 0,
+            limit: None,
         }
     }
 
     pub fn chunked() -> Decoder {
         Decoder {
-            kind: Kind::Chunked(None)
+            kind: Kind::Chunked(None),
+            read: 0,
+            limit: None,
         }
     }
 
     pub fn eof() -> Decoder {
         Decoder {
-            kind: Kind::Eof(false)
+            kind: Kind::Eof(false),
+            read: 0,
+            limit: None,
         }
     }
 }
@@ -65,10 +73,44 @@ impl Decoder {
             _ => false
         }
     }
+
+    /// The number of body bytes decoded so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.read
+    }
+
+    /// Refuse to decode a body longer than `limit` bytes.
+    pub fn set_limit(&mut self, limit: Option<u64>) {
+        self.limit = limit;
+    }
+
+    /// Whether the body is longer than the limit, or is declared to be.
+    pub fn is_too_large(&self) -> bool {
+        match self.limit {
+            Some(limit) => match self.kind {
+                Length(remaining) |
+                Chunked(Some(remaining)) => self.read + remaining > limit,
+                _ => self.read > limit
+            },
+            None => false
+        }
+    }
 }
 
 impl Decoder {
     pub fn decode<R: Read>(&mut self, body: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+        if self.is_too_large() {
+            return Err(too_large());
+        }
+        let num = try!(self.decode_kind(body, buf));
+        self.read += num as u64;
+        if self.is_too_large() {
+            return Err(too_large());
+        }
+        Ok(num)
+    }
+
+    fn decode_kind<R: Read>(&mut self, body: &mut R, buf: &mut [u8]) -> io::Result<usize> {
         match self.kind {
             Length(ref mut remaining) => {
                 trace!("Sized read, remaining={:?}", remaining);
@@ -136,6 +178,10 @@ impl Decoder {
     }
 }
 
+fn too_large() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "body too large")
+}
+
 fn eat<R: Read>(rdr: &mut R, bytes: &[u8]) -> io::Result<()> {
     let mut buf = [0];
     for &b in bytes.iter() {
@@ -272,6 +318,7 @@ mod tests {
         let mut decoder = Decoder::length(10);
         let mut buf = [0u8; 10];
         assert_eq!(decoder.decode(&mut bytes, &mut buf).unwrap(), 7);
+        assert_eq!(decoder.bytes_read(), 7);
         let e = decoder.decode(&mut bytes, &mut buf).unwrap_err();
         assert_eq!(e.kind(), io::ErrorKind::Other);
         assert_eq!(e.description(), "early eof");
@@ -290,4 +337,34 @@ mod tests {
         assert_eq!(e.kind(), io::ErrorKind::Other);
         assert_eq!(e.description(), "early eof");
     }
+
+    #[test]
+    fn test_read_limit() {
+        let mut decoder = Decoder::length(10);
+        decoder.set_limit(Some(9));
+        assert!(decoder.is_too_large());
+        let e = decoder.decode(&mut &b"0123456789"[..], &mut [0u8; 10]).unwrap_err();
+        assert_eq!(e.description(), "body too large");
+
+        let mut decoder = Decoder::length(10);
+        decoder.set_limit(Some(10));
+        assert_eq!(decoder.decode(&mut &b"0123456789"[..], &mut [0u8; 10]).unwrap(), 10);
+        assert!(!decoder.is_too_large());
+
+        let mut bytes = &b"\
+            4\r\n\
+            abcd\r\n\
+            4\r\n\
+            efgh\r\n\
+            0\r\n\
+            \r\n\
+        "[..];
+        let mut decoder = Decoder::chunked();
+        decoder.set_limit(Some(6));
+        let mut buf = [0u8; 10];
+        assert_eq!(decoder.decode(&mut bytes, &mut buf).unwrap(), 4);
+        let e = decoder.decode(&mut bytes, &mut buf).unwrap_err();
+        assert_eq!(e.description(), "body too large");
+        assert!(decoder.is_too_large());
+    }
 }
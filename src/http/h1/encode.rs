@@ -10,6 +10,7 @@ pub struct Encoder {
     kind: Kind,
     prefix: Prefix,
     is_closed: bool,
+    written: u64,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -28,6 +29,7 @@ impl Encoder {
             kind: Kind::Chunked(Chunked::Init),
             prefix: Prefix(None),
             is_closed: false,
+            written: 0,
         }
     }
 
@@ -36,6 +38,7 @@ impl Encoder {
             kind: Kind::Length(len),
             prefix: Prefix(None),
             is_closed: false,
+            written: 0,
         }
     }
 
@@ -63,6 +66,11 @@ impl Encoder {
         self.is_closed = true;
     }
 
+    /// The number of body bytes encoded so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.written
+    }
+
     pub fn finish(self) -> Option<WriteBuf<Cow<'static, [u8]>>> {
         let trailer = self.trailer();
         let buf = self.prefix.0;
@@ -99,6 +107,12 @@ impl Encoder {
     }
 
     pub fn encode<W: AtomicWrite>(&mut self, w: &mut W, msg: &[u8]) -> io::Result<usize> {
+        let n = try!(self.encode_kind(w, msg));
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn encode_kind<W: AtomicWrite>(&mut self, w: &mut W, msg: &[u8]) -> io::Result<usize> {
         match self.kind {
             Kind::Chunked(ref mut chunked) => {
                 chunked.encode(w, &mut self.prefix, msg)
@@ -355,6 +369,7 @@ mod tests {
         encoder.encode(&mut dst, b"baz quux herp").unwrap();
         encoder.encode(&mut dst, b"").unwrap();
         assert_eq!(&dst[..], &b"7\r\nfoo bar\r\nD\r\nbaz quux herp\r\n0\r\n\r\n"[..]);
+        assert_eq!(encoder.bytes_written(), 20);
     }
 
     #[test]
@@ -379,5 +394,6 @@ mod tests {
         assert_eq!(encoder.encode(&mut dst, b"baz").unwrap(), 1);
 
         assert_eq!(dst, b"foo barb");
+        assert_eq!(encoder.bytes_written(), 8);
     }
 }
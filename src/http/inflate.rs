@@ -0,0 +1,315 @@
+use std::fmt;
+use std::io::{self, Read};
+
+use flate2::{Crc, Decompress, Flush, Status};
+
+use header::Encoding;
+
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+
+/// Inflates a body sent with `Content-Encoding: gzip` or `deflate`, as it
+/// is read from a `Decoder`.
+///
+/// The state is kept between reads, so that a read which would block can
+/// be resumed on the next readable event. The trailer of a gzip body is
+/// checked against the inflated bytes, and a mismatch is `InvalidData`.
+pub struct Inflate {
+    coding: Coding,
+    head: Vec<u8>,
+    inflate: Option<Decompress>,
+    input: Vec<u8>,
+    pos: usize,
+    crc: Crc,
+    trailer: Option<Vec<u8>>,
+    done: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Coding {
+    Gzip,
+    Deflate,
+}
+
+impl Inflate {
+    /// Returns an `Inflate` for the `Encoding`, if it is one that can be
+    /// inflated.
+    pub fn new(encoding: &Encoding) -> Option<Inflate> {
+        let coding = match *encoding {
+            Encoding::Gzip => Coding::Gzip,
+            Encoding::Deflate => Coding::Deflate,
+            _ => return None
+        };
+        Some(Inflate {
+            coding: coding,
+            head: Vec::new(),
+            inflate: None,
+            input: Vec::new(),
+            pos: 0,
+            crc: Crc::new(),
+            trailer: None,
+            done: false,
+        })
+    }
+
+    /// Reads from `inner`, writing the inflated bytes into `buf`.
+    pub fn read<R: Read>(&mut self, inner: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if let Some(mut trailer) = self.trailer.take() {
+                while trailer.len() < 8 {
+                    let mut rest = [0; 8];
+                    let n = match inner.read(&mut rest[..8 - trailer.len()]) {
+                        Ok(n) => n,
+                        Err(e) => {
+                            self.trailer = Some(trailer);
+                            return Err(e);
+                        }
+                    };
+                    if n == 0 {
+                        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "gzip trailer ended early"));
+                    }
+                    trailer.extend_from_slice(&rest[..n]);
+                }
+                try!(check_gzip_trailer(&trailer, &self.crc));
+                self.done = true;
+            }
+
+            if self.done {
+                // drain anything after the body, so the connection can be reused
+                let mut scratch = [0; 64];
+                while try!(inner.read(&mut scratch)) > 0 {}
+                return Ok(0);
+            }
+
+            if let Some(ref mut inflate) = self.inflate {
+                // even without new input, there may be output left over from
+                // a previous read into a smaller `buf`
+                let (before_in, before_out) = (inflate.total_in(), inflate.total_out());
+                let status = try!(inflate.decompress(&self.input[self.pos..], buf, Flush::None)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)));
+                self.pos += (inflate.total_in() - before_in) as usize;
+                let n = (inflate.total_out() - before_out) as usize;
+                if self.coding == Coding::Gzip {
+                    self.crc.update(&buf[..n]);
+                }
+                if let Status::StreamEnd = status {
+                    if self.coding == Coding::Gzip {
+                        self.trailer = Some(self.input[self.pos..].to_vec());
+                        self.pos = self.input.len();
+                    } else {
+                        self.done = true;
+                    }
+                }
+                if n > 0 {
+                    return Ok(n);
+                }
+                if self.done || self.trailer.is_some() || self.pos < self.input.len() {
+                    continue;
+                }
+            }
+
+            let mut chunk = [0; 4096];
+            let n = try!(inner.read(&mut chunk));
+            if n == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "compressed body ended early"));
+            }
+            if self.inflate.is_some() {
+                self.input.clear();
+                self.input.extend_from_slice(&chunk[..n]);
+                self.pos = 0;
+            } else {
+                self.head.extend_from_slice(&chunk[..n]);
+                try!(self.start());
+            }
+        }
+    }
+
+    /// Starts inflating once enough of the body is read to tell how.
+    fn start(&mut self) -> io::Result<()> {
+        let (skip, zlib) = match self.coding {
+            Coding::Gzip => match try!(gzip_header_len(&self.head)) {
+                Some(len) => (len, false),
+                None => return Ok(())
+            },
+            Coding::Deflate => {
+                if self.head.len() < 2 {
+                    return Ok(());
+                }
+                // `deflate` should be zlib wrapped, but some servers send
+                // the raw format
+                (0, is_zlib_header(self.head[0], self.head[1]))
+            }
+        };
+        trace!("inflating {:?}, zlib={}", self.coding, zlib);
+        self.inflate = Some(Decompress::new(zlib));
+        self.input = self.head.split_off(skip);
+        self.pos = 0;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Inflate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Inflate")
+            .field("coding", &self.coding)
+            .field("started", &self.inflate.is_some())
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+/// Checks the CRC32 and ISIZE of a gzip trailer against the inflated bytes.
+fn check_gzip_trailer(trailer: &[u8], crc: &Crc) -> io::Result<()> {
+    let le = |bytes: &[u8]| {
+        bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24
+    };
+    if le(&trailer[0..4]) != crc.sum() || le(&trailer[4..8]) != crc.amount() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "gzip trailer does not match body"));
+    }
+    Ok(())
+}
+
+fn is_zlib_header(cmf: u8, flg: u8) -> bool {
+    cmf & 0x0f == 8 && ((cmf as u16) << 8 | flg as u16) % 31 == 0
+}
+
+/// Returns the length of the gzip header at the start of `bytes`, or
+/// `None` if more bytes are needed.
+fn gzip_header_len(bytes: &[u8]) -> io::Result<Option<usize>> {
+    if bytes.len() < 10 {
+        return Ok(None);
+    }
+    if bytes[0] != 0x1f || bytes[1] != 0x8b || bytes[2] != 8 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid gzip header"));
+    }
+    let flags = bytes[3];
+    let mut len = 10;
+    if flags & FEXTRA != 0 {
+        if bytes.len() < len + 2 {
+            return Ok(None);
+        }
+        len += 2 + (bytes[len] as usize | (bytes[len + 1] as usize) << 8);
+    }
+    for &flag in &[FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            match bytes.get(len..).and_then(|rest| rest.iter().position(|&b| b == 0)) {
+                Some(end) => len += end + 1,
+                None => return Ok(None)
+            }
+        }
+    }
+    if flags & FHCRC != 0 {
+        len += 2;
+    }
+    if bytes.len() < len {
+        Ok(None)
+    } else {
+        Ok(Some(len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+
+    use header::Encoding;
+    use super::{Inflate, gzip_header_len};
+
+    // "hello world", gzip compressed
+    const GZIP: &'static [u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0xcb, 0x48,
+        0xcd, 0xc9, 0xc9, 0x57, 0x28, 0xcf, 0x2f, 0xca, 0x49, 0x01, 0x00, 0x85,
+        0x11, 0x4a, 0x0d, 0x0b, 0x00, 0x00, 0x00,
+    ];
+
+    // "hello world", zlib compressed
+    const ZLIB: &'static [u8] = &[
+        0x78, 0x9c, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0x28, 0xcf, 0x2f, 0xca,
+        0x49, 0x01, 0x00, 0x1a, 0x0b, 0x04, 0x5d,
+    ];
+
+    /// Reads a few bytes at a time, blocking in between.
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        block: bool,
+    }
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.block = !self.block;
+            if self.block {
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "blocked"));
+            }
+            let n = ::std::cmp::min(3, ::std::cmp::min(buf.len(), self.bytes.len()));
+            buf[..n].copy_from_slice(&self.bytes[..n]);
+            self.bytes = &self.bytes[n..];
+            Ok(n)
+        }
+    }
+
+    fn inflate(encoding: Encoding, bytes: &[u8]) -> io::Result<Vec<u8>> {
+        let mut inflate = Inflate::new(&encoding).unwrap();
+        let mut inner = Trickle { bytes: bytes, block: false };
+        let mut out = Vec::new();
+        let mut buf = [0; 4];
+        loop {
+            match inflate.read(&mut inner, &mut buf) {
+                Ok(0) => return Ok(out),
+                Ok(n) => out.extend_from_slice(&buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+                Err(e) => return Err(e)
+            }
+        }
+    }
+
+    #[test]
+    fn test_inflate_gzip() {
+        assert_eq!(inflate(Encoding::Gzip, GZIP).unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn test_inflate_deflate() {
+        assert_eq!(inflate(Encoding::Deflate, ZLIB).unwrap(), b"hello world");
+        // raw deflate, without the zlib wrapper
+        assert_eq!(inflate(Encoding::Deflate, &ZLIB[2..ZLIB.len() - 4]).unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn test_inflate_truncated() {
+        let err = inflate(Encoding::Gzip, &GZIP[..15]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        // the trailer is cut short
+        let err = inflate(Encoding::Gzip, &GZIP[..GZIP.len() - 2]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_inflate_gzip_trailer() {
+        let mut crc = GZIP.to_vec();
+        let at = crc.len() - 8;
+        crc[at] ^= 0xff;
+        assert_eq!(inflate(Encoding::Gzip, &crc).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let mut isize = GZIP.to_vec();
+        let at = isize.len() - 4;
+        isize[at] += 1;
+        assert_eq!(inflate(Encoding::Gzip, &isize).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_gzip_header_len() {
+        assert_eq!(gzip_header_len(&GZIP[..9]).unwrap(), None);
+        assert_eq!(gzip_header_len(GZIP).unwrap(), Some(10));
+        // with FNAME "a"
+        let named = [0x1f, 0x8b, 0x08, 0x08, 0, 0, 0, 0, 0, 0x03, b'a', 0, 0xff];
+        assert_eq!(gzip_header_len(&named[..11]).unwrap(), None);
+        assert_eq!(gzip_header_len(&named).unwrap(), Some(12));
+        assert!(gzip_header_len(b"not gzipped").is_err());
+        assert!(Inflate::new(&Encoding::Chunked).is_none());
+    }
+}
@@ -17,12 +17,16 @@ use version::HttpVersion::{Http10, Http11};
 #[cfg(feature = "serde-serialization")]
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-pub use self::conn::{Conn, MessageHandler, MessageHandlerFactory, Seed, Key};
+pub use self::conn::{Conn, MessageHandler, MessageHandlerFactory, MessageStats, Seed, Key};
+pub use self::deflate::Deflate;
+pub use self::inflate::Inflate;
 
 mod buffer;
 pub mod channel;
 mod conn;
+mod deflate;
 mod h1;
+mod inflate;
 //mod h2;
 
 /// Wraps a `Transport` to provide HTTP decoding when reading.
@@ -36,6 +40,7 @@ pub struct Encoder<'a, T: Transport + 'a>(EncoderImpl<'a, T>);
 #[derive(Debug)]
 enum DecoderImpl<'a, T: Read + 'a> {
     H1(&'a mut h1::Decoder, Trans<'a, T>),
+    Inflate(&'a mut Inflate, &'a mut h1::Decoder, Trans<'a, T>),
 }
 
 #[derive(Debug)]
@@ -51,6 +56,51 @@ impl<'a, T: Read + 'a> Trans<'a, T> {
             Trans::Buf(ref buf) => buf.get_ref()
         }
     }
+
+    fn reborrow(&mut self) -> Trans<T> {
+        match *self {
+            Trans::Port(ref mut t) => Trans::Port(&mut **t),
+            Trans::Buf(ref mut buf) => Trans::Buf(buf.reborrow())
+        }
+    }
+}
+
+/// Wraps a `Decoder` so that reads from it are inflated.
+pub fn inflate<'a, 'b: 'a, T: Read>(decoder: &'a mut Decoder<'b, T>, inflate: &'a mut Inflate) -> Decoder<'a, T> {
+    match decoder.0 {
+        DecoderImpl::H1(ref mut decoder, ref mut transport) |
+        DecoderImpl::Inflate(_, ref mut decoder, ref mut transport) => {
+            Decoder(DecoderImpl::Inflate(inflate, &mut **decoder, transport.reborrow()))
+        }
+    }
+}
+
+/// Limits the body a `Decoder` will read to `limit` bytes.
+pub fn limit_body<T: Read>(decoder: &mut Decoder<T>, limit: Option<u64>) {
+    match decoder.0 {
+        DecoderImpl::H1(ref mut decoder, _) |
+        DecoderImpl::Inflate(_, ref mut decoder, _) => decoder.set_limit(limit)
+    }
+}
+
+/// Whether a `Decoder` has refused a body for being over its limit.
+pub fn body_too_large<T: Read>(decoder: &Decoder<T>) -> bool {
+    match decoder.0 {
+        DecoderImpl::H1(ref decoder, _) |
+        DecoderImpl::Inflate(_, ref decoder, _) => decoder.is_too_large()
+    }
+}
+
+/// Reads the bytes of a body from a transport, before they are inflated.
+struct Raw<'a, 'b: 'a, T: Read + 'b> {
+    decoder: &'a mut h1::Decoder,
+    transport: &'a mut Trans<'b, T>,
+}
+
+impl<'a, 'b, T: Read> Read for Raw<'a, 'b, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.decoder.decode(self.transport, buf)
+    }
 }
 
 impl<'a, T: Read + 'a> Read for Trans<'a, T> {
@@ -65,6 +115,33 @@ impl<'a, T: Read + 'a> Read for Trans<'a, T> {
 #[derive(Debug)]
 enum EncoderImpl<'a, T: Transport + 'a> {
     H1(&'a mut h1::Encoder, &'a mut T),
+    Deflate(&'a mut Deflate, &'a mut h1::Encoder, &'a mut T),
+}
+
+/// Wraps an `Encoder` so that writes to it are compressed.
+pub fn deflate<'a, 'b: 'a, T: Transport>(encoder: &'a mut Encoder<'b, T>, deflate: &'a mut Deflate) -> Encoder<'a, T> {
+    match encoder.0 {
+        EncoderImpl::H1(ref mut encoder, ref mut transport) |
+        EncoderImpl::Deflate(_, ref mut encoder, ref mut transport) => {
+            Encoder(EncoderImpl::Deflate(deflate, &mut **encoder, &mut **transport))
+        }
+    }
+}
+
+/// Writes the bytes of a body to a transport, after they are compressed.
+struct Compressed<'a, T: Transport + 'a> {
+    encoder: &'a mut h1::Encoder,
+    transport: &'a mut T,
+}
+
+impl<'a, T: Transport> Write for Compressed<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder.encode(self.transport, buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.transport.flush()
+    }
 }
 
 impl<'a, T: Read> Decoder<'a, T> {
@@ -78,6 +155,9 @@ impl<'a, T: Read> Decoder<'a, T> {
         match self.0 {
             DecoderImpl::H1(ref mut decoder, ref mut transport) => {
                 decoder.decode(transport, buf)
+            },
+            DecoderImpl::Inflate(ref mut inflate, ref mut decoder, ref mut transport) => {
+                inflate.read(&mut Raw { decoder: decoder, transport: transport }, buf)
             }
         }
     }
@@ -101,7 +181,8 @@ impl<'a, T: Read> Decoder<'a, T> {
     /// Get a reference to the transport.
     pub fn get_ref(&self) -> &T {
         match self.0 {
-            DecoderImpl::H1(_, ref transport) => transport.get_ref()
+            DecoderImpl::H1(_, ref transport) |
+            DecoderImpl::Inflate(_, _, ref transport) => transport.get_ref()
         }
     }
 }
@@ -124,6 +205,13 @@ impl<'a, T: Transport> Encoder<'a, T> {
                 } else {
                     encoder.encode(*transport, data)
                 }
+            },
+            EncoderImpl::Deflate(ref mut deflate, ref mut encoder, ref mut transport) => {
+                if encoder.is_closed() {
+                    Ok(0)
+                } else {
+                    deflate.write(&mut Compressed { encoder: encoder, transport: transport }, data)
+                }
             }
         }
     }
@@ -152,14 +240,25 @@ impl<'a, T: Transport> Encoder<'a, T> {
     /// the `Encoder` should write the end chunk, or `0\r\n\r\n`.
     pub fn close(&mut self) {
         match self.0 {
-            EncoderImpl::H1(ref mut encoder, _) => encoder.close()
+            EncoderImpl::H1(ref mut encoder, _) => encoder.close(),
+            EncoderImpl::Deflate(ref mut deflate, ref mut encoder, ref mut transport) => {
+                // the end of the compressed stream must be written before
+                // the body can end, which may take more writable events
+                let finished = deflate.finish(&mut Compressed { encoder: encoder, transport: transport });
+                match finished {
+                    Ok(()) => encoder.close(),
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+                    Err(e) => debug!("error finishing compressed body: {:?}", e)
+                }
+            }
         }
     }
 
     /// Get a reference to the transport.
     pub fn get_ref(&self) -> &T {
         match self.0 {
-            EncoderImpl::H1(_, ref transport) => &*transport
+            EncoderImpl::H1(_, ref transport) |
+            EncoderImpl::Deflate(_, _, ref transport) => &*transport
         }
     }
 }
@@ -182,6 +281,10 @@ impl<'a, T: Transport> Write for Encoder<'a, T> {
         match self.0 {
             EncoderImpl::H1(_, ref mut transport) => {
                 transport.flush()
+            },
+            EncoderImpl::Deflate(ref mut deflate, ref mut encoder, ref mut transport) => {
+                try!(deflate.flush_pending(&mut Compressed { encoder: encoder, transport: transport }));
+                transport.flush()
             }
         }
     }
@@ -450,6 +553,27 @@ impl Next {
     }
 }
 
+/// The timeout a `Next` asks for, if any.
+pub fn next_timeout(next: &Next) -> Option<Duration> {
+    next.timeout
+}
+
+/// Whether a `Next` waits to read, and nothing else.
+pub fn next_is_read(next: &Next) -> bool {
+    match next.interest {
+        Next_::Read => true,
+        _ => false
+    }
+}
+
+/// Whether a `Next` ends the message, or removes the transport.
+pub fn next_is_end(next: &Next) -> bool {
+    match next.interest {
+        Next_::End | Next_::Remove => true,
+        _ => false
+    }
+}
+
 impl Next_ {
     fn register(&self) -> Reg {
         match *self {
@@ -27,8 +27,11 @@ extern crate security_framework;
 #[cfg(feature = "serde-serialization")]
 extern crate serde;
 extern crate cookie;
+extern crate flate2;
 extern crate unicase;
 extern crate httparse;
+extern crate net2;
+extern crate rand;
 extern crate rotor;
 extern crate spmc;
 extern crate vecio;
@@ -67,11 +70,15 @@ macro_rules! rotor_try {
     });
 }
 
+mod hash;
 #[cfg(test)]
 mod mock;
+mod random;
 pub mod client;
 pub mod error;
+pub mod form;
 pub mod method;
+pub mod multipart;
 pub mod header;
 mod http;
 pub mod net;
@@ -0,0 +1,551 @@
+//! Multipart form data
+//!
+//! A `Multipart` writes a `multipart/form-data` body, as browsers send for
+//! forms with file inputs, and a `Parser` reads one. Both work with the
+//! non-blocking `Encoder` and `Decoder`: a write or read that would block
+//! returns `WouldBlock`, and can be called again on the next event.
+//!
+//! # Example
+//!
+//! ```
+//! use hyper::multipart::{Multipart, Parser};
+//!
+//! let mut form = Multipart::new()
+//!     .text("greeting", "hello")
+//!     .file("upload", "hello.txt", hyper::mime::Mime(
+//!         hyper::mime::TopLevel::Text, hyper::mime::SubLevel::Plain, vec![]), &b"hi there"[..]);
+//! let content_type = form.content_type();
+//!
+//! let mut body = Vec::new();
+//! form.write_to(&mut body).unwrap();
+//!
+//! let mut parser = Parser::from_content_type(&content_type).unwrap();
+//! let mut reader = &body[..];
+//! let part = parser.next_part(&mut reader).unwrap().unwrap();
+//! assert_eq!(part.name(), Some("greeting"));
+//! let mut value = [0; 16];
+//! let n = parser.read(&mut reader, &mut value).unwrap();
+//! assert_eq!(&value[..n], b"hello");
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use httparse;
+use unicase::UniCase;
+
+use header::{Charset, ContentDisposition, ContentType, DispositionParam, DispositionType, Headers};
+use mime::{Attr, Mime, SubLevel, TopLevel, Value};
+use random;
+
+/// The most bytes the headers of one part may take.
+const MAX_PART_HEAD: usize = 8 * 1024;
+const MAX_PART_HEADERS: usize = 32;
+
+const CHUNK: usize = 8 * 1024;
+
+/// Writes a `multipart/form-data` body.
+///
+/// Parts are added with the builder methods, and the body is then written
+/// with `write_to`. File parts are read from their `Read` as they are
+/// written, rather than all at once.
+pub struct Multipart {
+    boundary: String,
+    parts: VecDeque<Part>,
+    current: Option<Box<Read + Send>>,
+    buf: Vec<u8>,
+    pos: usize,
+    finished: bool,
+}
+
+struct Part {
+    headers: Headers,
+    body: Box<Read + Send>,
+}
+
+impl Multipart {
+    /// Create an empty `Multipart`, with a new random boundary.
+    pub fn new() -> Multipart {
+        Multipart::with_boundary(boundary())
+    }
+
+    /// Create an empty `Multipart`, separating parts with `boundary`.
+    ///
+    /// The boundary must not occur in any of the parts.
+    pub fn with_boundary<S: Into<String>>(boundary: S) -> Multipart {
+        Multipart {
+            boundary: boundary.into(),
+            parts: VecDeque::new(),
+            current: None,
+            buf: Vec::new(),
+            pos: 0,
+            finished: false,
+        }
+    }
+
+    /// The boundary separating the parts.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// The `ContentType` to send with the body, including the boundary.
+    pub fn content_type(&self) -> ContentType {
+        ContentType(Mime(TopLevel::Multipart, SubLevel::FormData,
+                         vec![(Attr::Boundary, Value::Ext(self.boundary.clone()))]))
+    }
+
+    /// Add a text field.
+    pub fn text<N: Into<String>, V: Into<String>>(self, name: N, value: V) -> Multipart {
+        let mut headers = Headers::new();
+        headers.set(form_data(name.into(), None));
+        self.part(headers, io::Cursor::new(value.into().into_bytes()))
+    }
+
+    /// Add a file field, whose contents are read from `body`.
+    pub fn file<N, F, R>(self, name: N, filename: F, mime: Mime, body: R) -> Multipart
+    where N: Into<String>, F: Into<String>, R: Read + Send + 'static {
+        let mut headers = Headers::new();
+        headers.set(form_data(name.into(), Some(filename.into())));
+        headers.set(ContentType(mime));
+        self.part(headers, body)
+    }
+
+    /// Add a part with any `Headers`.
+    ///
+    /// A form field should have a `ContentDisposition` of `form-data`, with
+    /// a `name` parameter.
+    pub fn part<R: Read + Send + 'static>(mut self, headers: Headers, body: R) -> Multipart {
+        self.parts.push_back(Part {
+            headers: headers,
+            body: Box::new(body),
+        });
+        self
+    }
+
+    /// Write as much of the body to `w` as possible.
+    ///
+    /// Returns `Ok(())` once the whole body has been written. If `w` or a
+    /// part's `Read` would block, the `WouldBlock` error is returned, and
+    /// writing continues from the same place on the next call.
+    pub fn write_to<W: Write>(&mut self, w: &mut W) -> io::Result<()> {
+        loop {
+            while self.pos < self.buf.len() {
+                match try!(w.write(&self.buf[self.pos..])) {
+                    0 => return Err(io::Error::new(io::ErrorKind::WriteZero, "multipart body closed")),
+                    n => self.pos += n
+                }
+            }
+            self.buf.clear();
+            self.pos = 0;
+
+            if let Some(mut body) = self.current.take() {
+                self.buf.resize(CHUNK, 0);
+                let n = match body.read(&mut self.buf) {
+                    Ok(n) => n,
+                    Err(e) => {
+                        self.buf.clear();
+                        self.current = Some(body);
+                        return Err(e);
+                    }
+                };
+                self.buf.truncate(n);
+                if n == 0 {
+                    self.buf.extend_from_slice(b"\r\n");
+                } else {
+                    self.current = Some(body);
+                }
+            } else if let Some(part) = self.parts.pop_front() {
+                let head = format!("--{}\r\n{}\r\n", self.boundary, part.headers);
+                self.buf.extend_from_slice(head.as_bytes());
+                self.current = Some(part.body);
+            } else if !self.finished {
+                let end = format!("--{}--\r\n", self.boundary);
+                self.buf.extend_from_slice(end.as_bytes());
+                self.finished = true;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Multipart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Multipart")
+            .field("boundary", &self.boundary)
+            .field("parts", &self.parts.len())
+            .field("finished", &self.finished)
+            .finish()
+    }
+}
+
+fn form_data(name: String, filename: Option<String>) -> ContentDisposition {
+    let mut parameters = vec![DispositionParam::Ext("name".to_owned(), escape(&name))];
+    if let Some(filename) = filename {
+        parameters.push(DispositionParam::Filename(
+            Charset::Ext("UTF-8".to_owned()), None, escape(&filename).into_bytes()));
+    }
+    ContentDisposition {
+        disposition: DispositionType::Ext("form-data".to_owned()),
+        parameters: parameters,
+    }
+}
+
+/// Escapes what would end a quoted parameter, or the header, the way
+/// browsers do.
+fn escape(value: &str) -> String {
+    value.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Generates a boundary unlikely to be found in any part.
+fn boundary() -> String {
+    format!("hyper-{}", random::hex(12))
+}
+
+/// Reads the parts of a `multipart/form-data` body.
+///
+/// `next_part` moves to the next part, returning its headers, and `read`
+/// reads the body of the current part.
+pub struct Parser {
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    state: State,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Preamble,
+    Boundary,
+    Head,
+    Body,
+    Done,
+}
+
+impl Parser {
+    /// Create a `Parser` for a body with parts separated by `boundary`.
+    pub fn new(boundary: &str) -> Parser {
+        let mut delimiter = b"\r\n--".to_vec();
+        delimiter.extend_from_slice(boundary.as_bytes());
+        Parser {
+            delimiter: delimiter,
+            // the first boundary may start the body, without a line break
+            buf: b"\r\n".to_vec(),
+            state: State::Preamble,
+        }
+    }
+
+    /// Create a `Parser` with the boundary of a `multipart` `ContentType`.
+    ///
+    /// Returns `None` if it is not multipart, or has no boundary.
+    pub fn from_content_type(content_type: &ContentType) -> Option<Parser> {
+        let Mime(ref top, _, ref params) = content_type.0;
+        if *top != TopLevel::Multipart {
+            return None;
+        }
+        params.iter()
+            .find(|&&(ref attr, _)| *attr == Attr::Boundary)
+            .map(|&(_, ref value)| Parser::new(&value.to_string()))
+    }
+
+    /// Move to the next part, skipping the rest of the current one.
+    ///
+    /// Returns `Ok(None)` once all parts have been read.
+    pub fn next_part<R: Read>(&mut self, inner: &mut R) -> io::Result<Option<FormPart>> {
+        loop {
+            match self.state {
+                State::Preamble => {
+                    if let Some(i) = find(&self.buf, &self.delimiter) {
+                        let end = i + self.delimiter.len();
+                        self.buf.drain(..end);
+                        self.state = State::Boundary;
+                    } else {
+                        // a delimiter may start at the end of the buffer
+                        let keep = self.delimiter.len() - 1;
+                        if self.buf.len() > keep {
+                            let end = self.buf.len() - keep;
+                            self.buf.drain(..end);
+                        }
+                        try!(self.fill(inner));
+                    }
+                },
+                State::Boundary => {
+                    if self.buf.len() < 2 {
+                        try!(self.fill(inner));
+                    } else if &self.buf[..2] == b"--" {
+                        trace!("multipart body done");
+                        self.buf.clear();
+                        self.state = State::Done;
+                    } else if &self.buf[..2] == b"\r\n" {
+                        // the line break is kept, so a part without headers
+                        // ends its head at once
+                        self.state = State::Head;
+                    } else {
+                        return Err(invalid("invalid multipart boundary"));
+                    }
+                },
+                State::Head => {
+                    if let Some(i) = find(&self.buf, b"\r\n\r\n") {
+                        let headers = try!(parse_head(&self.buf[2..i + 4]));
+                        self.buf.drain(..i + 4);
+                        self.state = State::Body;
+                        return Ok(Some(FormPart {
+                            headers: headers,
+                        }));
+                    } else if self.buf.len() > MAX_PART_HEAD {
+                        return Err(invalid("multipart headers too large"));
+                    }
+                    try!(self.fill(inner));
+                },
+                State::Body => {
+                    let mut scratch = [0; 1024];
+                    while try!(self.read(inner, &mut scratch)) > 0 {}
+                },
+                State::Done => return Ok(None)
+            }
+        }
+    }
+
+    /// Read the body of the current part into `buf`.
+    ///
+    /// Returns `Ok(0)` at the end of the part.
+    pub fn read<R: Read>(&mut self, inner: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+        if self.state != State::Body || buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let available = match find(&self.buf, &self.delimiter) {
+                Some(0) => {
+                    let end = self.delimiter.len();
+                    self.buf.drain(..end);
+                    self.state = State::Boundary;
+                    return Ok(0);
+                },
+                Some(i) => i,
+                None => self.buf.len().saturating_sub(self.delimiter.len() - 1)
+            };
+            if available > 0 {
+                let n = ::std::cmp::min(available, buf.len());
+                buf[..n].copy_from_slice(&self.buf[..n]);
+                self.buf.drain(..n);
+                return Ok(n);
+            }
+            try!(self.fill(inner));
+        }
+    }
+
+    fn fill<R: Read>(&mut self, inner: &mut R) -> io::Result<()> {
+        let mut chunk = [0; 4096];
+        match try!(inner.read(&mut chunk)) {
+            0 => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "multipart body ended early")),
+            n => {
+                self.buf.extend_from_slice(&chunk[..n]);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Parser {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Parser")
+            .field("state", &self.state)
+            .field("buffered", &self.buf.len())
+            .finish()
+    }
+}
+
+/// The head of one part of a `multipart/form-data` body.
+#[derive(Debug, Clone)]
+pub struct FormPart {
+    headers: Headers,
+}
+
+impl FormPart {
+    /// The headers of this part.
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// The form field name, from the `ContentDisposition`.
+    pub fn name(&self) -> Option<&str> {
+        self.disposition().and_then(|cd| {
+            cd.parameters.iter().filter_map(|param| match *param {
+                DispositionParam::Ext(ref key, ref value) if UniCase(&**key) == UniCase("name") => Some(&value[..]),
+                _ => None
+            }).next()
+        })
+    }
+
+    /// The file name, from the `ContentDisposition`, if this part is a file.
+    pub fn filename(&self) -> Option<String> {
+        self.disposition().and_then(|cd| {
+            cd.parameters.iter().filter_map(|param| match *param {
+                DispositionParam::Filename(_, _, ref bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+                _ => None
+            }).next()
+        })
+    }
+
+    /// The media type of this part, if it has a `ContentType`.
+    pub fn content_type(&self) -> Option<&Mime> {
+        self.headers.get::<ContentType>().map(|ct| &ct.0)
+    }
+
+    fn disposition(&self) -> Option<&ContentDisposition> {
+        self.headers.get::<ContentDisposition>()
+    }
+}
+
+fn parse_head(bytes: &[u8]) -> io::Result<Headers> {
+    let mut headers = [httparse::EMPTY_HEADER; MAX_PART_HEADERS];
+    match httparse::parse_headers(bytes, &mut headers) {
+        Ok(httparse::Status::Complete((_, headers))) => {
+            Headers::from_raw(headers).map_err(|_| invalid("invalid multipart headers"))
+        },
+        Ok(httparse::Status::Partial) => Err(invalid("incomplete multipart headers")),
+        Err(e) => {
+            debug!("invalid multipart headers: {:?}", e);
+            Err(invalid("invalid multipart headers"))
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+
+    use header::ContentType;
+    use mime::{Mime, TopLevel, SubLevel};
+    use super::{Multipart, Parser, form_data};
+
+    /// Reads a few bytes at a time, blocking in between.
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        block: bool,
+    }
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.block = !self.block;
+            if self.block {
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "blocked"));
+            }
+            let n = ::std::cmp::min(7, ::std::cmp::min(buf.len(), self.bytes.len()));
+            buf[..n].copy_from_slice(&self.bytes[..n]);
+            self.bytes = &self.bytes[n..];
+            Ok(n)
+        }
+    }
+
+    fn retry<T, F: FnMut() -> io::Result<T>>(mut f: F) -> T {
+        loop {
+            match f() {
+                Ok(v) => return v,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+                Err(e) => panic!("multipart error: {:?}", e)
+            }
+        }
+    }
+
+    fn parse(content_type: &ContentType, body: &[u8]) -> Vec<(Option<String>, Option<String>, Vec<u8>)> {
+        let mut parser = Parser::from_content_type(content_type).unwrap();
+        let mut inner = Trickle { bytes: body, block: false };
+        let mut parts = Vec::new();
+        while let Some(part) = retry(|| parser.next_part(&mut inner)) {
+            let mut body = Vec::new();
+            let mut buf = [0; 3];
+            loop {
+                match retry(|| parser.read(&mut inner, &mut buf)) {
+                    0 => break,
+                    n => body.extend_from_slice(&buf[..n])
+                }
+            }
+            parts.push((part.name().map(|s| s.to_owned()), part.filename(), body));
+        }
+        parts
+    }
+
+    #[test]
+    fn test_multipart_round_trip() {
+        let file = (0..1000).flat_map(|_| b"line\r\n".iter().cloned()).collect::<Vec<u8>>();
+        let mut form = Multipart::new()
+            .text("title", "hello")
+            .file("upload", "lines.txt", Mime(TopLevel::Text, SubLevel::Plain, vec![]), io::Cursor::new(file.clone()))
+            .text("empty", "");
+        let content_type = form.content_type();
+        let mut body = Vec::new();
+        form.write_to(&mut body).unwrap();
+        assert!(body.ends_with(format!("--{}--\r\n", form.boundary()).as_bytes()));
+
+        let parts = parse(&content_type, &body);
+        assert_eq!(parts, vec![
+            (Some("title".to_owned()), None, b"hello".to_vec()),
+            (Some("upload".to_owned()), Some("lines.txt".to_owned()), file),
+            (Some("empty".to_owned()), None, vec![]),
+        ]);
+    }
+
+    #[test]
+    fn test_multipart_parse_browser_form() {
+        let body = b"preamble\r\n\
+            --AaB03x\r\n\
+            Content-Disposition: form-data; name=\"submit-name\"\r\n\
+            \r\n\
+            Larry\r\n\
+            --AaB03x\r\n\
+            Content-Disposition: form-data; name=\"files\"; filename=\"file1.txt\"\r\n\
+            Content-Type: text/plain\r\n\
+            \r\n\
+            ... contents of file1.txt ...\r\n\
+            --AaB03x--\r\n";
+        let content_type = ContentType("multipart/form-data; boundary=AaB03x".parse().unwrap());
+        let parts = parse(&content_type, body);
+        assert_eq!(parts, vec![
+            (Some("submit-name".to_owned()), None, b"Larry".to_vec()),
+            (Some("files".to_owned()), Some("file1.txt".to_owned()), b"... contents of file1.txt ...".to_vec()),
+        ]);
+    }
+
+    #[test]
+    fn test_multipart_skips_unread_parts() {
+        let body = b"--b\r\n\r\nfirst\r\n--b\r\nContent-Disposition: form-data; name=\"second\"\r\n\r\n2\r\n--b--";
+        let mut parser = Parser::new("b");
+        let mut inner = &body[..];
+        assert_eq!(parser.next_part(&mut inner).unwrap().unwrap().name(), None);
+        let part = parser.next_part(&mut inner).unwrap().unwrap();
+        assert_eq!(part.name(), Some("second"));
+        assert!(parser.next_part(&mut inner).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_multipart_truncated() {
+        let body = b"--b\r\n\r\nno end";
+        let mut parser = Parser::new("b");
+        let mut inner = &body[..];
+        parser.next_part(&mut inner).unwrap().unwrap();
+        let err = parser.next_part(&mut inner).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_multipart_boundaries_differ() {
+        assert!(Multipart::new().boundary() != Multipart::new().boundary());
+        assert!(Parser::from_content_type(&ContentType::json()).is_none());
+    }
+
+    #[test]
+    fn test_multipart_form_data_escaped() {
+        let disposition = form_data("a\"b\r\nc".to_owned(), Some("x\".txt".to_owned()));
+        assert_eq!(disposition.to_string(),
+                   "form-data; name=\"a%22b%0D%0Ac\"; filename=\"x%22.txt\"");
+    }
+}
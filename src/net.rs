@@ -1,7 +1,9 @@
 //! A collection of traits abstracting over Listeners and Streams.
+use std::cmp;
 use std::io::{self, Read, Write};
 use std::net::{SocketAddr};
 use std::option;
+use std::time::Duration;
 
 use rotor::mio::tcp::{TcpStream, TcpListener};
 use rotor::mio::{Selector, Token, Evented, EventSet, PollOpt, TryAccept};
@@ -26,6 +28,21 @@ pub trait Transport: Read + Write + Evented + ::vecio::Writev {
     fn blocked(&self) -> Option<Blocked> {
         None
     }
+
+    /// Returns the address of the remote peer, if it is known.
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    /// Advances any handshake that must finish before this transport can
+    /// carry HTTP, such as TLS or that of a proxy.
+    ///
+    /// Returns `Ok(())` once the transport is ready. While incomplete, a
+    /// `WouldBlock` error is returned, and `blocked()` reports which event
+    /// the handshake is waiting on. By default, there is no handshake.
+    fn handshake(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 /// A trait representing a socket transport that can be used in a Client or Server.
@@ -42,6 +59,21 @@ pub trait Transport: Read + Write + Evented {
     fn blocked(&self) -> Option<Blocked> {
         None
     }
+
+    /// Returns the address of the remote peer, if it is known.
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    /// Advances any handshake that must finish before this transport can
+    /// carry HTTP, such as TLS or that of a proxy.
+    ///
+    /// Returns `Ok(())` once the transport is ready. While incomplete, a
+    /// `WouldBlock` error is returned, and `blocked()` reports which event
+    /// the handshake is waiting on. By default, there is no handshake.
+    fn handshake(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 /// Declares when a transport is blocked from any further action, until the
@@ -69,10 +101,35 @@ pub trait Accept: Evented {
 #[derive(Debug)]
 pub struct HttpStream(pub TcpStream);
 
+impl HttpStream {
+    /// Sets `TCP_NODELAY`, sending small writes immediately instead of
+    /// coalescing them.
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.0.set_nodelay(nodelay)
+    }
+
+    /// Sets `SO_KEEPALIVE`, sending keepalive probes once the connection
+    /// has been idle for `idle`. `None` disables the probes.
+    pub fn set_keepalive(&self, idle: Option<Duration>) -> io::Result<()> {
+        self.0.set_keepalive(idle.map(keepalive_secs))
+    }
+}
+
+/// Rounds a keepalive interval up to whole seconds, as the socket option
+/// takes seconds and rejects 0.
+fn keepalive_secs(idle: Duration) -> u32 {
+    let secs = idle.as_secs() + if idle.subsec_nanos() > 0 { 1 } else { 0 };
+    cmp::min(cmp::max(secs, 1), ::std::u32::MAX as u64) as u32
+}
+
 impl Transport for HttpStream {
     fn take_socket_error(&mut self) -> io::Result<()> {
         self.0.take_socket_error()
     }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        self.0.peer_addr().ok()
+    }
 }
 
 impl Read for HttpStream {
@@ -178,6 +235,107 @@ impl IntoIterator for HttpListener {
     }
 }
 
+/// An `HttpListener` that tunes the streams it accepts.
+#[derive(Debug)]
+pub struct TunedHttpListener {
+    listener: HttpListener,
+    nodelay: bool,
+    keepalive: Option<Duration>,
+}
+
+impl TunedHttpListener {
+    /// Wrap an `HttpListener`, initially without any tuning.
+    pub fn new(listener: HttpListener) -> TunedHttpListener {
+        TunedHttpListener {
+            listener: listener,
+            nodelay: false,
+            keepalive: None,
+        }
+    }
+
+    /// Set `TCP_NODELAY` on accepted streams.
+    ///
+    /// Default is `false`.
+    pub fn nodelay(mut self, nodelay: bool) -> TunedHttpListener {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// Send keepalive probes on accepted streams once they have been idle
+    /// for this long.
+    ///
+    /// Default is `None`, sending no probes.
+    pub fn keepalive(mut self, idle: Option<Duration>) -> TunedHttpListener {
+        self.keepalive = idle;
+        self
+    }
+
+    /// Try to duplicate the underlying listening socket.
+    pub fn try_clone(&self) -> io::Result<TunedHttpListener> {
+        self.listener.try_clone().map(|listener| TunedHttpListener {
+            listener: listener,
+            nodelay: self.nodelay,
+            keepalive: self.keepalive,
+        })
+    }
+}
+
+impl Accept for TunedHttpListener {
+    type Output = HttpStream;
+
+    #[inline]
+    fn accept(&self) -> io::Result<Option<HttpStream>> {
+        self.listener.accept().map(|ok| ok.map(|stream| {
+            tune(&stream, self.nodelay, self.keepalive);
+            stream
+        }))
+    }
+
+    #[inline]
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+impl Evented for TunedHttpListener {
+    #[inline]
+    fn register(&self, selector: &mut Selector, token: Token, interest: EventSet, opts: PollOpt) -> io::Result<()> {
+        self.listener.register(selector, token, interest, opts)
+    }
+
+    #[inline]
+    fn reregister(&self, selector: &mut Selector, token: Token, interest: EventSet, opts: PollOpt) -> io::Result<()> {
+        self.listener.reregister(selector, token, interest, opts)
+    }
+
+    #[inline]
+    fn deregister(&self, selector: &mut Selector) -> io::Result<()> {
+        self.listener.deregister(selector)
+    }
+}
+
+impl IntoIterator for TunedHttpListener {
+    type Item = Self;
+    type IntoIter = option::IntoIter<Self>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Some(self).into_iter()
+    }
+}
+
+/// Sets the TCP options of an accepted stream.
+///
+/// A socket refusing an option is logged, keeping the stream rather than
+/// failing the accept.
+fn tune(stream: &HttpStream, nodelay: bool, keepalive: Option<Duration>) {
+    if let Err(e) = stream.set_nodelay(nodelay) {
+        debug!("error setting TCP_NODELAY on accepted stream: {}", e);
+    }
+    if let Err(e) = stream.set_keepalive(keepalive) {
+        debug!("error setting SO_KEEPALIVE on accepted stream: {}", e);
+    }
+}
+
 /// Deprecated
 ///
 /// Use `SslClient` and `SslServer` instead.
@@ -332,6 +490,22 @@ impl<S: Transport> Transport for HttpsStream<S> {
             HttpsStream::Https(ref s) => s.blocked(),
         }
     }
+
+    #[inline]
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        match *self {
+            HttpsStream::Http(ref s) => s.peer_addr(),
+            HttpsStream::Https(ref s) => s.peer_addr(),
+        }
+    }
+
+    #[inline]
+    fn handshake(&mut self) -> io::Result<()> {
+        match *self {
+            HttpsStream::Http(ref mut s) => s.handshake(),
+            HttpsStream::Https(ref mut s) => s.handshake(),
+        }
+    }
 }
 
 /// An `HttpListener` over SSL.
@@ -339,25 +513,43 @@ impl<S: Transport> Transport for HttpsStream<S> {
 pub struct HttpsListener<S: SslServer> {
     listener: TcpListener,
     ssl: S,
+    nodelay: bool,
+    keepalive: Option<Duration>,
 }
 
 impl<S: SslServer> HttpsListener<S> {
     /// Start listening to an address over HTTPS.
     #[inline]
     pub fn new(addr: &SocketAddr, ssl: S) -> io::Result<HttpsListener<S>> {
-        TcpListener::bind(addr).map(|l| HttpsListener {
-            listener: l,
-            ssl: ssl
-        })
+        TcpListener::bind(addr).map(|l| HttpsListener::with_listener(l, ssl))
     }
 
     /// Construct an `HttpsListener` from a bound `TcpListener`.
     pub fn with_listener(listener: TcpListener, ssl: S) -> HttpsListener<S> {
         HttpsListener {
             listener: listener,
-            ssl: ssl
+            ssl: ssl,
+            nodelay: false,
+            keepalive: None,
         }
     }
+
+    /// Set `TCP_NODELAY` on accepted streams.
+    ///
+    /// Default is `false`.
+    pub fn nodelay(mut self, nodelay: bool) -> HttpsListener<S> {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// Send keepalive probes on accepted streams once they have been idle
+    /// for this long.
+    ///
+    /// Default is `None`, sending no probes.
+    pub fn keepalive(mut self, idle: Option<Duration>) -> HttpsListener<S> {
+        self.keepalive = idle;
+        self
+    }
 }
 
 impl<S: SslServer> Accept for HttpsListener<S> {
@@ -366,13 +558,17 @@ impl<S: SslServer> Accept for HttpsListener<S> {
     #[inline]
     fn accept(&self) -> io::Result<Option<S::Stream>> {
         self.listener.accept().and_then(|s| match s {
-            Some((s, _)) => self.ssl.wrap_server(HttpStream(s)).map(Some).map_err(|e| {
-                match e {
-                    ::Error::Io(e) => e,
-                    _ => io::Error::new(io::ErrorKind::Other, e),
-
-                }
-            }),
+            Some((s, _)) => {
+                let stream = HttpStream(s);
+                tune(&stream, self.nodelay, self.keepalive);
+                self.ssl.wrap_server(stream).map(Some).map_err(|e| {
+                    match e {
+                        ::Error::Io(e) => e,
+                        _ => io::Error::new(io::ErrorKind::Other, e),
+
+                    }
+                })
+            },
             None => Ok(None),
         })
     }
@@ -636,6 +832,26 @@ mod openssl {
         fn take_socket_error(&mut self) -> io::Result<()> {
             self.stream.get_mut().take_socket_error()
         }
+
+        fn peer_addr(&self) -> Option<::std::net::SocketAddr> {
+            self.stream.get_ref().peer_addr()
+        }
+
+        fn handshake(&mut self) -> io::Result<()> {
+            // connecting over a nonblocking socket leaves the handshake to
+            // the first write, so run it with a write of nothing at all
+            if handshaken(&self.stream) {
+                return Ok(());
+            }
+            match io::Write::write(self, &[]) {
+                Err(ref e) if e.kind() != io::ErrorKind::WouldBlock && handshaken(&self.stream) => Ok(()),
+                res => res.map(|_| ())
+            }
+        }
+    }
+
+    fn handshaken<T>(stream: &SslStream<T>) -> bool {
+        stream.ssl().state_string() == "SSLOK "
     }
 }
 
@@ -728,6 +944,10 @@ mod security_framework {
         fn blocked(&self) -> Option<super::Blocked> {
             self.0.get_ref().blocked
         }
+
+        fn peer_addr(&self) -> Option<::std::net::SocketAddr> {
+            self.0.get_ref().inner.peer_addr()
+        }
     }
 
 
@@ -776,3 +996,19 @@ mod security_framework {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::keepalive_secs;
+
+    #[test]
+    fn test_keepalive_secs() {
+        assert_eq!(keepalive_secs(Duration::from_secs(0)), 1);
+        assert_eq!(keepalive_secs(Duration::from_millis(500)), 1);
+        assert_eq!(keepalive_secs(Duration::from_millis(1500)), 2);
+        assert_eq!(keepalive_secs(Duration::from_secs(60)), 60);
+        assert_eq!(keepalive_secs(Duration::from_secs(1 << 40)), u32::max_value());
+    }
+}
@@ -0,0 +1,30 @@
+//! Random values
+//!
+//! Nonces and boundaries are made from the random source of the operating
+//! system, so that they can't be guessed from earlier ones.
+
+use rand::{OsRng, Rng};
+
+/// A hex string of `n` random bytes.
+pub fn hex(n: usize) -> String {
+    let mut bytes = vec![0; n];
+    OsRng::new().expect("operating system random source is unavailable").fill_bytes(&mut bytes);
+    let mut hex = String::with_capacity(n * 2);
+    for b in bytes {
+        hex.push_str(&format!("{:02x}", b));
+    }
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::hex;
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(hex(16).len(), 32);
+        assert!(hex(16).bytes().all(|b| (b'0' <= b && b <= b'9') || (b'a' <= b && b <= b'f')));
+        assert!(hex(16) != hex(16));
+        assert_eq!(hex(0), "");
+    }
+}
@@ -0,0 +1,235 @@
+//! Access logging
+//!
+//! A `Server` given an access log hook calls it with a `LogEntry` once each
+//! message is done, whether its response was completely written or the
+//! connection was closed part way through.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serialize::json::Json;
+use time::{self, Tm};
+
+use header::{Referer, UserAgent};
+use http::RequestHead;
+use method::Method;
+use status::StatusCode;
+use uri::RequestUri;
+use version::HttpVersion;
+
+/// A completed message, as seen by the access log.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    /// The address of the client, if the transport knows it.
+    pub peer: Option<SocketAddr>,
+    /// When the request was received.
+    pub time: Tm,
+    /// The method of the request.
+    pub method: Method,
+    /// The target of the request.
+    pub uri: RequestUri,
+    /// The version of the request.
+    pub version: HttpVersion,
+    /// The status of the response, if one was started.
+    pub status: Option<StatusCode>,
+    /// The body bytes read from the request.
+    pub request_bytes: u64,
+    /// The body bytes written to the response.
+    pub response_bytes: u64,
+    /// The time from receiving the request to finishing the response.
+    pub elapsed: Duration,
+    /// The `Referer` of the request.
+    pub referer: Option<String>,
+    /// The `User-Agent` of the request.
+    pub user_agent: Option<String>,
+}
+
+impl LogEntry {
+    /// Format the entry in the Common Log Format.
+    ///
+    /// ```text
+    /// 127.0.0.1 - - [10/Oct/2016:13:55:36 +0000] "GET /index.html HTTP/1.1" 200 2326
+    /// ```
+    pub fn common(&self) -> String {
+        let peer = match self.peer {
+            Some(addr) => addr.ip().to_string(),
+            None => "-".to_owned()
+        };
+        let status = match self.status {
+            Some(status) => status.to_u16().to_string(),
+            None => "-".to_owned()
+        };
+        let bytes = match self.response_bytes {
+            0 => "-".to_owned(),
+            n => n.to_string()
+        };
+        format!("{} - - [{}] \"{}\" {} {}",
+                peer,
+                self.time.strftime("%d/%b/%Y:%H:%M:%S %z").expect("valid time format"),
+                escape(&format!("{} {} {}", self.method, self.uri, self.version)),
+                status,
+                bytes)
+    }
+
+    /// Format the entry in the Combined Log Format, which is the Common Log
+    /// Format followed by the `Referer` and `User-Agent` of the request.
+    pub fn combined(&self) -> String {
+        fn quoted(value: &Option<String>) -> String {
+            match *value {
+                Some(ref value) => format!("\"{}\"", escape(value)),
+                None => "\"-\"".to_owned()
+            }
+        }
+        format!("{} {} {}", self.common(), quoted(&self.referer), quoted(&self.user_agent))
+    }
+
+    /// Format the entry as a line of JSON, with a field of each detail.
+    pub fn json(&self) -> String {
+        let optional = |value: Option<Json>| value.unwrap_or(Json::Null);
+        let mut object = BTreeMap::new();
+        object.insert("peer".to_owned(), optional(self.peer.map(|addr| Json::String(addr.to_string()))));
+        object.insert("time".to_owned(), Json::String(self.time.rfc3339().to_string()));
+        object.insert("method".to_owned(), Json::String(self.method.to_string()));
+        object.insert("uri".to_owned(), Json::String(self.uri.to_string()));
+        object.insert("version".to_owned(), Json::String(self.version.to_string()));
+        object.insert("status".to_owned(), optional(self.status.map(|status| Json::U64(status.to_u16() as u64))));
+        object.insert("request_bytes".to_owned(), Json::U64(self.request_bytes));
+        object.insert("response_bytes".to_owned(), Json::U64(self.response_bytes));
+        object.insert("elapsed_us".to_owned(),
+                      Json::U64(self.elapsed.as_secs() * 1_000_000 + self.elapsed.subsec_nanos() as u64 / 1_000));
+        object.insert("referer".to_owned(), optional(self.referer.clone().map(Json::String)));
+        object.insert("user_agent".to_owned(), optional(self.user_agent.clone().map(Json::String)));
+        Json::Object(object).to_string()
+    }
+}
+
+/// Escapes quotes, backslashes and unprintable bytes, as Apache does.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'"' | b'\\' => {
+                escaped.push('\\');
+                escaped.push(b as char);
+            },
+            b' '...b'~' => escaped.push(b as char),
+            b => escaped.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    escaped
+}
+
+/// Starts the entry of a request.
+pub fn entry(head: &RequestHead, peer: Option<SocketAddr>) -> LogEntry {
+    LogEntry {
+        peer: peer,
+        time: time::now_utc(),
+        method: head.subject.0.clone(),
+        uri: head.subject.1.clone(),
+        version: head.version,
+        status: None,
+        request_bytes: 0,
+        response_bytes: 0,
+        elapsed: Duration::from_secs(0),
+        referer: head.headers.get::<Referer>().map(|referer| referer.0.clone()),
+        user_agent: head.headers.get::<UserAgent>().map(|agent| agent.0.clone()),
+    }
+}
+
+/// The hook a `Server` calls with each `LogEntry`.
+#[derive(Clone)]
+pub struct AccessLog(Arc<Fn(&LogEntry) + Send + Sync>);
+
+impl AccessLog {
+    pub fn new<F: Fn(&LogEntry) + Send + Sync + 'static>(hook: F) -> AccessLog {
+        AccessLog(Arc::new(hook))
+    }
+
+    pub fn log(&self, entry: &LogEntry) {
+        (self.0)(entry)
+    }
+}
+
+impl fmt::Debug for AccessLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("AccessLog")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serialize::json::Json;
+    use time;
+
+    use header::{Headers, Referer, UserAgent};
+    use http::{MessageHead, RequestLine};
+    use method::Method;
+    use status::StatusCode;
+    use uri::RequestUri;
+    use version::HttpVersion;
+    use super::{entry, LogEntry};
+
+    fn log_entry(headers: Headers) -> LogEntry {
+        let head = MessageHead {
+            version: HttpVersion::Http11,
+            subject: RequestLine(Method::Get, RequestUri::AbsolutePath {
+                path: "/a".to_owned(),
+                query: Some("b=\"c\"".to_owned()),
+            }),
+            headers: headers,
+        };
+        let mut entry = entry(&head, Some("127.0.0.1:4000".parse().unwrap()));
+        entry.time = time::at_utc(time::Timespec::new(1476107736, 0));
+        entry.status = Some(StatusCode::Ok);
+        entry.response_bytes = 2326;
+        entry.elapsed = Duration::new(1, 500_000);
+        entry
+    }
+
+    #[test]
+    fn test_common() {
+        let mut entry = log_entry(Headers::new());
+        assert_eq!(entry.common(),
+                   "127.0.0.1 - - [10/Oct/2016:13:55:36 +0000] \"GET /a?b=\\\"c\\\" HTTP/1.1\" 200 2326");
+        entry.peer = None;
+        entry.status = None;
+        entry.response_bytes = 0;
+        assert_eq!(entry.common(),
+                   "- - - [10/Oct/2016:13:55:36 +0000] \"GET /a?b=\\\"c\\\" HTTP/1.1\" - -");
+    }
+
+    #[test]
+    fn test_combined() {
+        let mut headers = Headers::new();
+        headers.set(UserAgent("curl/7.50\n".to_owned()));
+        let entry = log_entry(headers);
+        assert_eq!(entry.combined(),
+                   format!("{} \"-\" \"curl/7.50\\x0a\"", entry.common()));
+
+        let mut headers = Headers::new();
+        headers.set(Referer("http://example.com/".to_owned()));
+        let entry = log_entry(headers);
+        assert!(entry.combined().ends_with(" \"http://example.com/\" \"-\""));
+    }
+
+    #[test]
+    fn test_json() {
+        let entry = log_entry(Headers::new());
+        let json = Json::from_str(&entry.json()).unwrap();
+        assert_eq!(json.find("peer").and_then(Json::as_string), Some("127.0.0.1:4000"));
+        assert_eq!(json.find("time").and_then(Json::as_string), Some("2016-10-10T13:55:36Z"));
+        assert_eq!(json.find("method").and_then(Json::as_string), Some("GET"));
+        assert_eq!(json.find("uri").and_then(Json::as_string), Some("/a?b=\"c\""));
+        assert_eq!(json.find("version").and_then(Json::as_string), Some("HTTP/1.1"));
+        assert_eq!(json.find("status").and_then(Json::as_u64), Some(200));
+        assert_eq!(json.find("request_bytes").and_then(Json::as_u64), Some(0));
+        assert_eq!(json.find("response_bytes").and_then(Json::as_u64), Some(2326));
+        assert_eq!(json.find("elapsed_us").and_then(Json::as_u64), Some(1_000_500));
+        assert_eq!(json.find("referer"), Some(&Json::Null));
+        assert!(!entry.json().contains('\n'));
+    }
+}
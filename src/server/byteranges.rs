@@ -0,0 +1,408 @@
+//! Byte range responses
+//!
+//! `ByteRanges` answers a `Range` request from a seekable source. A single
+//! range is sent as a `206 Partial Content` with a `ContentRange`, and
+//! several are sent as one `multipart/byteranges` body, each part with its
+//! own `ContentType` and `ContentRange`. Ranges that overlap or touch are
+//! coalesced first, and if none of them can be satisfied, the answer is a
+//! `416 Range Not Satisfiable`. A request for more than `MAX_PARTS` ranges
+//! is answered with the whole source instead.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use hyper::header::{ByteRangeSpec, Range};
+//! use hyper::server::byteranges::ByteRanges;
+//!
+//! let file = File::open("video.mp4").unwrap();
+//! let len = file.metadata().unwrap().len();
+//! let range = Range::Bytes(vec![ByteRangeSpec::FromTo(0, 99), ByteRangeSpec::Last(100)]);
+//! let handler = ByteRanges::new(file, len, "video/mp4".parse().unwrap(), &range);
+//! ```
+
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use mime::Mime;
+
+use header::{ByteRangeSpec, ContentLength, ContentRange, ContentRangeSpec, ContentType, Header,
+             Headers, Range};
+use http::{self, Next};
+use method::Method;
+use net::Transport;
+use random;
+use status::StatusCode;
+use super::{Handler, Request, Response};
+
+/// How much of the source is read at a time.
+const CHUNK_SIZE: usize = 8192;
+
+/// The most ranges sent in one response, so that a request for many small
+/// ranges can't make a response far larger than the source.
+pub const MAX_PARTS: usize = 32;
+
+/// A `Handler` sending ranges of bytes of a seekable source.
+pub struct ByteRanges<R> {
+    source: R,
+    status: StatusCode,
+    headers: Headers,
+    parts: Vec<Part>,
+    tail: Option<Vec<u8>>,
+    next: usize,
+    remaining: u64,
+    buf: Vec<u8>,
+    pos: usize,
+    head: bool,
+}
+
+/// One range to send, with the part headers to send before it.
+struct Part {
+    first: u64,
+    last: u64,
+    head: Vec<u8>,
+}
+
+impl<R: Read + Seek> ByteRanges<R> {
+    /// Answer `range` from `source`, which is `len` bytes of `content_type`.
+    ///
+    /// A `Range` of some unit other than bytes is ignored, and the whole
+    /// source is sent.
+    pub fn new(source: R, len: u64, content_type: Mime, range: &Range) -> ByteRanges<R> {
+        let specs = match *range {
+            Range::Bytes(ref specs) => specs,
+            Range::Unregistered(..) => return ByteRanges::whole(source, len, content_type)
+        };
+        let ranges = satisfiable(specs, len);
+        if ranges.len() > MAX_PARTS {
+            debug!("{} ranges requested, sending the whole source", ranges.len());
+            return ByteRanges::whole(source, len, content_type);
+        }
+        let mut headers = Headers::new();
+        let mut parts = Vec::new();
+        let mut tail = None;
+        let status = match ranges.len() {
+            0 => {
+                headers.set(ContentRange(ContentRangeSpec::Bytes {
+                    range: None,
+                    instance_length: Some(len),
+                }));
+                StatusCode::RangeNotSatisfiable
+            },
+            1 => {
+                let (first, last) = ranges[0];
+                headers.set(ContentType(content_type));
+                headers.set(ContentRange(ContentRangeSpec::Bytes {
+                    range: Some((first, last)),
+                    instance_length: Some(len),
+                }));
+                parts.push(Part { first: first, last: last, head: Vec::new() });
+                StatusCode::PartialContent
+            },
+            _ => {
+                let boundary = boundary();
+                for (i, &(first, last)) in ranges.iter().enumerate() {
+                    let spec = ContentRangeSpec::Bytes {
+                        range: Some((first, last)),
+                        instance_length: Some(len),
+                    };
+                    let head = format!("{}--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+                                       if i == 0 { "" } else { "\r\n" }, boundary, content_type, spec);
+                    parts.push(Part { first: first, last: last, head: head.into_bytes() });
+                }
+                tail = Some(format!("\r\n--{}--\r\n", boundary).into_bytes());
+                let mime = format!("multipart/byteranges; boundary={}", boundary);
+                headers.set(ContentType(mime.parse().expect("boundary is a valid mime parameter")));
+                StatusCode::PartialContent
+            }
+        };
+        ByteRanges::with_parts(source, status, headers, parts, tail)
+    }
+
+    /// Send the whole of `source`, which is `len` bytes of `content_type`,
+    /// with a `200 OK`.
+    pub fn whole(source: R, len: u64, content_type: Mime) -> ByteRanges<R> {
+        let mut headers = Headers::new();
+        headers.set(ContentType(content_type));
+        let parts = if len > 0 {
+            vec![Part { first: 0, last: len - 1, head: Vec::new() }]
+        } else {
+            Vec::new()
+        };
+        ByteRanges::with_parts(source, StatusCode::Ok, headers, parts, None)
+    }
+
+    fn with_parts(source: R, status: StatusCode, mut headers: Headers, parts: Vec<Part>,
+                  tail: Option<Vec<u8>>) -> ByteRanges<R> {
+        let len = parts.iter().fold(0, |len, part| {
+            len + part.head.len() as u64 + part.last - part.first + 1
+        }) + tail.as_ref().map_or(0, |tail| tail.len() as u64);
+        headers.set(ContentLength(len));
+        ByteRanges {
+            source: source,
+            status: status,
+            headers: headers,
+            parts: parts,
+            tail: tail,
+            next: 0,
+            remaining: 0,
+            buf: Vec::new(),
+            pos: 0,
+            head: false,
+        }
+    }
+
+    /// Set another header on the response.
+    pub fn header<H: Header>(mut self, header: H) -> ByteRanges<R> {
+        self.headers.set(header);
+        self
+    }
+
+    /// The status of the response.
+    pub fn status(&self) -> &StatusCode {
+        &self.status
+    }
+
+    /// The headers of the response.
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Refills the buffer with the next bytes to write, returning `false`
+    /// when there are none left.
+    fn fill(&mut self) -> io::Result<bool> {
+        loop {
+            if self.remaining > 0 {
+                let want = if self.remaining < CHUNK_SIZE as u64 { self.remaining as usize } else { CHUNK_SIZE };
+                self.buf.resize(want, 0);
+                let n = try!(self.source.read(&mut self.buf));
+                if n == 0 {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "source ended early"));
+                }
+                self.buf.truncate(n);
+                self.remaining -= n as u64;
+            } else if self.next < self.parts.len() {
+                let part = &mut self.parts[self.next];
+                self.next += 1;
+                try!(self.source.seek(SeekFrom::Start(part.first)));
+                self.remaining = part.last - part.first + 1;
+                if part.head.is_empty() {
+                    continue;
+                }
+                self.buf = ::std::mem::replace(&mut part.head, Vec::new());
+            } else if let Some(tail) = self.tail.take() {
+                self.buf = tail;
+            } else {
+                return Ok(false);
+            }
+            self.pos = 0;
+            return Ok(true);
+        }
+    }
+}
+
+impl<R: Read + Seek, T: Transport> Handler<T> for ByteRanges<R> {
+    fn on_request(&mut self, req: Request<T>) -> Next {
+        self.head = *req.method() == Method::Head;
+        Next::write()
+    }
+
+    fn on_request_readable(&mut self, _decoder: &mut http::Decoder<T>) -> Next {
+        Next::write()
+    }
+
+    fn on_response(&mut self, res: &mut Response) -> Next {
+        res.set_status(self.status);
+        res.headers_mut().extend(self.headers.iter());
+        if self.head || self.parts.is_empty() {
+            Next::end()
+        } else {
+            Next::write()
+        }
+    }
+
+    fn on_response_writable(&mut self, encoder: &mut http::Encoder<T>) -> Next {
+        loop {
+            if self.pos == self.buf.len() {
+                match self.fill() {
+                    Ok(true) => (),
+                    Ok(false) => return Next::end(),
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        debug!("error reading byte ranges: {:?}", e);
+                        return Next::remove();
+                    }
+                }
+            }
+            match encoder.write(&self.buf[self.pos..]) {
+                Ok(0) => return Next::end(),
+                Ok(n) => self.pos += n,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Next::write(),
+                Err(e) => {
+                    debug!("error writing byte ranges: {:?}", e);
+                    return Next::remove();
+                }
+            }
+        }
+    }
+}
+
+impl<R> fmt::Debug for ByteRanges<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ByteRanges")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish()
+    }
+}
+
+/// The first and last bytes of a range of a source of `len` bytes, or
+/// `None` if it is unsatisfiable.
+pub fn byte_range(spec: &ByteRangeSpec, len: u64) -> Option<(u64, u64)> {
+    match *spec {
+        ByteRangeSpec::FromTo(first, last) if first < len && first <= last => {
+            Some((first, if last < len { last } else { len - 1 }))
+        },
+        ByteRangeSpec::AllFrom(first) if first < len => Some((first, len - 1)),
+        ByteRangeSpec::Last(n) if n > 0 && len > 0 => {
+            Some((if n < len { len - n } else { 0 }, len - 1))
+        },
+        _ => None
+    }
+}
+
+/// The satisfiable ranges of `specs`, in order, with any that overlap or
+/// touch coalesced.
+pub fn satisfiable(specs: &[ByteRangeSpec], len: u64) -> Vec<(u64, u64)> {
+    let mut ranges = specs.iter().filter_map(|spec| byte_range(spec, len)).collect::<Vec<_>>();
+    ranges.sort();
+    let mut coalesced: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (first, last) in ranges {
+        if let Some(prev) = coalesced.last_mut() {
+            if first <= prev.1 + 1 {
+                if last > prev.1 {
+                    prev.1 = last;
+                }
+                continue;
+            }
+        }
+        coalesced.push((first, last));
+    }
+    coalesced
+}
+
+/// A multipart boundary, which must not be found in the source.
+fn boundary() -> String {
+    format!("hyper-byteranges-{}", random::hex(8))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use header::{ByteRangeSpec, ContentLength, ContentRange, ContentRangeSpec, ContentType, Range};
+    use status::StatusCode;
+    use super::{ByteRanges, MAX_PARTS, byte_range, satisfiable};
+
+    const SOURCE: &'static [u8] = b"0123456789abcdefghij";
+
+    /// Writes out the body, as the `Handler` would.
+    fn body(ranges: &mut ByteRanges<Cursor<&[u8]>>) -> Vec<u8> {
+        let mut body = Vec::new();
+        while ranges.fill().unwrap() {
+            body.write_all(&ranges.buf).unwrap();
+            ranges.pos = ranges.buf.len();
+        }
+        body
+    }
+
+    #[test]
+    fn test_byte_range() {
+        assert_eq!(byte_range(&ByteRangeSpec::FromTo(0, 9), 100), Some((0, 9)));
+        assert_eq!(byte_range(&ByteRangeSpec::FromTo(90, 200), 100), Some((90, 99)));
+        assert_eq!(byte_range(&ByteRangeSpec::FromTo(100, 200), 100), None);
+        assert_eq!(byte_range(&ByteRangeSpec::AllFrom(50), 100), Some((50, 99)));
+        assert_eq!(byte_range(&ByteRangeSpec::Last(10), 100), Some((90, 99)));
+        assert_eq!(byte_range(&ByteRangeSpec::Last(500), 100), Some((0, 99)));
+        assert_eq!(byte_range(&ByteRangeSpec::Last(0), 100), None);
+        assert_eq!(byte_range(&ByteRangeSpec::AllFrom(0), 0), None);
+    }
+
+    #[test]
+    fn test_satisfiable_coalesces() {
+        let specs = vec![
+            ByteRangeSpec::Last(5),
+            ByteRangeSpec::FromTo(0, 4),
+            ByteRangeSpec::FromTo(3, 9),
+            ByteRangeSpec::FromTo(10, 11),
+            ByteRangeSpec::AllFrom(200),
+        ];
+        assert_eq!(satisfiable(&specs, 100), vec![(0, 11), (95, 99)]);
+        assert_eq!(satisfiable(&specs[4..], 100), vec![]);
+    }
+
+    #[test]
+    fn test_byte_ranges_single() {
+        let range = Range::Bytes(vec![ByteRangeSpec::FromTo(2, 5), ByteRangeSpec::FromTo(4, 7)]);
+        let mut ranges = ByteRanges::new(Cursor::new(SOURCE), 20, mime!(Text/Plain), &range);
+        assert_eq!(*ranges.status(), StatusCode::PartialContent);
+        assert_eq!(ranges.headers().get(), Some(&ContentRange(ContentRangeSpec::Bytes {
+            range: Some((2, 7)),
+            instance_length: Some(20),
+        })));
+        assert_eq!(ranges.headers().get(), Some(&ContentLength(6)));
+        assert_eq!(body(&mut ranges), b"234567");
+    }
+
+    #[test]
+    fn test_byte_ranges_multipart() {
+        let range = Range::Bytes(vec![ByteRangeSpec::FromTo(0, 1), ByteRangeSpec::Last(3)]);
+        let mut ranges = ByteRanges::new(Cursor::new(SOURCE), 20, mime!(Text/Plain), &range);
+        assert_eq!(*ranges.status(), StatusCode::PartialContent);
+        let boundary = {
+            let content_type = ranges.headers().get::<ContentType>().unwrap().to_string();
+            assert!(content_type.starts_with("multipart/byteranges; boundary="), "{}", content_type);
+            content_type["multipart/byteranges; boundary=".len()..].to_owned()
+        };
+        let expected = format!("--{0}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/20\r\n\r\n01\
+                                \r\n--{0}\r\nContent-Type: text/plain\r\nContent-Range: bytes 17-19/20\r\n\r\nhij\
+                                \r\n--{0}--\r\n", boundary);
+        assert_eq!(ranges.headers().get(), Some(&ContentLength(expected.len() as u64)));
+        assert_eq!(String::from_utf8(body(&mut ranges)).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_byte_ranges_unsatisfiable() {
+        let range = Range::Bytes(vec![ByteRangeSpec::FromTo(30, 40)]);
+        let mut ranges = ByteRanges::new(Cursor::new(SOURCE), 20, mime!(Text/Plain), &range);
+        assert_eq!(*ranges.status(), StatusCode::RangeNotSatisfiable);
+        assert_eq!(ranges.headers().get(), Some(&ContentRange(ContentRangeSpec::Bytes {
+            range: None,
+            instance_length: Some(20),
+        })));
+        assert_eq!(ranges.headers().get(), Some(&ContentLength(0)));
+        assert!(body(&mut ranges).is_empty());
+    }
+
+    #[test]
+    fn test_byte_ranges_too_many() {
+        let source = vec![b'a'; 100];
+        let specs = |n: u64| Range::Bytes((0..n).map(|i| ByteRangeSpec::FromTo(i * 2, i * 2)).collect());
+
+        let ranges = ByteRanges::new(Cursor::new(&source[..]), 100, mime!(Text/Plain), &specs(MAX_PARTS as u64));
+        assert_eq!(*ranges.status(), StatusCode::PartialContent);
+
+        let mut ranges = ByteRanges::new(Cursor::new(&source[..]), 100, mime!(Text/Plain), &specs(MAX_PARTS as u64 + 1));
+        assert_eq!(*ranges.status(), StatusCode::Ok);
+        assert_eq!(ranges.headers().get(), Some(&ContentLength(100)));
+        assert_eq!(body(&mut ranges), source);
+    }
+
+    #[test]
+    fn test_byte_ranges_unregistered() {
+        let range = Range::Unregistered("lines".to_owned(), "1-2".to_owned());
+        let mut ranges = ByteRanges::new(Cursor::new(SOURCE), 20, mime!(Text/Plain), &range);
+        assert_eq!(*ranges.status(), StatusCode::Ok);
+        assert_eq!(body(&mut ranges), SOURCE);
+    }
+}
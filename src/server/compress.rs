@@ -0,0 +1,290 @@
+//! Response compression
+//!
+//! `Compress` wraps a `Handler`, and compresses the body it writes with
+//! `gzip` or `deflate`, whichever the client prefers in `Accept-Encoding`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use hyper::{Decoder, Encoder, Next, HttpStream};
+//! use hyper::server::{Compress, Handler, Request, Response, Server};
+//!
+//! struct Hello;
+//!
+//! impl Handler<HttpStream> for Hello {
+//!     fn on_request(&mut self, _: Request<HttpStream>) -> Next {
+//!         Next::write()
+//!     }
+//!     fn on_request_readable(&mut self, _: &mut Decoder<HttpStream>) -> Next {
+//!         Next::write()
+//!     }
+//!     fn on_response(&mut self, _: &mut Response) -> Next {
+//!         Next::write()
+//!     }
+//!     fn on_response_writable(&mut self, encoder: &mut Encoder<HttpStream>) -> Next {
+//!         let _ = encoder.write(&[b'a'; 4096]);
+//!         encoder.close();
+//!         Next::end()
+//!     }
+//! }
+//!
+//! let server = Server::http(&"127.0.0.1:0".parse().unwrap()).unwrap();
+//! let _ = server.handle(|_| Compress::new(Hello));
+//! ```
+
+use mime::{Mime, TopLevel, SubLevel};
+use unicase::UniCase;
+
+use header::{AcceptEncoding, ContentEncoding, ContentLength, ContentRange, ContentType, ETag,
+             Encoding, EntityTag, Headers, Vary};
+use http::{self, Deflate, Next};
+use method::Method;
+use net::Transport;
+use status::StatusCode;
+use super::{Handler, Request, Response};
+
+/// The default smallest body that is compressed, in bytes.
+pub const DEFAULT_MIN_SIZE: u64 = 1024;
+
+/// A `Handler` that compresses the responses of another `Handler`.
+///
+/// A response is compressed only if the client accepts `gzip` or `deflate`,
+/// it has a body, and that body is not already encoded. Responses with a
+/// `ContentType` that is usually compressed already, such as images, or a
+/// `ContentLength` under the minimum size, are sent as they are, as are
+/// partial responses, whose ranges count bytes of the uncompressed body.
+///
+/// A compressed response is sent with chunked encoding, since its length is
+/// not known until it has been written. Its `ETag`, if any, is made weak, as
+/// the compressed bytes differ from those the tag was given to.
+#[derive(Debug)]
+pub struct Compress<H> {
+    handler: H,
+    min_size: u64,
+    coding: Option<Encoding>,
+    head: bool,
+    deflate: Option<Deflate>,
+    finishing: Option<Next>,
+}
+
+impl<H> Compress<H> {
+    /// Wrap a `Handler`, compressing its responses.
+    pub fn new(handler: H) -> Compress<H> {
+        Compress {
+            handler: handler,
+            min_size: DEFAULT_MIN_SIZE,
+            coding: None,
+            head: false,
+            deflate: None,
+            finishing: None,
+        }
+    }
+
+    /// Set the smallest `ContentLength` that is compressed.
+    ///
+    /// Bodies without a `ContentLength` are always compressed.
+    ///
+    /// Default is `DEFAULT_MIN_SIZE`.
+    pub fn min_size(mut self, size: u64) -> Compress<H> {
+        self.min_size = size;
+        self
+    }
+
+    /// Whether a response could be compressed, whatever the request accepts.
+    fn compressible(&self, res: &Response) -> bool {
+        let has_body = match *res.status() {
+            StatusCode::NoContent | StatusCode::NotModified | StatusCode::PartialContent => false,
+            ref status => !status.is_informational()
+        };
+        if !has_body || res.headers().has::<ContentEncoding>() || res.headers().has::<ContentRange>() {
+            return false;
+        }
+        if let Some(&ContentType(ref mime)) = res.headers().get::<ContentType>() {
+            if is_compressed(mime) {
+                return false;
+            }
+        }
+        match res.headers().get::<ContentLength>() {
+            Some(&ContentLength(len)) => len >= self.min_size,
+            None => true
+        }
+    }
+}
+
+impl<H: Handler<T>, T: Transport> Handler<T> for Compress<H> {
+    fn on_request(&mut self, req: Request<T>) -> Next {
+        self.coding = req.headers().get::<AcceptEncoding>().and_then(negotiate);
+        self.head = *req.method() == Method::Head;
+        self.handler.on_request(req)
+    }
+
+    fn on_request_readable(&mut self, decoder: &mut http::Decoder<T>) -> Next {
+        self.handler.on_request_readable(decoder)
+    }
+
+    fn on_response(&mut self, res: &mut Response) -> Next {
+        let next = self.handler.on_response(res);
+        if !self.compressible(res) {
+            return next;
+        }
+        // the response depends on Accept-Encoding, even if this request's
+        // does not allow compressing it
+        vary_accept_encoding(res.headers_mut());
+        if self.head || http::next_is_end(&next) {
+            return next;
+        }
+        if let Some(coding) = self.coding.take() {
+            debug!("compressing response with {}", coding);
+            self.deflate = Deflate::new(&coding);
+            res.headers_mut().set(ContentEncoding(vec![coding]));
+            res.headers_mut().remove::<ContentLength>();
+            weaken_etag(res.headers_mut());
+        }
+        next
+    }
+
+    fn on_response_writable(&mut self, encoder: &mut http::Encoder<T>) -> Next {
+        let deflate = match self.deflate {
+            Some(ref mut deflate) => deflate,
+            None => return self.handler.on_response_writable(encoder)
+        };
+        let next = match self.finishing.take() {
+            Some(next) => next,
+            None => self.handler.on_response_writable(&mut http::deflate(encoder, deflate))
+        };
+        if http::next_is_end(&next) && !deflate.is_done() {
+            // the end of the compressed stream may not fit in one write
+            http::deflate(encoder, deflate).close();
+            if !deflate.is_done() {
+                self.finishing = Some(next);
+                return Next::write();
+            }
+        }
+        next
+    }
+
+    fn on_error(&mut self, err: ::Error) -> Next {
+        self.handler.on_error(err)
+    }
+
+    fn on_remove(self, transport: T) {
+        self.handler.on_remove(transport)
+    }
+}
+
+/// Picks `gzip` or `deflate`, by the qualities the client gives them.
+///
+/// Ties prefer `gzip`, and `*` applies to codings not listed.
+fn negotiate(accept: &AcceptEncoding) -> Option<Encoding> {
+    accept.negotiate(&[Encoding::Gzip, Encoding::Deflate]).cloned()
+}
+
+/// Whether a media type is usually compressed already.
+fn is_compressed(mime: &Mime) -> bool {
+    match *mime {
+        Mime(TopLevel::Image, SubLevel::Ext(ref sub), _) => sub != "svg+xml",
+        Mime(TopLevel::Image, _, _) |
+        Mime(TopLevel::Audio, _, _) |
+        Mime(TopLevel::Video, _, _) => true,
+        Mime(TopLevel::Application, SubLevel::Ext(ref sub), _) => {
+            match &sub[..] {
+                "zip" | "gzip" | "x-gzip" | "x-bzip2" | "x-xz" | "x-7z-compressed" |
+                "x-rar-compressed" | "font-woff" | "font-woff2" => true,
+                _ => false
+            }
+        },
+        _ => false
+    }
+}
+
+fn vary_accept_encoding(headers: &mut Headers) {
+    let name = UniCase("Accept-Encoding".to_owned());
+    let mut items = match headers.get::<Vary>() {
+        Some(&Vary::Any) => return,
+        Some(&Vary::Items(ref items)) if items.contains(&name) => return,
+        Some(&Vary::Items(ref items)) => items.clone(),
+        None => Vec::new()
+    };
+    items.push(name);
+    headers.set(Vary::Items(items));
+}
+
+/// Makes a strong `ETag` weak, as it no longer names these exact bytes.
+fn weaken_etag(headers: &mut Headers) {
+    let tag = match headers.get::<ETag>() {
+        Some(&ETag(ref tag)) if !tag.weak => tag.tag().to_owned(),
+        _ => return
+    };
+    headers.set(ETag(EntityTag::weak(tag)));
+}
+
+#[cfg(test)]
+mod tests {
+    use mime::Mime;
+    use unicase::UniCase;
+
+    use header::{AcceptEncoding, ETag, Encoding, EntityTag, Headers, Vary, qitem, Quality,
+                 QualityItem};
+    use super::{negotiate, is_compressed, vary_accept_encoding, weaken_etag};
+
+    fn accept(items: Vec<QualityItem<Encoding>>) -> AcceptEncoding {
+        AcceptEncoding(items)
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(&accept(vec![])), None);
+        assert_eq!(negotiate(&accept(vec![qitem(Encoding::Deflate), qitem(Encoding::Gzip)])),
+                   Some(Encoding::Gzip));
+        assert_eq!(negotiate(&accept(vec![
+            QualityItem::new(Encoding::Gzip, Quality(500)),
+            qitem(Encoding::Deflate),
+        ])), Some(Encoding::Deflate));
+        assert_eq!(negotiate(&accept(vec![
+            QualityItem::new(Encoding::Gzip, Quality(0)),
+            QualityItem::new(Encoding::Deflate, Quality(0)),
+        ])), None);
+        assert_eq!(negotiate(&accept(vec![qitem(Encoding::Identity)])), None);
+        assert_eq!(negotiate(&accept(vec![
+            QualityItem::new(Encoding::Gzip, Quality(0)),
+            qitem(Encoding::EncodingExt("*".to_owned())),
+        ])), Some(Encoding::Deflate));
+    }
+
+    #[test]
+    fn test_is_compressed() {
+        let mime = |s: &str| s.parse::<Mime>().unwrap();
+        assert!(is_compressed(&mime("image/png")));
+        assert!(is_compressed(&mime("video/mp4")));
+        assert!(is_compressed(&mime("application/zip")));
+        assert!(!is_compressed(&mime("image/svg+xml")));
+        assert!(!is_compressed(&mime("text/html")));
+        assert!(!is_compressed(&mime("application/json")));
+    }
+
+    #[test]
+    fn test_vary_accept_encoding() {
+        let mut headers = Headers::new();
+        vary_accept_encoding(&mut headers);
+        vary_accept_encoding(&mut headers);
+        assert_eq!(headers.get::<Vary>(),
+                   Some(&Vary::Items(vec![UniCase("Accept-Encoding".to_owned())])));
+
+        headers.set(Vary::Any);
+        vary_accept_encoding(&mut headers);
+        assert_eq!(headers.get::<Vary>(), Some(&Vary::Any));
+    }
+
+    #[test]
+    fn test_weaken_etag() {
+        let mut headers = Headers::new();
+        weaken_etag(&mut headers);
+        assert!(!headers.has::<ETag>());
+
+        headers.set(ETag(EntityTag::strong("v1".to_owned())));
+        weaken_etag(&mut headers);
+        assert_eq!(headers.get(), Some(&ETag(EntityTag::weak("v1".to_owned()))));
+        weaken_etag(&mut headers);
+        assert_eq!(headers.get(), Some(&ETag(EntityTag::weak("v1".to_owned()))));
+    }
+}
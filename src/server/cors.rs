@@ -0,0 +1,312 @@
+//! Cross-Origin Resource Sharing
+//!
+//! `Cors` is a `Middleware` applying a [CORS](https://www.w3.org/TR/cors/)
+//! policy. It answers preflight `OPTIONS` requests itself, refusing those
+//! whose origin, method or headers are not allowed, and adds the
+//! `Access-Control-*` headers to the responses of allowed origins.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use hyper::method::Method;
+//! use hyper::server::{Layer, Server};
+//! use hyper::server::cors::Cors;
+//! use hyper::server::router::Router;
+//! # use hyper::{Decoder, Encoder, Next, HttpStream};
+//! # use hyper::server::{Handler, Request, Response};
+//! # struct Hello;
+//! # impl Handler<HttpStream> for Hello {
+//! #     fn on_request(&mut self, _: Request<HttpStream>) -> Next { Next::write() }
+//! #     fn on_request_readable(&mut self, _: &mut Decoder<HttpStream>) -> Next { Next::write() }
+//! #     fn on_response(&mut self, _: &mut Response) -> Next { Next::end() }
+//! #     fn on_response_writable(&mut self, _: &mut Encoder<HttpStream>) -> Next { Next::end() }
+//! # }
+//!
+//! let cors = Cors::new()
+//!     .allow_origin("https://example.com")
+//!     .allow_methods(vec![Method::Get, Method::Put])
+//!     .allow_header("Content-Type")
+//!     .max_age(3600);
+//! let router = Router::new().get("/", |_, _| Hello);
+//! let server = Server::http(&"127.0.0.1:0".parse().unwrap()).unwrap();
+//! let _ = server.handle(Layer::new(router, cors));
+//! ```
+
+use unicase::UniCase;
+
+use header::{AccessControlAllowCredentials, AccessControlAllowHeaders, AccessControlAllowMethods,
+             AccessControlAllowOrigin, AccessControlExposeHeaders, AccessControlMaxAge,
+             AccessControlRequestHeaders, AccessControlRequestMethod, Headers, Origin, Vary};
+use method::Method;
+use net::Transport;
+use status::StatusCode;
+use super::{Middleware, Reply, Request, Response};
+
+/// A `Middleware` applying a CORS policy.
+///
+/// By default, any origin may make `GET`, `HEAD` and `POST` requests,
+/// without credentials or extra headers.
+#[derive(Debug, Clone)]
+pub struct Cors {
+    origins: Option<Vec<UniCase<String>>>,
+    methods: Vec<Method>,
+    headers: Vec<UniCase<String>>,
+    expose: Vec<UniCase<String>>,
+    credentials: bool,
+    max_age: Option<u32>,
+    origin: Option<String>,
+}
+
+impl Cors {
+    /// Create a `Cors` with the default policy.
+    pub fn new() -> Cors {
+        Cors {
+            origins: None,
+            methods: vec![Method::Get, Method::Head, Method::Post],
+            headers: Vec::new(),
+            expose: Vec::new(),
+            credentials: false,
+            max_age: None,
+            origin: None,
+        }
+    }
+
+    /// Allow an origin, such as `https://example.com`.
+    ///
+    /// Once any origin is allowed, all others are not.
+    pub fn allow_origin<S: Into<String>>(mut self, origin: S) -> Cors {
+        let mut origins = self.origins.take().unwrap_or_else(Vec::new);
+        origins.push(UniCase(origin.into()));
+        self.origins = Some(origins);
+        self
+    }
+
+    /// Set the methods allowed.
+    pub fn allow_methods(mut self, methods: Vec<Method>) -> Cors {
+        self.methods = methods;
+        self
+    }
+
+    /// Allow requests to send a header.
+    pub fn allow_header<S: Into<String>>(mut self, name: S) -> Cors {
+        self.headers.push(UniCase(name.into()));
+        self
+    }
+
+    /// Allow scripts to read a header of responses.
+    pub fn expose_header<S: Into<String>>(mut self, name: S) -> Cors {
+        self.expose.push(UniCase(name.into()));
+        self
+    }
+
+    /// Allow requests with credentials, such as cookies.
+    ///
+    /// Credentials are only allowed from the origins given to
+    /// `allow_origin`. Without any, this has no effect, as any origin could
+    /// otherwise make requests with the credentials of a user.
+    pub fn allow_credentials(mut self) -> Cors {
+        self.credentials = true;
+        self
+    }
+
+    /// Let clients cache the answers to preflight requests for `secs`.
+    pub fn max_age(mut self, secs: u32) -> Cors {
+        self.max_age = Some(secs);
+        self
+    }
+
+    /// Checks a request, answering it if it is a preflight.
+    fn check(&mut self, method: &Method, headers: &Headers) -> Option<Reply> {
+        self.origin = None;
+        let origin = match headers.get::<Origin>() {
+            Some(origin) => format!("{}://{}", origin.scheme, origin.host),
+            None => return None
+        };
+        let allowed = self.origins.as_ref().map_or(true, |origins| {
+            origins.contains(&UniCase(origin.clone()))
+        });
+        let preflight = match (method, headers.get::<AccessControlRequestMethod>()) {
+            (&Method::Options, Some(&AccessControlRequestMethod(ref method))) => Some(method),
+            _ => None
+        };
+        let preflight = match preflight {
+            Some(method) => method,
+            None => {
+                if allowed {
+                    self.origin = Some(origin);
+                }
+                return None;
+            }
+        };
+
+        let requested = headers.get::<AccessControlRequestHeaders>().map_or(&[][..], |h| &h.0[..]);
+        if !allowed || !self.methods.contains(preflight) ||
+                !requested.iter().all(|name| self.headers.contains(name)) {
+            debug!("refused CORS preflight from {} for {}", origin, preflight);
+            return Some(Reply::new(StatusCode::Forbidden));
+        }
+        self.origin = Some(origin);
+        let mut reply = Reply::new(StatusCode::Ok)
+            .header(AccessControlAllowMethods(self.methods.clone()));
+        if !requested.is_empty() {
+            reply = reply.header(AccessControlAllowHeaders(requested.to_vec()));
+        }
+        if let Some(max_age) = self.max_age {
+            reply = reply.header(AccessControlMaxAge(max_age));
+        }
+        Some(reply)
+    }
+
+    /// Adds the headers for an allowed origin to a response.
+    fn decorate(&self, headers: &mut Headers) {
+        if self.origins.is_some() {
+            vary_origin(headers);
+        }
+        let origin = match self.origin {
+            Some(ref origin) => origin,
+            None => return
+        };
+        if self.origins.is_some() {
+            headers.set(AccessControlAllowOrigin::Value(origin.clone()));
+            if self.credentials {
+                headers.set(AccessControlAllowCredentials);
+            }
+        } else {
+            headers.set(AccessControlAllowOrigin::Any);
+        }
+        if !self.expose.is_empty() {
+            headers.set(AccessControlExposeHeaders(self.expose.clone()));
+        }
+    }
+}
+
+impl Default for Cors {
+    fn default() -> Cors {
+        Cors::new()
+    }
+}
+
+impl<T: Transport> Middleware<T> for Cors {
+    fn on_request(&mut self, req: &Request<T>) -> Option<Reply> {
+        self.check(req.method(), req.headers())
+    }
+
+    fn on_response(&mut self, res: &mut Response) {
+        self.decorate(res.headers_mut());
+    }
+}
+
+/// Adds `Origin` to the `Vary` header, as the response depends on it.
+fn vary_origin(headers: &mut Headers) {
+    let name = UniCase("Origin".to_owned());
+    let mut items = match headers.get::<Vary>() {
+        Some(&Vary::Any) => return,
+        Some(&Vary::Items(ref items)) if items.contains(&name) => return,
+        Some(&Vary::Items(ref items)) => items.clone(),
+        None => Vec::new()
+    };
+    items.push(name);
+    headers.set(Vary::Items(items));
+}
+
+#[cfg(test)]
+mod tests {
+    use unicase::UniCase;
+
+    use header::{AccessControlAllowCredentials, AccessControlAllowHeaders, AccessControlAllowMethods,
+                 AccessControlAllowOrigin, AccessControlMaxAge, AccessControlRequestHeaders,
+                 AccessControlRequestMethod, Headers, Origin, Vary};
+    use method::Method;
+    use status::StatusCode;
+    use super::Cors;
+
+    fn request(origin: Option<&str>, preflight: Option<Method>, names: &[&str]) -> Headers {
+        let mut headers = Headers::new();
+        if let Some(origin) = origin {
+            headers.set(origin.parse::<Origin>().unwrap());
+        }
+        if let Some(method) = preflight {
+            headers.set(AccessControlRequestMethod(method));
+        }
+        if !names.is_empty() {
+            headers.set(AccessControlRequestHeaders(names.iter().map(|name| UniCase(name.to_string())).collect()));
+        }
+        headers
+    }
+
+    #[test]
+    fn test_cors_not_cross_origin() {
+        let mut cors = Cors::new().allow_origin("https://a.example");
+        assert!(cors.check(&Method::Get, &request(None, None, &[])).is_none());
+        let mut res = Headers::new();
+        cors.decorate(&mut res);
+        assert!(res.get::<AccessControlAllowOrigin>().is_none());
+        assert_eq!(res.get::<Vary>(), Some(&Vary::Items(vec![UniCase("Origin".to_owned())])));
+    }
+
+    #[test]
+    fn test_cors_actual_request() {
+        let mut cors = Cors::new();
+        assert!(cors.check(&Method::Get, &request(Some("https://a.example"), None, &[])).is_none());
+        let mut res = Headers::new();
+        cors.decorate(&mut res);
+        assert_eq!(res.get(), Some(&AccessControlAllowOrigin::Any));
+        assert!(res.get::<Vary>().is_none());
+
+        let mut cors = Cors::new().allow_origin("https://a.example").allow_credentials();
+        assert!(cors.check(&Method::Post, &request(Some("https://a.example"), None, &[])).is_none());
+        let mut res = Headers::new();
+        cors.decorate(&mut res);
+        assert_eq!(res.get(), Some(&AccessControlAllowOrigin::Value("https://a.example".to_owned())));
+        assert_eq!(res.get(), Some(&AccessControlAllowCredentials));
+
+        assert!(cors.check(&Method::Post, &request(Some("https://b.example"), None, &[])).is_none());
+        let mut res = Headers::new();
+        cors.decorate(&mut res);
+        assert!(res.get::<AccessControlAllowOrigin>().is_none());
+    }
+
+    #[test]
+    fn test_cors_credentials_need_origins() {
+        let mut cors = Cors::new().allow_credentials();
+        assert!(cors.check(&Method::Get, &request(Some("https://evil.example"), None, &[])).is_none());
+        let mut res = Headers::new();
+        cors.decorate(&mut res);
+        assert_eq!(res.get(), Some(&AccessControlAllowOrigin::Any));
+        assert!(res.get::<AccessControlAllowCredentials>().is_none());
+    }
+
+    #[test]
+    fn test_cors_preflight() {
+        let mut cors = Cors::new()
+            .allow_origin("https://a.example")
+            .allow_methods(vec![Method::Get, Method::Put])
+            .allow_header("X-Token")
+            .max_age(600);
+        let reply = cors.check(&Method::Options, &request(Some("https://a.example"), Some(Method::Put), &["x-token"]))
+            .unwrap();
+        assert_eq!(*reply.status(), StatusCode::Ok);
+        assert_eq!(reply.headers().get(), Some(&AccessControlAllowMethods(vec![Method::Get, Method::Put])));
+        assert_eq!(reply.headers().get(), Some(&AccessControlAllowHeaders(vec![UniCase("x-token".to_owned())])));
+        assert_eq!(reply.headers().get(), Some(&AccessControlMaxAge(600)));
+        let mut res = Headers::new();
+        cors.decorate(&mut res);
+        assert_eq!(res.get(), Some(&AccessControlAllowOrigin::Value("https://a.example".to_owned())));
+    }
+
+    #[test]
+    fn test_cors_preflight_refused() {
+        let mut cors = Cors::new().allow_origin("https://a.example").allow_header("X-Token");
+        let refused = |cors: &mut Cors, headers: Headers| {
+            cors.check(&Method::Options, &headers).map(|reply| *reply.status())
+        };
+        assert_eq!(refused(&mut cors, request(Some("https://b.example"), Some(Method::Get), &[])),
+                   Some(StatusCode::Forbidden));
+        assert_eq!(refused(&mut cors, request(Some("https://a.example"), Some(Method::Delete), &[])),
+                   Some(StatusCode::Forbidden));
+        assert_eq!(refused(&mut cors, request(Some("https://a.example"), Some(Method::Get), &["X-Other"])),
+                   Some(StatusCode::Forbidden));
+        // an OPTIONS request without Access-Control-Request-Method isn't a preflight
+        assert_eq!(refused(&mut cors, request(Some("https://a.example"), None, &[])), None);
+    }
+}
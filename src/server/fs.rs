@@ -0,0 +1,307 @@
+//! Static files
+//!
+//! `Static` is a `HandlerFactory` serving the files under a root directory.
+//! Request paths are percent-decoded and mapped onto the root, and paths
+//! that would leave it, with `..` segments, are not found.
+//!
+//! Files are sent with a `ContentType` guessed from their extension, a
+//! `LastModified` date and a strong `ETag`. Their preconditions are evaluated
+//! with `Headers::evaluate_preconditions`, giving a `304 Not Modified` or a
+//! `412 Precondition Failed`, and `Range` requests are answered by
+//! `ByteRanges`. The file is streamed to the `Encoder`, a chunk at a time.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use hyper::server::Server;
+//! use hyper::server::fs::Static;
+//!
+//! let server = Server::http(&"127.0.0.1:0".parse().unwrap()).unwrap();
+//! let _ = server.handle(Static::new("public").index("index.html"));
+//! ```
+
+use std::fmt;
+use std::fs::{File, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use mime::Mime;
+use time;
+use url::percent_encoding::percent_decode;
+
+use header::{AcceptRanges, Allow, ETag, EntityTag, Headers, HttpDate, LastModified, Precondition, Range,
+             RangeUnit};
+use http::{self, Control, Next};
+use method::Method;
+use net::Transport;
+use status::StatusCode;
+use super::{ByteRanges, Handler, HandlerFactory, Reply, Request, Response};
+
+/// A `HandlerFactory` serving the files under a root directory.
+#[derive(Debug, Clone)]
+pub struct Static {
+    root: PathBuf,
+    index: Option<String>,
+}
+
+impl Static {
+    /// Serve the files under `root`.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Static {
+        Static {
+            root: root.into(),
+            index: None,
+        }
+    }
+
+    /// Serve the file of this name for requests of a directory.
+    ///
+    /// By default, directories are not found.
+    pub fn index<S: Into<String>>(mut self, name: S) -> Static {
+        self.index = Some(name.into());
+        self
+    }
+
+    /// Create the `Handler` of one request.
+    pub fn handler(&self) -> StaticFile {
+        StaticFile {
+            root: self.root.clone(),
+            index: self.index.clone(),
+            state: State::Waiting,
+        }
+    }
+}
+
+impl<T: Transport> HandlerFactory<T> for Static {
+    type Output = StaticFile;
+
+    fn create(&mut self, _ctrl: Control) -> StaticFile {
+        self.handler()
+    }
+}
+
+/// The `Handler` of a `Static`, for one request.
+pub struct StaticFile {
+    root: PathBuf,
+    index: Option<String>,
+    state: State,
+}
+
+enum State {
+    Waiting,
+    Reply(Reply),
+    NotModified(Headers),
+    File(ByteRanges<File>),
+}
+
+impl StaticFile {
+    /// Decides how to answer a request.
+    fn prepare(&self, method: &Method, path: Option<&str>, headers: &Headers) -> State {
+        if *method != Method::Get && *method != Method::Head {
+            return State::Reply(Reply::new(StatusCode::MethodNotAllowed)
+                .header(Allow(vec![Method::Get, Method::Head])));
+        }
+        let path = match path.and_then(|path| resolve(&self.root, path)) {
+            Some(path) => path,
+            None => return State::Reply(Reply::new(StatusCode::NotFound))
+        };
+        let (mut path, mut file, mut meta) = match open(&path) {
+            Ok((file, meta)) => (path, file, meta),
+            Err(e) => return State::Reply(error_reply(&e))
+        };
+        if meta.is_dir() {
+            path = match self.index {
+                Some(ref index) => path.join(index),
+                None => return State::Reply(Reply::new(StatusCode::NotFound))
+            };
+            match open(&path) {
+                Ok((index, index_meta)) => {
+                    file = index;
+                    meta = index_meta;
+                },
+                Err(e) => return State::Reply(error_reply(&e))
+            }
+            if meta.is_dir() {
+                return State::Reply(Reply::new(StatusCode::NotFound));
+            }
+        }
+
+        let etag = etag(&meta);
+        let modified = modified(&meta);
+        match headers.evaluate_preconditions(method, Some(&etag), modified.as_ref()) {
+            Precondition::Proceed => (),
+            Precondition::NotModified => {
+                let mut validators = Headers::new();
+                validators.set(ETag(etag));
+                if let Some(modified) = modified {
+                    validators.set(LastModified(modified));
+                }
+                return State::NotModified(validators);
+            },
+            Precondition::Failed => return State::Reply(Reply::new(StatusCode::PreconditionFailed))
+        }
+
+        let len = meta.len();
+        let content_type = content_type(&path);
+        let ranges = match headers.get::<Range>() {
+            Some(range) if headers.range_applies(Some(&etag), modified.as_ref()) => {
+                ByteRanges::new(file, len, content_type, range)
+            },
+            _ => ByteRanges::whole(file, len, content_type)
+        };
+        let mut ranges = ranges.header(AcceptRanges(vec![RangeUnit::Bytes])).header(ETag(etag));
+        if let Some(modified) = modified {
+            ranges = ranges.header(LastModified(modified));
+        }
+        State::File(ranges)
+    }
+}
+
+impl<T: Transport> Handler<T> for StaticFile {
+    fn on_request(&mut self, req: Request<T>) -> Next {
+        self.state = self.prepare(req.method(), req.path(), req.headers());
+        match self.state {
+            State::Waiting | State::NotModified(..) => Next::write(),
+            State::Reply(ref mut reply) => reply.on_request(req),
+            State::File(ref mut ranges) => ranges.on_request(req)
+        }
+    }
+
+    fn on_request_readable(&mut self, _decoder: &mut http::Decoder<T>) -> Next {
+        Next::write()
+    }
+
+    fn on_response(&mut self, res: &mut Response) -> Next {
+        match self.state {
+            State::Waiting => Next::end(),
+            State::Reply(ref mut reply) => Handler::<T>::on_response(reply, res),
+            State::NotModified(ref headers) => {
+                res.set_status(StatusCode::NotModified);
+                res.headers_mut().extend(headers.iter());
+                Next::end()
+            },
+            State::File(ref mut ranges) => Handler::<T>::on_response(ranges, res)
+        }
+    }
+
+    fn on_response_writable(&mut self, encoder: &mut http::Encoder<T>) -> Next {
+        match self.state {
+            State::Waiting | State::NotModified(..) => Next::end(),
+            State::Reply(ref mut reply) => reply.on_response_writable(encoder),
+            State::File(ref mut ranges) => ranges.on_response_writable(encoder)
+        }
+    }
+}
+
+impl fmt::Debug for StaticFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StaticFile")
+            .field("root", &self.root)
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+/// Maps a request path onto the root, or `None` if it would leave it.
+fn resolve(root: &Path, path: &str) -> Option<PathBuf> {
+    let mut resolved = root.to_path_buf();
+    for segment in path.split('/') {
+        let segment = String::from_utf8_lossy(&percent_decode(segment.as_bytes()).collect::<Vec<u8>>())
+            .into_owned();
+        match &segment[..] {
+            "" | "." => (),
+            ".." => return None,
+            s if s.contains('/') || s.contains('\\') || s.contains('\0') || s.contains(':') => return None,
+            s => resolved.push(s)
+        }
+    }
+    Some(resolved)
+}
+
+fn open(path: &Path) -> io::Result<(File, Metadata)> {
+    let file = try!(File::open(path));
+    let meta = try!(file.metadata());
+    Ok((file, meta))
+}
+
+fn error_reply(err: &io::Error) -> Reply {
+    match err.kind() {
+        io::ErrorKind::NotFound => Reply::new(StatusCode::NotFound),
+        io::ErrorKind::PermissionDenied => Reply::new(StatusCode::Forbidden),
+        _ => {
+            debug!("error opening static file: {:?}", err);
+            Reply::new(StatusCode::InternalServerError)
+        }
+    }
+}
+
+/// A strong `EntityTag` of the modification time and length of a file.
+fn etag(meta: &Metadata) -> EntityTag {
+    let (secs, nanos) = meta.modified().ok()
+        .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+        .map_or((0, 0), |since| (since.as_secs(), since.subsec_nanos()));
+    EntityTag::strong(format!("{:x}-{:x}-{:x}", secs, nanos, meta.len()))
+}
+
+fn modified(meta: &Metadata) -> Option<HttpDate> {
+    meta.modified().ok()
+        .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+        .map(|since| HttpDate(time::at_utc(time::Timespec::new(since.as_secs() as i64, 0))))
+}
+
+/// Guesses the `Mime` of a file from its extension.
+fn content_type(path: &Path) -> Mime {
+    let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("").to_lowercase();
+    let mime = match &ext[..] {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" => "application/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "xml" => "text/xml; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "wasm" => "application/wasm",
+        "woff" => "application/font-woff",
+        "woff2" => "font/woff2",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        _ => "application/octet-stream"
+    };
+    mime.parse().expect("static mime types are valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{content_type, resolve};
+
+    #[test]
+    fn test_resolve() {
+        let root = Path::new("/srv/www");
+        assert_eq!(resolve(root, "/a/b.txt"), Some(PathBuf::from("/srv/www/a/b.txt")));
+        assert_eq!(resolve(root, "/a//./b%20c.txt"), Some(PathBuf::from("/srv/www/a/b c.txt")));
+        assert_eq!(resolve(root, "/"), Some(PathBuf::from("/srv/www")));
+        assert_eq!(resolve(root, "/../etc/passwd"), None);
+        assert_eq!(resolve(root, "/a/%2e%2e/%2e%2e/etc/passwd"), None);
+        assert_eq!(resolve(root, "/a%2f..%2f..%2fetc"), None);
+        assert_eq!(resolve(root, "/a\\..\\b"), None);
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(content_type(Path::new("index.HTML")).to_string(), "text/html; charset=utf-8");
+        assert_eq!(content_type(Path::new("a/b.png")).to_string(), "image/png");
+        assert_eq!(content_type(Path::new("README")).to_string(), "application/octet-stream");
+    }
+}
@@ -1,10 +1,13 @@
 use std::marker::PhantomData;
 
 
+use header::{Connection, ContentLength};
 use http::{self, Next};
 use net::Transport;
+use status::StatusCode;
 
 use super::{Handler, request, response};
+use super::access_log::{self, AccessLog, LogEntry};
 
 /// A `MessageHandler` for a Server.
 ///
@@ -13,16 +16,38 @@ use super::{Handler, request, response};
 /// would expect in a Server Handler.
 pub struct Message<H: Handler<T>, T: Transport> {
     handler: H,
+    max_body_size: Option<u64>,
+    too_large: bool,
+    responding: bool,
+    access_log: Option<AccessLog>,
+    entry: Option<LogEntry>,
     _marker: PhantomData<T>
 }
 
 impl<H: Handler<T>, T: Transport> Message<H, T> {
-    pub fn new(handler: H) -> Message<H, T> {
+    pub fn new(handler: H, max_body_size: Option<u64>, access_log: Option<AccessLog>) -> Message<H, T> {
         Message {
             handler: handler,
+            max_body_size: max_body_size,
+            too_large: false,
+            responding: false,
+            access_log: access_log,
+            entry: None,
             _marker: PhantomData,
         }
     }
+
+    /// Tells the handler the request body is over the limit, and answers
+    /// with a `413` in its place, unless its response has already begun.
+    fn too_large(&mut self) -> Next {
+        self.too_large = true;
+        let _ = self.handler.on_error(::Error::TooLarge);
+        if self.responding {
+            Next::remove()
+        } else {
+            Next::write()
+        }
+    }
 }
 
 impl<H: Handler<T>, T: Transport> http::MessageHandler<T> for Message<H, T> {
@@ -30,17 +55,49 @@ impl<H: Handler<T>, T: Transport> http::MessageHandler<T> for Message<H, T> {
 
     fn on_incoming(&mut self, head: http::RequestHead, transport: &T) -> Next {
         trace!("on_incoming {:?}", head);
-        let req = request::new(head, transport);
-        self.handler.on_request(req)
+        if self.access_log.is_some() {
+            self.entry = Some(access_log::entry(&head, transport.peer_addr()));
+        }
+        let content_length = head.headers.get::<ContentLength>().map(|len| len.0);
+        let next = {
+            let req = request::new(head, transport, &mut self.max_body_size);
+            self.handler.on_request(req)
+        };
+        match (self.max_body_size, content_length) {
+            (Some(limit), Some(len)) if len > limit => {
+                debug!("request body of {} bytes is over the limit of {}", len, limit);
+                self.too_large()
+            },
+            _ => next
+        }
     }
 
     fn on_decode(&mut self, transport: &mut http::Decoder<T>) -> Next {
-        self.handler.on_request_readable(transport)
+        http::limit_body(transport, self.max_body_size);
+        let next = self.handler.on_request_readable(transport);
+        if http::body_too_large(transport) && !self.too_large {
+            debug!("request body is over the limit of {:?}", self.max_body_size);
+            self.too_large()
+        } else {
+            next
+        }
     }
 
-    fn on_outgoing(&mut self, head: &mut http::MessageHead<::status::StatusCode>) -> Next {
-        let mut res = response::new(head);
-        self.handler.on_response(&mut res)
+    fn on_outgoing(&mut self, head: &mut http::MessageHead<StatusCode>) -> Next {
+        self.responding = true;
+        let next = if self.too_large {
+            head.subject = StatusCode::PayloadTooLarge;
+            head.headers.set(ContentLength(0));
+            head.headers.set(Connection::close());
+            Next::end()
+        } else {
+            let mut res = response::new(head);
+            self.handler.on_response(&mut res)
+        };
+        if let Some(ref mut entry) = self.entry {
+            entry.status = Some(head.subject);
+        }
+        next
     }
 
     fn on_encode(&mut self, transport: &mut http::Encoder<T>) -> Next {
@@ -51,6 +108,15 @@ impl<H: Handler<T>, T: Transport> http::MessageHandler<T> for Message<H, T> {
         self.handler.on_error(error)
     }
 
+    fn on_complete(&mut self, stats: http::MessageStats) {
+        if let (Some(access_log), Some(mut entry)) = (self.access_log.as_ref(), self.entry.take()) {
+            entry.request_bytes = stats.read;
+            entry.response_bytes = stats.written;
+            entry.elapsed = stats.elapsed;
+            access_log.log(&entry);
+        }
+    }
+
     fn on_remove(self, transport: T) {
         self.handler.on_remove(transport);
     }
@@ -0,0 +1,340 @@
+//! Middleware
+//!
+//! A `Middleware` hooks into the events of every message handled by a
+//! `Handler`, without implementing all of the `Handler` itself. It can look
+//! at each request before the `Handler` does, answering it with a `Reply`
+//! instead, change each response head after the `Handler` sets it, and
+//! wrap the reading of request bodies and writing of response bodies.
+//!
+//! A `Chain` runs one `Middleware` around one `Handler`, and is itself a
+//! `Handler`, so chains nest. A `Layer` does the same for each `Handler` a
+//! `HandlerFactory` creates.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use hyper::{Decoder, Encoder, Next, HttpStream};
+//! use hyper::header::Authorization;
+//! use hyper::server::{Handler, Request, Response, Server};
+//! use hyper::server::middleware::{Layer, Middleware, Reply};
+//! use hyper::status::StatusCode;
+//!
+//! #[derive(Clone)]
+//! struct RequireAuth;
+//!
+//! impl Middleware<HttpStream> for RequireAuth {
+//!     fn on_request(&mut self, req: &Request<HttpStream>) -> Option<Reply> {
+//!         if req.headers().has::<Authorization<String>>() {
+//!             None
+//!         } else {
+//!             Some(Reply::new(StatusCode::Unauthorized))
+//!         }
+//!     }
+//! }
+//!
+//! struct Hello;
+//!
+//! impl Handler<HttpStream> for Hello {
+//!     fn on_request(&mut self, _: Request<HttpStream>) -> Next {
+//!         Next::write()
+//!     }
+//!     fn on_request_readable(&mut self, _: &mut Decoder<HttpStream>) -> Next {
+//!         Next::write()
+//!     }
+//!     fn on_response(&mut self, _: &mut Response) -> Next {
+//!         Next::write()
+//!     }
+//!     fn on_response_writable(&mut self, encoder: &mut Encoder<HttpStream>) -> Next {
+//!         let _ = encoder.write(b"Hello World!");
+//!         Next::end()
+//!     }
+//! }
+//!
+//! let server = Server::http(&"127.0.0.1:0".parse().unwrap()).unwrap();
+//! let _ = server.handle(Layer::new(|_| Hello, RequireAuth));
+//! ```
+
+use std::fmt;
+use std::io;
+
+use header::{ContentLength, ContentType, Header, Headers};
+use http::{self, Next};
+use method::Method;
+use net::Transport;
+use status::StatusCode;
+use super::{Handler, HandlerFactory, Request, Response};
+
+/// Hooks run around the events of a `Handler`.
+///
+/// Every hook has a default that does nothing, or calls through to the
+/// `Handler`, so only the ones needed are implemented.
+pub trait Middleware<T: Transport> {
+    /// Called with each request, before the `Handler`.
+    ///
+    /// Returning a `Reply` answers the request with it, and the `Handler` is
+    /// not called for this request, except for `on_remove` once the message
+    /// is done, so that it can still release what it holds.
+    fn on_request(&mut self, _request: &Request<T>) -> Option<Reply> {
+        None
+    }
+
+    /// Called each time the request body is ready to be read. `handler`
+    /// calls the `Handler` with the `Decoder`.
+    fn on_request_readable(&mut self, decoder: &mut http::Decoder<T>,
+                           handler: &mut FnMut(&mut http::Decoder<T>) -> Next) -> Next {
+        handler(decoder)
+    }
+
+    /// Called with each response head, after the `Handler`, or a `Reply`,
+    /// has set it, and before it is written.
+    fn on_response(&mut self, _response: &mut Response) {}
+
+    /// Called each time the response body is ready to be written to.
+    /// `handler` calls the `Handler` with the `Encoder`.
+    fn on_response_writable(&mut self, encoder: &mut http::Encoder<T>,
+                            handler: &mut FnMut(&mut http::Encoder<T>) -> Next) -> Next {
+        handler(encoder)
+    }
+
+    /// Called with each error, before the `Handler`.
+    fn on_error(&mut self, _err: &::Error) {}
+
+    /// Called when the message is done, before the `Handler`.
+    fn on_remove(&mut self) {}
+}
+
+/// A response given by a `Middleware` instead of the `Handler`.
+///
+/// A `Reply` is also a `Handler` itself, answering any request with the
+/// same response.
+#[derive(Debug, Clone)]
+pub struct Reply {
+    status: StatusCode,
+    headers: Headers,
+    body: Vec<u8>,
+    head: bool,
+    written: usize,
+}
+
+impl Reply {
+    /// Create a `Reply` with the status, and no body.
+    pub fn new(status: StatusCode) -> Reply {
+        Reply {
+            status: status,
+            headers: Headers::new(),
+            body: Vec::new(),
+            head: false,
+            written: 0,
+        }
+    }
+
+    /// Create a `406 Not Acceptable` reply, for when none of the `offered`
+    /// representations can be negotiated.
+    ///
+    /// The body lists the offered representations, one per line.
+    ///
+    /// # Example
+    /// ```
+    /// use hyper::header::{Accept, qitem};
+    /// use hyper::mime::Mime;
+    /// use hyper::header::ContentType;
+    /// use hyper::server::Reply;
+    /// use hyper::status::StatusCode;
+    ///
+    /// let accept = Accept(vec![qitem("image/*".parse().unwrap())]);
+    /// let offered: Vec<Mime> = vec!["text/html".parse().unwrap(), "application/json".parse().unwrap()];
+    /// let reply = match accept.negotiate(&offered) {
+    ///     Some(mime) => Reply::new(StatusCode::Ok).header(ContentType(mime.clone())),
+    ///     None => Reply::not_acceptable(&offered),
+    /// };
+    /// assert_eq!(reply.status().to_u16(), 406);
+    /// ```
+    pub fn not_acceptable<R: fmt::Display>(offered: &[R]) -> Reply {
+        let mut body = String::new();
+        for representation in offered {
+            body.push_str(&representation.to_string());
+            body.push('\n');
+        }
+        Reply::new(StatusCode::NotAcceptable)
+            .header(ContentType::plaintext())
+            .body(body)
+    }
+
+    /// Set a header on the reply.
+    pub fn header<H: Header>(mut self, header: H) -> Reply {
+        self.headers.set(header);
+        self
+    }
+
+    /// Set the body of the reply.
+    ///
+    /// A `ContentLength` header of its length is sent with the reply, though
+    /// it isn't among `headers()`.
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Reply {
+        self.body = body.into();
+        self
+    }
+
+    /// The status of the reply.
+    pub fn status(&self) -> &StatusCode {
+        &self.status
+    }
+
+    /// The headers of the reply.
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+}
+
+impl<T: Transport> Handler<T> for Reply {
+    fn on_request(&mut self, req: Request<T>) -> Next {
+        self.head = *req.method() == Method::Head;
+        Next::write()
+    }
+
+    fn on_request_readable(&mut self, _decoder: &mut http::Decoder<T>) -> Next {
+        Next::write()
+    }
+
+    fn on_response(&mut self, res: &mut Response) -> Next {
+        res.set_status(self.status);
+        res.headers_mut().extend(self.headers.iter());
+        res.headers_mut().set(ContentLength(self.body.len() as u64));
+        if self.head || self.body.is_empty() {
+            Next::end()
+        } else {
+            Next::write()
+        }
+    }
+
+    fn on_response_writable(&mut self, encoder: &mut http::Encoder<T>) -> Next {
+        while self.written < self.body.len() {
+            match encoder.write(&self.body[self.written..]) {
+                Ok(0) => break,
+                Ok(n) => self.written += n,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Next::write(),
+                Err(e) => {
+                    debug!("error writing reply: {:?}", e);
+                    return Next::remove();
+                }
+            }
+        }
+        Next::end()
+    }
+}
+
+/// A `Handler` that runs a `Middleware` around another `Handler`.
+pub struct Chain<M, H> {
+    middleware: M,
+    handler: H,
+    reply: Option<Reply>,
+}
+
+impl<M, H> Chain<M, H> {
+    /// Run `middleware` around the events of `handler`.
+    pub fn new(middleware: M, handler: H) -> Chain<M, H> {
+        Chain {
+            middleware: middleware,
+            handler: handler,
+            reply: None,
+        }
+    }
+}
+
+impl<M: Middleware<T>, H: Handler<T>, T: Transport> Handler<T> for Chain<M, H> {
+    fn on_request(&mut self, req: Request<T>) -> Next {
+        match self.middleware.on_request(&req) {
+            Some(mut reply) => {
+                debug!("middleware replied {} to {} {}", reply.status, req.method(), req.uri());
+                let next = reply.on_request(req);
+                self.reply = Some(reply);
+                next
+            },
+            None => self.handler.on_request(req)
+        }
+    }
+
+    fn on_request_readable(&mut self, decoder: &mut http::Decoder<T>) -> Next {
+        if self.reply.is_some() {
+            return Next::write();
+        }
+        let handler = &mut self.handler;
+        self.middleware.on_request_readable(decoder, &mut |decoder| handler.on_request_readable(decoder))
+    }
+
+    fn on_response(&mut self, res: &mut Response) -> Next {
+        let next = match self.reply {
+            Some(ref mut reply) => reply.on_response(res),
+            None => self.handler.on_response(res)
+        };
+        self.middleware.on_response(res);
+        next
+    }
+
+    fn on_response_writable(&mut self, encoder: &mut http::Encoder<T>) -> Next {
+        if let Some(ref mut reply) = self.reply {
+            return reply.on_response_writable(encoder);
+        }
+        let handler = &mut self.handler;
+        self.middleware.on_response_writable(encoder, &mut |encoder| handler.on_response_writable(encoder))
+    }
+
+    fn on_error(&mut self, err: ::Error) -> Next {
+        self.middleware.on_error(&err);
+        if self.reply.is_some() {
+            debug!("error answering with middleware reply: {:?}", err);
+            return Next::remove();
+        }
+        self.handler.on_error(err)
+    }
+
+    fn on_remove(mut self, transport: T) {
+        self.middleware.on_remove();
+        self.handler.on_remove(transport);
+    }
+}
+
+impl<M: fmt::Debug, H: fmt::Debug> fmt::Debug for Chain<M, H> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Chain")
+            .field("middleware", &self.middleware)
+            .field("handler", &self.handler)
+            .field("reply", &self.reply)
+            .finish()
+    }
+}
+
+/// A `HandlerFactory` that runs a clone of a `Middleware` around each
+/// `Handler` of another `HandlerFactory`.
+///
+/// Layers stack with `Layer::with`. The last `Middleware` added is the
+/// outermost, so it sees each request first, and each response last.
+#[derive(Debug, Clone)]
+pub struct Layer<F, M> {
+    factory: F,
+    middleware: M,
+}
+
+impl<F, M> Layer<F, M> {
+    /// Run a clone of `middleware` around each `Handler` of `factory`.
+    pub fn new(factory: F, middleware: M) -> Layer<F, M> {
+        Layer {
+            factory: factory,
+            middleware: middleware,
+        }
+    }
+
+    /// Add another `Middleware`, around the ones of this `Layer`.
+    pub fn with<N>(self, middleware: N) -> Layer<Layer<F, M>, N> {
+        Layer::new(self, middleware)
+    }
+}
+
+impl<F, M, T> HandlerFactory<T> for Layer<F, M>
+where F: HandlerFactory<T>, M: Middleware<T> + Clone, T: Transport {
+    type Output = Chain<M, F::Output>;
+
+    fn create(&mut self, ctrl: http::Control) -> Chain<M, F::Output> {
+        Chain::new(self.middleware.clone(), self.factory.create(ctrl))
+    }
+}
@@ -11,15 +11,32 @@ use std::time::Duration;
 use rotor::mio::{EventSet, PollOpt};
 use rotor::{self, Scope};
 
+pub use self::access_log::LogEntry;
+pub use self::byteranges::ByteRanges;
+pub use self::compress::Compress;
+pub use self::cors::Cors;
+pub use self::fs::Static;
+pub use self::middleware::{Chain, Layer, Middleware, Reply};
+pub use self::router::Router;
+pub use self::sse::EventStream;
 pub use self::request::Request;
 pub use self::response::Response;
 
 use http::{self, Next};
+use self::access_log::AccessLog;
 
-pub use net::{Accept, HttpListener, HttpsListener};
+pub use net::{Accept, HttpListener, HttpsListener, TunedHttpListener};
 use net::{SslServer, Transport};
 
 
+pub mod byteranges;
+pub mod compress;
+pub mod cors;
+pub mod fs;
+pub mod middleware;
+pub mod router;
+pub mod sse;
+mod access_log;
 mod request;
 mod response;
 mod message;
@@ -43,6 +60,8 @@ pub struct Server<A> {
     keep_alive: bool,
     idle_timeout: Option<Duration>,
     max_sockets: usize,
+    max_body_size: Option<u64>,
+    access_log: Option<AccessLog>,
 }
 
 impl<A: Accept> Server<A> {
@@ -60,6 +79,8 @@ impl<A: Accept> Server<A> {
             keep_alive: true,
             idle_timeout: Some(Duration::from_secs(10)),
             max_sockets: 4096,
+            max_body_size: None,
+            access_log: None,
         }
     }
 
@@ -86,14 +107,44 @@ impl<A: Accept> Server<A> {
         self.max_sockets = val;
         self
     }
+
+    /// Sets the largest request body that will be read, in bytes.
+    ///
+    /// A request declaring a longer `Content-Length` is answered with
+    /// `413 Payload Too Large` before its body is read, and a chunked body
+    /// is cut off once it grows over the limit. Either way, the `Handler`
+    /// is told with `on_error(Error::TooLarge)`, and the connection is
+    /// closed. A `Handler` can change the limit of a request with
+    /// `Request::set_max_body_size` in `on_request`.
+    ///
+    /// Default is `None`, reading bodies of any size.
+    pub fn max_body_size(mut self, val: Option<u64>) -> Server<A> {
+        self.max_body_size = val;
+        self
+    }
+
+    /// Calls `hook` with a `LogEntry` once each message is done.
+    ///
+    /// `LogEntry` can format itself in the Common or Combined Log Format,
+    /// or as a line of JSON:
+    ///
+    /// ```no_run
+    /// use hyper::server::Server;
+    ///
+    /// let server = Server::http(&"127.0.0.1:0".parse().unwrap()).unwrap()
+    ///     .access_log(|entry| println!("{}", entry.combined()));
+    /// ```
+    pub fn access_log<F>(mut self, hook: F) -> Server<A>
+    where F: Fn(&LogEntry) + Send + Sync + 'static {
+        self.access_log = Some(AccessLog::new(hook));
+        self
+    }
 }
 
 impl Server<HttpListener> { //<H: HandlerFactory<<HttpListener as Accept>::Output>> Server<HttpListener, H> {
     /// Creates a new HTTP server config listening on the provided address.
     pub fn http(addr: &SocketAddr) -> ::Result<Server<HttpListener>> {
-        use ::rotor::mio::tcp::TcpListener;
-        TcpListener::bind(addr)
-            .map(HttpListener)
+        HttpListener::bind(addr)
             .map(Server::new)
             .map_err(From::from)
     }
@@ -123,6 +174,8 @@ impl<A: Accept> Server<A> {
         config.mio().notify_capacity(self.max_sockets);
         let keep_alive = self.keep_alive;
         let idle_timeout = self.idle_timeout;
+        let max_body_size = self.max_body_size;
+        let access_log = self.access_log;
         let mut loop_ = rotor::Loop::new(&config).unwrap();
 
         let mut addrs = Vec::with_capacity(1 + self.other_listeners.len());
@@ -161,6 +214,8 @@ impl<A: Accept> Server<A> {
                 factory: factory,
                 idle_timeout: idle_timeout,
                 keep_alive: keep_alive,
+                max_body_size: max_body_size,
+                access_log: access_log,
             }))
         };
         Ok((listening, server))
@@ -189,13 +244,16 @@ struct Context<F> {
     factory: F,
     idle_timeout: Option<Duration>,
     keep_alive: bool,
+    max_body_size: Option<u64>,
+    access_log: Option<AccessLog>,
 }
 
 impl<F: HandlerFactory<T>, T: Transport> http::MessageHandlerFactory<(), T> for Context<F> {
     type Output = message::Message<F::Output, T>;
 
     fn create(&mut self, seed: http::Seed<()>) -> Option<Self::Output> {
-        Some(message::Message::new(self.factory.create(seed.control())))
+        let handler = self.factory.create(seed.control());
+        Some(message::Message::new(handler, self.max_body_size, self.access_log.clone()))
     }
 
     fn keep_alive_interest(&self) -> Next {
@@ -5,13 +5,14 @@
 
 use std::fmt;
 
+use form::Form;
 use version::HttpVersion;
 use method::Method;
-use header::Headers;
+use header::{EntityTag, Headers, HttpDate, Precondition};
 use http::{RequestHead, MessageHead, RequestLine};
 use uri::RequestUri;
 
-pub fn new<'a, T>(incoming: RequestHead, transport: &'a T) -> Request<'a, T> {
+pub fn new<'a, T>(incoming: RequestHead, transport: &'a T, max_body_size: &'a mut Option<u64>) -> Request<'a, T> {
     let MessageHead { version, subject: RequestLine(method, uri), headers } = incoming;
     debug!("Request Line: {:?} {:?} {:?}", method, uri, version);
     debug!("{:#?}", headers);
@@ -22,6 +23,7 @@ pub fn new<'a, T>(incoming: RequestHead, transport: &'a T) -> Request<'a, T> {
         headers: headers,
         version: version,
         transport: transport,
+        max_body_size: max_body_size,
     }
 }
 
@@ -32,6 +34,7 @@ pub struct Request<'a, T: 'a> {
     version: HttpVersion,
     headers: Headers,
     transport: &'a T,
+    max_body_size: &'a mut Option<u64>,
 }
 
 impl<'a, T> fmt::Debug for Request<'a, T> {
@@ -86,6 +89,33 @@ impl<'a, T> Request<'a, T> {
         }
     }
 
+    /// The pairs of the query string, percent-decoded.
+    ///
+    /// A `Request` without a query has an empty `Form`.
+    pub fn query_form(&self) -> Form {
+        self.query().map(|q| Form::parse(q.as_bytes())).unwrap_or_else(Form::new)
+    }
+
+    /// Evaluate the preconditions of this Request against the current
+    /// validators of the resource.
+    ///
+    /// See `Headers::evaluate_preconditions`.
+    pub fn evaluate_preconditions(&self, etag: Option<&EntityTag>,
+                                  last_modified: Option<&HttpDate>) -> Precondition {
+        self.headers.evaluate_preconditions(&self.method, etag, last_modified)
+    }
+
+    /// The largest body the server will read for this Request.
+    #[inline]
+    pub fn max_body_size(&self) -> Option<u64> { *self.max_body_size }
+
+    /// Override the `Server::max_body_size` for this Request.
+    ///
+    /// A body over the limit is refused with a `413 Payload Too Large`.
+    pub fn set_max_body_size(&mut self, val: Option<u64>) {
+        *self.max_body_size = val;
+    }
+
     /// Deconstruct this Request into its pieces.
     ///
     /// Modifying these pieces will have no effect on how hyper behaves.
@@ -0,0 +1,472 @@
+//! Request routing
+//!
+//! A `Router` is a `HandlerFactory` that picks the `Handler` for each
+//! request by its `Method` and path, matched against path templates:
+//!
+//! - `/users` matches only that path.
+//! - `/users/:id` captures one segment as the parameter `id`.
+//! - `/files/*path` captures the rest of the path, which may be empty, as
+//!   the parameter `path`.
+//!
+//! Routes are tried in the order they were added. A path that matches no
+//! route is answered with `404 Not Found`, and a path that matches only
+//! routes of other methods with `405 Method Not Allowed`, listing the
+//! methods in `Allow`. `OPTIONS` requests are answered with `Allow` too,
+//! unless a route handles them.
+//!
+//! `HEAD` requests that no route handles go to the matching `GET` route,
+//! whose handler should then leave out the body of its response.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use hyper::{Decoder, Encoder, Next, HttpStream};
+//! use hyper::server::{Handler, Request, Response, Server};
+//! use hyper::server::router::{Params, Router};
+//!
+//! struct User {
+//!     id: String,
+//! }
+//!
+//! impl Handler<HttpStream> for User {
+//!     fn on_request(&mut self, _: Request<HttpStream>) -> Next {
+//!         Next::write()
+//!     }
+//!     fn on_request_readable(&mut self, _: &mut Decoder<HttpStream>) -> Next {
+//!         Next::write()
+//!     }
+//!     fn on_response(&mut self, _: &mut Response) -> Next {
+//!         Next::write()
+//!     }
+//!     fn on_response_writable(&mut self, encoder: &mut Encoder<HttpStream>) -> Next {
+//!         let _ = encoder.write(self.id.as_bytes());
+//!         Next::end()
+//!     }
+//! }
+//!
+//! let router = Router::new()
+//!     .get("/users/:id", |_, params: Params| User { id: params.get("id").unwrap().to_owned() });
+//! let server = Server::http(&"127.0.0.1:0".parse().unwrap()).unwrap();
+//! let _ = server.handle(router);
+//! ```
+
+use std::fmt;
+use std::slice;
+use std::sync::Arc;
+
+use url::percent_encoding::percent_decode;
+
+use header::Allow;
+use http::{self, Control, Next};
+use method::Method;
+use net::Transport;
+use status::StatusCode;
+use super::{Handler, HandlerFactory, Reply, Request, Response};
+
+/// The parameters captured from a request path by a route's template.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Params {
+    pairs: Vec<(String, String)>,
+}
+
+impl Params {
+    /// The percent-decoded value of a parameter.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.pairs.iter().find(|pair| pair.0 == name).map(|pair| &pair.1[..])
+    }
+
+    /// Iterate over the parameters, in the order of the template.
+    pub fn iter(&self) -> Iter {
+        Iter(self.pairs.iter())
+    }
+
+    /// The number of parameters.
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    /// Whether there are no parameters.
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+}
+
+/// An iterator over the parameters of `Params`.
+#[derive(Debug)]
+pub struct Iter<'a>(slice::Iter<'a, (String, String)>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<(&'a str, &'a str)> {
+        self.0.next().map(|pair| (&pair.0[..], &pair.1[..]))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+    Tail(String),
+}
+
+/// A parsed path template.
+#[derive(Debug, Clone, PartialEq)]
+struct Pattern(Vec<Segment>);
+
+impl Pattern {
+    fn parse(template: &str) -> Pattern {
+        assert!(template.starts_with('/'), "route template must start with '/': {:?}", template);
+        let parts = template[1..].split('/').collect::<Vec<_>>();
+        let last = parts.len() - 1;
+        Pattern(parts.into_iter().enumerate().map(|(i, part)| {
+            if part.starts_with(':') {
+                Segment::Param(part[1..].to_owned())
+            } else if part.starts_with('*') {
+                assert!(i == last, "route wildcard must be last: {:?}", template);
+                Segment::Tail(part[1..].to_owned())
+            } else {
+                Segment::Literal(decode(part))
+            }
+        }).collect())
+    }
+
+    /// Matches a request path, returning the captured parameters.
+    fn matches(&self, path: &str) -> Option<Params> {
+        if !path.starts_with('/') {
+            return None;
+        }
+        let mut params = Params::default();
+        let mut rest = &path[1..];
+        for (i, segment) in self.0.iter().enumerate() {
+            if let Segment::Tail(ref name) = *segment {
+                params.pairs.push((name.clone(), decode(rest)));
+                return Some(params);
+            }
+            let (part, next) = match rest.find('/') {
+                Some(slash) => (&rest[..slash], Some(&rest[slash + 1..])),
+                None => (rest, None)
+            };
+            match *segment {
+                Segment::Literal(ref literal) => {
+                    if decode(part) != *literal {
+                        return None;
+                    }
+                },
+                Segment::Param(ref name) => {
+                    if part.is_empty() {
+                        return None;
+                    }
+                    params.pairs.push((name.clone(), decode(part)));
+                },
+                Segment::Tail(..) => unreachable!("tail segments return early")
+            }
+            match next {
+                Some(next) => rest = next,
+                None => return if i == self.0.len() - 1 { Some(params) } else { None }
+            }
+        }
+        // the path has more segments than the template
+        None
+    }
+}
+
+fn decode(s: &str) -> String {
+    String::from_utf8_lossy(&percent_decode(s.as_bytes()).collect::<Vec<u8>>()).into_owned()
+}
+
+/// A `Handler` of any type, boxed so that routes can differ.
+trait Boxed<T: Transport> {
+    fn on_request(&mut self, request: Request<T>) -> Next;
+    fn on_request_readable(&mut self, request: &mut http::Decoder<T>) -> Next;
+    fn on_response(&mut self, response: &mut Response) -> Next;
+    fn on_response_writable(&mut self, response: &mut http::Encoder<T>) -> Next;
+    fn on_error(&mut self, err: ::Error) -> Next;
+    fn on_remove(self: Box<Self>, transport: T);
+}
+
+impl<H: Handler<T>, T: Transport> Boxed<T> for H {
+    fn on_request(&mut self, request: Request<T>) -> Next {
+        Handler::on_request(self, request)
+    }
+
+    fn on_request_readable(&mut self, request: &mut http::Decoder<T>) -> Next {
+        Handler::on_request_readable(self, request)
+    }
+
+    fn on_response(&mut self, response: &mut Response) -> Next {
+        Handler::on_response(self, response)
+    }
+
+    fn on_response_writable(&mut self, response: &mut http::Encoder<T>) -> Next {
+        Handler::on_response_writable(self, response)
+    }
+
+    fn on_error(&mut self, err: ::Error) -> Next {
+        Handler::on_error(self, err)
+    }
+
+    fn on_remove(self: Box<Self>, transport: T) {
+        Handler::on_remove(*self, transport)
+    }
+}
+
+type Create<T> = Box<Fn(Control, Params) -> Box<Boxed<T>> + Send + Sync>;
+
+struct Route<T: Transport> {
+    method: Method,
+    pattern: Pattern,
+    create: Create<T>,
+}
+
+/// A `HandlerFactory` that dispatches each request to the `Handler` of the
+/// route matching its `Method` and path.
+pub struct Router<T: Transport> {
+    routes: Arc<Vec<Route<T>>>,
+}
+
+impl<T: Transport> Router<T> {
+    /// Create a `Router` without routes.
+    pub fn new() -> Router<T> {
+        Router {
+            routes: Arc::new(Vec::new()),
+        }
+    }
+
+    /// Add a route for requests of `method` whose path matches `template`.
+    ///
+    /// `create` is called with the `Control` of the message and the
+    /// captured `Params` to create the `Handler` of each matching request.
+    ///
+    /// # Panics
+    ///
+    /// If the template doesn't start with `/`, or has a `*` wildcard that
+    /// isn't its last segment.
+    pub fn route<F, H>(mut self, method: Method, template: &str, create: F) -> Router<T>
+    where F: Fn(Control, Params) -> H + Send + Sync + 'static, H: Handler<T> + 'static {
+        let route = Route {
+            method: method,
+            pattern: Pattern::parse(template),
+            create: Box::new(move |ctrl, params| Box::new(create(ctrl, params)) as Box<Boxed<T>>),
+        };
+        Arc::get_mut(&mut self.routes).expect("routes are only shared once the router is used").push(route);
+        self
+    }
+
+    /// Add a route for `GET` requests.
+    pub fn get<F, H>(self, template: &str, create: F) -> Router<T>
+    where F: Fn(Control, Params) -> H + Send + Sync + 'static, H: Handler<T> + 'static {
+        self.route(Method::Get, template, create)
+    }
+
+    /// Add a route for `POST` requests.
+    pub fn post<F, H>(self, template: &str, create: F) -> Router<T>
+    where F: Fn(Control, Params) -> H + Send + Sync + 'static, H: Handler<T> + 'static {
+        self.route(Method::Post, template, create)
+    }
+
+    /// Add a route for `PUT` requests.
+    pub fn put<F, H>(self, template: &str, create: F) -> Router<T>
+    where F: Fn(Control, Params) -> H + Send + Sync + 'static, H: Handler<T> + 'static {
+        self.route(Method::Put, template, create)
+    }
+
+    /// Add a route for `DELETE` requests.
+    pub fn delete<F, H>(self, template: &str, create: F) -> Router<T>
+    where F: Fn(Control, Params) -> H + Send + Sync + 'static, H: Handler<T> + 'static {
+        self.route(Method::Delete, template, create)
+    }
+}
+
+impl<T: Transport> fmt::Debug for Router<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut list = f.debug_list();
+        for route in self.routes.iter() {
+            list.entry(&(&route.method, &route.pattern));
+        }
+        list.finish()
+    }
+}
+
+impl<T: Transport> HandlerFactory<T> for Router<T> {
+    type Output = Routed<T>;
+
+    fn create(&mut self, ctrl: Control) -> Routed<T> {
+        Routed {
+            routes: self.routes.clone(),
+            ctrl: Some(ctrl),
+            handler: None,
+        }
+    }
+}
+
+/// The `Handler` of a `Router`, for one message.
+pub struct Routed<T: Transport> {
+    routes: Arc<Vec<Route<T>>>,
+    ctrl: Option<Control>,
+    handler: Option<Box<Boxed<T>>>,
+}
+
+impl<T: Transport> Routed<T> {
+    /// Picks the handler of a request, or a reply if no route handles it.
+    fn dispatch(&mut self, method: &Method, path: Option<&str>) -> Box<Boxed<T>> {
+        let ctrl = self.ctrl.take().expect("Routed dispatches once");
+        let mut allowed = Vec::new();
+        let path = match path {
+            Some(path) => path,
+            None => {
+                // `OPTIONS *` asks about the server as a whole
+                if *method == Method::Options {
+                    for route in self.routes.iter() {
+                        allow_route(&mut allowed, &route.method);
+                    }
+                    allow(&mut allowed, &Method::Options);
+                    return Box::new(Reply::new(StatusCode::Ok).header(Allow(allowed)));
+                }
+                return Box::new(Reply::new(StatusCode::NotFound));
+            }
+        };
+        let mut get = None;
+        for route in self.routes.iter() {
+            if let Some(params) = route.pattern.matches(path) {
+                if route.method == *method {
+                    return (route.create)(ctrl, params);
+                }
+                if *method == Method::Head && route.method == Method::Get && get.is_none() {
+                    get = Some((route, params));
+                }
+                allow_route(&mut allowed, &route.method);
+            }
+        }
+        if let Some((route, params)) = get {
+            return (route.create)(ctrl, params);
+        }
+        if allowed.is_empty() {
+            return Box::new(Reply::new(StatusCode::NotFound));
+        }
+        allow(&mut allowed, &Method::Options);
+        if *method == Method::Options {
+            Box::new(Reply::new(StatusCode::Ok).header(Allow(allowed)))
+        } else {
+            Box::new(Reply::new(StatusCode::MethodNotAllowed).header(Allow(allowed)))
+        }
+    }
+
+    fn handler(&mut self) -> &mut Box<Boxed<T>> {
+        self.handler.as_mut().expect("Routed.handler is missing")
+    }
+}
+
+fn allow(allowed: &mut Vec<Method>, method: &Method) {
+    if !allowed.contains(method) {
+        allowed.push(method.clone());
+    }
+}
+
+/// Allows the method of a route, and `HEAD` along with `GET`.
+fn allow_route(allowed: &mut Vec<Method>, method: &Method) {
+    allow(allowed, method);
+    if *method == Method::Get {
+        allow(allowed, &Method::Head);
+    }
+}
+
+impl<T: Transport> Handler<T> for Routed<T> {
+    fn on_request(&mut self, req: Request<T>) -> Next {
+        let mut handler = self.dispatch(req.method(), req.path());
+        let next = handler.on_request(req);
+        self.handler = Some(handler);
+        next
+    }
+
+    fn on_request_readable(&mut self, decoder: &mut http::Decoder<T>) -> Next {
+        self.handler().on_request_readable(decoder)
+    }
+
+    fn on_response(&mut self, res: &mut Response) -> Next {
+        self.handler().on_response(res)
+    }
+
+    fn on_response_writable(&mut self, encoder: &mut http::Encoder<T>) -> Next {
+        self.handler().on_response_writable(encoder)
+    }
+
+    fn on_error(&mut self, err: ::Error) -> Next {
+        match self.handler {
+            Some(ref mut handler) => handler.on_error(err),
+            None => {
+                debug!("router error before dispatch: {:?}", err);
+                Next::remove()
+            }
+        }
+    }
+
+    fn on_remove(self, transport: T) {
+        if let Some(handler) = self.handler {
+            handler.on_remove(transport);
+        }
+    }
+}
+
+impl<T: Transport> fmt::Debug for Routed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Routed")
+            .field("dispatched", &self.handler.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Params, Pattern, Segment};
+
+    fn params(pairs: &[(&str, &str)]) -> Params {
+        Params {
+            pairs: pairs.iter().map(|&(n, v)| (n.to_owned(), v.to_owned())).collect()
+        }
+    }
+
+    #[test]
+    fn test_pattern_parse() {
+        assert_eq!(Pattern::parse("/users/:id/files/*path"), Pattern(vec![
+            Segment::Literal("users".to_owned()),
+            Segment::Param("id".to_owned()),
+            Segment::Literal("files".to_owned()),
+            Segment::Tail("path".to_owned()),
+        ]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_pattern_parse_tail_not_last() {
+        Pattern::parse("/files/*path/edit");
+    }
+
+    #[test]
+    fn test_pattern_matches() {
+        let root = Pattern::parse("/");
+        assert_eq!(root.matches("/"), Some(params(&[])));
+        assert_eq!(root.matches("/users"), None);
+
+        let user = Pattern::parse("/users/:id");
+        assert_eq!(user.matches("/users/42"), Some(params(&[("id", "42")])));
+        assert_eq!(user.matches("/users/a%20b"), Some(params(&[("id", "a b")])));
+        assert_eq!(user.matches("/users/"), None);
+        assert_eq!(user.matches("/users"), None);
+        assert_eq!(user.matches("/users/42/posts"), None);
+        assert_eq!(user.matches("/people/42"), None);
+
+        let files = Pattern::parse("/files/*path");
+        assert_eq!(files.matches("/files/a/b%2Fc.txt"), Some(params(&[("path", "a/b/c.txt")])));
+        assert_eq!(files.matches("/files/"), Some(params(&[("path", "")])));
+        assert_eq!(files.matches("/files"), None);
+    }
+
+    #[test]
+    fn test_params() {
+        let params = params(&[("id", "42"), ("name", "x")]);
+        assert_eq!(params.get("name"), Some("x"));
+        assert_eq!(params.get("missing"), None);
+        assert_eq!(params.iter().collect::<Vec<_>>(), vec![("id", "42"), ("name", "x")]);
+    }
+}
@@ -0,0 +1,318 @@
+//! Server-Sent Events
+//!
+//! An `EventStream` is a `Handler` answering a request with a
+//! [`text/event-stream`](https://html.spec.whatwg.org/multipage/server-sent-events.html)
+//! response, which stays open, sending each `Event` given to its
+//! `EventSender`. The sender can be moved to other threads, and sending an
+//! event wakes the stream with its `http::Control`.
+//!
+//! When a client reconnects, it sends the ID of the last event it saw as a
+//! `LastEventID` header, which is handed to the stream's `on_open` callback
+//! along with the sender, so that it can resume from there. The stream ends
+//! once every clone of the sender has been dropped.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::thread;
+//! use std::time::Duration;
+//! use hyper::server::Server;
+//! use hyper::server::sse::{Event, EventStream};
+//!
+//! let server = Server::http(&"127.0.0.1:0".parse().unwrap()).unwrap();
+//! let _ = server.handle(|ctrl| {
+//!     EventStream::new(ctrl, |events, last_event_id: Option<String>| {
+//!         let mut id = last_event_id.and_then(|id| id.parse().ok()).unwrap_or(0u64);
+//!         thread::spawn(move || {
+//!             loop {
+//!                 id += 1;
+//!                 let tick = Event::new(format!("tick {}", id)).event("tick").id(id.to_string());
+//!                 if events.send(tick).is_err() {
+//!                     break;
+//!                 }
+//!                 thread::sleep(Duration::from_secs(1));
+//!             }
+//!         });
+//!     }).heartbeat(Duration::from_secs(15))
+//! });
+//! ```
+
+use std::fmt;
+use std::io;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use header::{CacheControl, CacheDirective, ContentType, LastEventID};
+use http::{self, Control, Next};
+use net::Transport;
+use status::StatusCode;
+use super::{Handler, Request, Response};
+
+/// An event of an `EventStream`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Event {
+    event: Option<String>,
+    id: Option<String>,
+    retry: Option<u32>,
+    data: String,
+}
+
+impl Event {
+    /// Create an event of the data, which may span several lines.
+    pub fn new<S: Into<String>>(data: S) -> Event {
+        Event {
+            data: data.into(),
+            ..Event::default()
+        }
+    }
+
+    /// Set the type of the event, which is `message` otherwise.
+    pub fn event<S: Into<String>>(mut self, event: S) -> Event {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// Set the ID of the event, which the client sends back as the
+    /// `LastEventID` when it reconnects.
+    pub fn id<S: Into<String>>(mut self, id: S) -> Event {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Tell the client to wait `millis` before reconnecting, if the stream
+    /// closes.
+    pub fn retry(mut self, millis: u32) -> Event {
+        self.retry = Some(millis);
+        self
+    }
+
+    /// Appends the event, in the `text/event-stream` format, to `buf`.
+    fn encode(&self, buf: &mut Vec<u8>) {
+        if let Some(ref event) = self.event {
+            field(buf, "event", event);
+        }
+        if let Some(ref id) = self.id {
+            field(buf, "id", id);
+        }
+        if let Some(retry) = self.retry {
+            field(buf, "retry", &retry.to_string());
+        }
+        for line in self.data.split('\n') {
+            field(buf, "data", line.trim_right_matches('\r'));
+        }
+        buf.push(b'\n');
+    }
+}
+
+/// Appends one field, leaving out any line breaks that would end it early.
+fn field(buf: &mut Vec<u8>, name: &str, value: &str) {
+    buf.extend_from_slice(name.as_bytes());
+    buf.extend_from_slice(b": ");
+    buf.extend(value.bytes().filter(|&b| b != b'\r' && b != b'\n' && b != b'\0'));
+    buf.push(b'\n');
+}
+
+/// Sends `Event`s to an `EventStream`, from any thread.
+#[derive(Clone)]
+pub struct EventSender {
+    tx: mpsc::Sender<Event>,
+    ctrl: Control,
+}
+
+impl EventSender {
+    /// Send an event to the stream.
+    ///
+    /// If the stream has closed, the event is given back.
+    pub fn send(&self, event: Event) -> Result<(), Event> {
+        match self.tx.send(event) {
+            Ok(()) => {
+                let _ = self.ctrl.ready(Next::write());
+                Ok(())
+            },
+            Err(mpsc::SendError(event)) => Err(event)
+        }
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        // wake the stream, so that it notices when the last sender is gone
+        let _ = self.ctrl.ready(Next::write());
+    }
+}
+
+impl fmt::Debug for EventSender {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("EventSender")
+    }
+}
+
+/// A `Handler` answering a request with a stream of `Event`s.
+pub struct EventStream<F> {
+    on_open: Option<F>,
+    tx: Option<EventSender>,
+    rx: mpsc::Receiver<Event>,
+    heartbeat: Option<Duration>,
+    retry: Option<u32>,
+    beat: bool,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<F: FnOnce(EventSender, Option<String>)> EventStream<F> {
+    /// Create a stream woken by `ctrl`.
+    ///
+    /// Once the request is received, `on_open` is called with the
+    /// `EventSender`, and the `LastEventID` the client sent, if any.
+    pub fn new(ctrl: Control, on_open: F) -> EventStream<F> {
+        let (tx, rx) = mpsc::channel();
+        EventStream {
+            on_open: Some(on_open),
+            tx: Some(EventSender {
+                tx: tx,
+                ctrl: ctrl,
+            }),
+            rx: rx,
+            heartbeat: None,
+            retry: None,
+            beat: false,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Send a comment after each `interval` without events, so that
+    /// proxies don't close the connection for being idle.
+    pub fn heartbeat(mut self, interval: Duration) -> EventStream<F> {
+        self.heartbeat = Some(interval);
+        self
+    }
+
+    /// Tell the client to wait `millis` before reconnecting, if the stream
+    /// closes.
+    pub fn retry(mut self, millis: u32) -> EventStream<F> {
+        self.retry = Some(millis);
+        self
+    }
+
+    /// Waits for the next event, or heartbeat.
+    fn idle(&self) -> Next {
+        match self.heartbeat {
+            Some(interval) => Next::wait().timeout(interval),
+            None => Next::wait()
+        }
+    }
+
+    /// Refills the buffer with any heartbeat and events sent since it was
+    /// last filled, returning whether the stream has ended.
+    fn fill(&mut self) -> bool {
+        self.buf.clear();
+        self.pos = 0;
+        if self.beat {
+            self.beat = false;
+            self.buf.extend_from_slice(b":\n\n");
+        }
+        loop {
+            match self.rx.try_recv() {
+                Ok(event) => event.encode(&mut self.buf),
+                Err(mpsc::TryRecvError::Empty) => return false,
+                Err(mpsc::TryRecvError::Disconnected) => return true
+            }
+        }
+    }
+}
+
+impl<F: FnOnce(EventSender, Option<String>), T: Transport> Handler<T> for EventStream<F> {
+    fn on_request(&mut self, req: Request<T>) -> Next {
+        let last_event_id = req.headers().get::<LastEventID>().map(|id| id.0.clone());
+        if let (Some(on_open), Some(tx)) = (self.on_open.take(), self.tx.take()) {
+            on_open(tx, last_event_id);
+        }
+        Next::write()
+    }
+
+    fn on_request_readable(&mut self, _decoder: &mut http::Decoder<T>) -> Next {
+        Next::write()
+    }
+
+    fn on_response(&mut self, res: &mut Response) -> Next {
+        res.set_status(StatusCode::Ok);
+        res.headers_mut().set(ContentType("text/event-stream".parse().expect("valid mime")));
+        res.headers_mut().set(CacheControl(vec![CacheDirective::NoCache]));
+        // start with something, so that the head is sent right away
+        match self.retry {
+            Some(retry) => self.buf.extend_from_slice(format!("retry: {}\n\n", retry).as_bytes()),
+            None => self.buf.extend_from_slice(b":\n\n")
+        }
+        Next::write()
+    }
+
+    fn on_response_writable(&mut self, encoder: &mut http::Encoder<T>) -> Next {
+        loop {
+            if self.pos == self.buf.len() {
+                let ended = self.fill();
+                if self.buf.is_empty() {
+                    return if ended { Next::end() } else { self.idle() };
+                }
+            }
+            match encoder.write(&self.buf[self.pos..]) {
+                Ok(0) => return Next::end(),
+                Ok(n) => self.pos += n,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Next::write(),
+                Err(e) => {
+                    debug!("error writing event stream: {:?}", e);
+                    return Next::remove();
+                }
+            }
+        }
+    }
+
+    fn on_error(&mut self, err: ::Error) -> Next {
+        match err {
+            ::Error::Timeout => {
+                self.beat = true;
+                Next::write()
+            },
+            err => {
+                debug!("event stream error: {:?}", err);
+                Next::remove()
+            }
+        }
+    }
+}
+
+impl<F> fmt::Debug for EventStream<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventStream")
+            .field("heartbeat", &self.heartbeat)
+            .field("retry", &self.retry)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Event;
+
+    fn encode(event: Event) -> String {
+        let mut buf = Vec::new();
+        event.encode(&mut buf);
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_event_encode() {
+        assert_eq!(encode(Event::new("hello")), "data: hello\n\n");
+        assert_eq!(encode(Event::new("")), "data: \n\n");
+        assert_eq!(encode(Event::new("{}").event("update").id("7").retry(1000)),
+                   "event: update\nid: 7\nretry: 1000\ndata: {}\n\n");
+    }
+
+    #[test]
+    fn test_event_encode_multiline() {
+        assert_eq!(encode(Event::new("one\ntwo\r\nthree\n")),
+                   "data: one\ndata: two\ndata: three\ndata: \n\n");
+        assert_eq!(encode(Event::new("x").event("a\nb").id("1\r2")),
+                   "event: ab\nid: 12\ndata: x\n\n");
+    }
+}
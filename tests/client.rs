@@ -2,7 +2,7 @@
 extern crate hyper;
 
 use std::io::{self, Read, Write};
-use std::net::TcpListener;
+use std::net::{IpAddr, TcpListener};
 use std::sync::mpsc;
 use std::time::Duration;
 
@@ -178,7 +178,12 @@ impl Drop for Client {
 }
 
 fn client() -> Client {
-    let c = hyper::Client::<TestHandler>::configure()
+    client_with(hyper::Client::<TestHandler>::configure())
+}
+
+fn client_with<C>(config: hyper::client::Config<C>) -> Client
+where C: hyper::client::Connect + Send + 'static {
+    let c = config
         .connector(HttpConnector::default())
         .build().unwrap();
     Client {
@@ -364,6 +369,187 @@ fn client_read_timeout() {
     }
 }
 
+#[test]
+fn client_first_byte_timeout() {
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = client_with(hyper::Client::<TestHandler>::configure()
+        .first_byte_timeout(Some(Duration::from_millis(500))));
+    let res = client.request(format!("http://{}/", addr), opts());
+
+    let mut inc = server.accept().unwrap().0;
+    let mut buf = [0; 4096];
+    inc.read(&mut buf).unwrap();
+
+    match res.recv() {
+        Ok(Msg::Error(hyper::Error::FirstByteTimeout)) => (),
+        other => panic!("expected first byte timeout, actual: {:?}", other)
+    }
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn client_handshake_timeout() {
+    use hyper::client::fetch::Fetch;
+
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = hyper::Client::<Fetch>::configure()
+        .handshake_timeout(Duration::from_millis(500))
+        .build().unwrap();
+    let future = client.get(format!("https://{}/", addr).parse().unwrap());
+
+    // accept, but never answer the ClientHello
+    let mut sock = server.accept().unwrap().0;
+    sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut buf = [0; 4096];
+    sock.read(&mut buf).unwrap();
+
+    match future.wait() {
+        Err(hyper::Error::HandshakeTimeout) => (),
+        other => panic!("expected handshake timeout, actual: {:?}", other)
+    }
+    client.close();
+}
+
+#[test]
+fn client_request_deadline() {
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = client_with(hyper::Client::<TestHandler>::configure()
+        .request_timeout(Some(Duration::from_secs(1))));
+    let res = client.request(format!("http://{}/", addr), opts().read_timeout(Duration::from_secs(5)));
+
+    let mut inc = server.accept().unwrap().0;
+    let mut buf = [0; 4096];
+    inc.read(&mut buf).unwrap();
+    inc.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nfoo").unwrap();
+
+    match res.recv() {
+        Ok(Msg::Head(head)) => assert_eq!(head.status(), &StatusCode::Ok),
+        other => panic!("expected head, actual: {:?}", other)
+    }
+    loop {
+        match res.recv() {
+            Ok(Msg::Chunk(_)) => (),
+            Ok(Msg::Error(hyper::Error::Deadline)) => break,
+            other => panic!("expected deadline, actual: {:?}", other)
+        }
+    }
+}
+
+#[test]
+fn client_local_address() {
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = hyper::Client::<TestHandler>::configure()
+        .connector(HttpConnector::default()
+            .local_address("127.0.0.1".parse().unwrap())
+            .nodelay(true)
+            .keepalive(Some(Duration::from_secs(60))))
+        .build().unwrap();
+    let client = Client {
+        client: Some(client),
+    };
+    let res = client.request(format!("http://{}/", addr), opts());
+
+    let (mut inc, peer) = server.accept().unwrap();
+    assert_eq!(peer.ip(), "127.0.0.1".parse::<IpAddr>().unwrap());
+    inc.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut buf = [0; 4096];
+    inc.read(&mut buf).unwrap();
+    inc.write_all(REPLY_OK.as_ref()).unwrap();
+
+    match res.recv() {
+        Ok(Msg::Head(head)) => assert_eq!(head.status(), &StatusCode::Ok),
+        other => panic!("expected head, actual: {:?}", other)
+    }
+}
+
+#[test]
+fn client_decompress_gzip() {
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = client_with(hyper::Client::<TestHandler>::configure().decompress(true));
+    let res = client.request(format!("http://{}/", addr), opts());
+
+    let mut inc = server.accept().unwrap().0;
+    inc.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut buf = [0; 4096];
+    let n = inc.read(&mut buf).unwrap();
+    assert!(s(&buf[..n]).contains("Accept-Encoding: gzip, deflate\r\n"));
+    inc.write_all(b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: 31\r\n\r\n").unwrap();
+    // "hello world"
+    inc.write_all(&[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0xcb, 0x48,
+        0xcd, 0xc9, 0xc9, 0x57, 0x28, 0xcf, 0x2f, 0xca, 0x49, 0x01, 0x00, 0x85,
+        0x11, 0x4a, 0x0d, 0x0b, 0x00, 0x00, 0x00,
+    ]).unwrap();
+
+    match res.recv() {
+        Ok(Msg::Head(head)) => {
+            assert!(!head.headers().has::<hyper::header::ContentEncoding>());
+            assert!(!head.headers().has::<hyper::header::ContentLength>());
+        },
+        other => panic!("expected head, actual: {:?}", other)
+    }
+    let mut body = Vec::new();
+    loop {
+        match res.recv() {
+            Ok(Msg::Chunk(ref chunk)) if chunk.is_empty() => break,
+            Ok(Msg::Chunk(chunk)) => body.extend_from_slice(&chunk),
+            other => panic!("expected body, actual: {:?}", other)
+        }
+    }
+    assert_eq!(body, b"hello world");
+}
+
+#[test]
+fn client_decompress_empty() {
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = client_with(hyper::Client::<TestHandler>::configure().decompress(true));
+    let res = client.request(format!("http://{}/", addr), opts());
+
+    let mut inc = server.accept().unwrap().0;
+    inc.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut buf = [0; 4096];
+    inc.read(&mut buf).unwrap();
+    inc.write_all(b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: 0\r\n\r\n").unwrap();
+
+    match res.recv() {
+        Ok(Msg::Head(head)) => {
+            // nothing is inflated, so the headers are kept
+            assert!(head.headers().has::<hyper::header::ContentEncoding>());
+            assert_eq!(head.headers().get(), Some(&hyper::header::ContentLength(0)));
+        },
+        other => panic!("expected head, actual: {:?}", other)
+    }
+}
+
+#[test]
+fn client_decompress_head() {
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = client_with(hyper::Client::<TestHandler>::configure().decompress(true));
+    let res = client.request(format!("http://{}/", addr), opts().method(Method::Head));
+
+    let mut inc = server.accept().unwrap().0;
+    inc.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut buf = [0; 4096];
+    inc.read(&mut buf).unwrap();
+    inc.write_all(b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: 31\r\n\r\n").unwrap();
+
+    match res.recv() {
+        Ok(Msg::Head(head)) => {
+            // a HEAD response has no body, so its headers describe the GET
+            assert!(head.headers().has::<hyper::header::ContentEncoding>());
+            assert_eq!(head.headers().get(), Some(&hyper::header::ContentLength(31)));
+        },
+        other => panic!("expected head, actual: {:?}", other)
+    }
+}
+
 #[test]
 fn client_keep_alive() {
     let server = TcpListener::bind("127.0.0.1:0").unwrap();
@@ -388,3 +574,361 @@ fn client_keep_alive() {
 
     while let Ok(_) = res.recv() {}
 }
+
+#[test]
+fn client_retry_closed_keep_alive() {
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = client();
+    let res = client.request(format!("http://{}/a", addr), opts());
+
+    let mut sock = server.accept().unwrap().0;
+    sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    sock.set_write_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut buf = [0; 4096];
+    sock.read(&mut buf).expect("read 1");
+    sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").expect("write 1");
+
+    while let Ok(_) = res.recv() {}
+    // the server closes the kept-alive connection while the client is idle
+    drop(sock);
+
+    let res = client.request(format!("http://{}/b", addr), opts());
+    let mut sock = server.accept().unwrap().0;
+    sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    sock.set_write_timeout(Some(Duration::from_secs(5))).unwrap();
+    sock.read(&mut buf).expect("read 2");
+    let second_get = b"GET /b HTTP/1.1\r\n";
+    assert_eq!(&buf[..second_get.len()], second_get);
+    sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").expect("write 2");
+
+    match res.recv() {
+        Ok(Msg::Head(head)) => assert_eq!(head.status(), &StatusCode::Ok),
+        other => panic!("expected retried response, actual: {:?}", other)
+    }
+}
+
+#[test]
+fn client_no_retry_partial_response() {
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = client();
+    let res = client.request(format!("http://{}/a", addr), opts());
+
+    let mut sock = server.accept().unwrap().0;
+    sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    sock.set_write_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut buf = [0; 4096];
+    sock.read(&mut buf).expect("read 1");
+    sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").expect("write 1");
+
+    while let Ok(_) = res.recv() {}
+
+    let res = client.request(format!("http://{}/b", addr), opts());
+    sock.read(&mut buf).expect("read 2");
+    // the server started answering, so it has seen the request
+    sock.write_all(b"HTTP/1.1 2").expect("write 2");
+    drop(sock);
+
+    match res.recv() {
+        Ok(Msg::Error(hyper::Error::Io(_))) => (),
+        other => panic!("expected io error, actual: {:?}", other)
+    }
+    server.set_nonblocking(true).unwrap();
+    match server.accept() {
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+        other => panic!("expected no retry, actual: {:?}", other)
+    }
+}
+
+#[test]
+fn client_blocking() {
+    use hyper::client::blocking;
+    use hyper::header::ContentLength;
+
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = blocking::Client::new().unwrap();
+
+    let thread = ::std::thread::spawn(move || {
+        let mut sock = server.accept().unwrap().0;
+        sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let expected = "POST /echo HTTP/1.1\r\nHost: ";
+        let mut buf = [0; 4096];
+        let mut n = 0;
+        while !s(&buf[..n]).ends_with("foo bar") {
+            n += sock.read(&mut buf[n..]).unwrap();
+        }
+        assert!(s(&buf[..n]).starts_with(expected));
+        sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\nhello world").unwrap();
+    });
+
+    let mut res = client.post(format!("http://{}/echo", addr).parse().unwrap())
+        .header(ContentLength(7))
+        .body(&b"foo bar"[..])
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), &StatusCode::Ok);
+    let mut body = String::new();
+    res.read_to_string(&mut body).unwrap();
+    assert_eq!(body, "hello world");
+    thread.join().unwrap();
+}
+
+#[test]
+fn client_blocking_retry_headers() {
+    use hyper::client::blocking;
+    use hyper::header::Referer;
+
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = blocking::Client::new().unwrap();
+
+    let thread = ::std::thread::spawn(move || {
+        let mut buf = [0; 4096];
+        let mut sock = server.accept().unwrap().0;
+        sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        sock.read(&mut buf).unwrap();
+        sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+        // the kept-alive connection closes without answering the second request
+        let n = sock.read(&mut buf).unwrap();
+        assert!(s(&buf[..n]).starts_with("GET /b HTTP/1.1\r\n"));
+        drop(sock);
+
+        let mut sock = server.accept().unwrap().0;
+        sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let n = sock.read(&mut buf).unwrap();
+        let req = s(&buf[..n]);
+        assert!(req.starts_with("GET /b HTTP/1.1\r\n"), "{}", req);
+        assert!(req.contains("Referer: http://example.com/\r\n"), "{}", req);
+        sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+    });
+
+    let mut body = Vec::new();
+    let mut res = client.get(format!("http://{}/a", addr).parse().unwrap()).send().unwrap();
+    res.read_to_end(&mut body).unwrap();
+    drop(res);
+
+    let res = client.get(format!("http://{}/b", addr).parse().unwrap())
+        .header(Referer("http://example.com/".to_owned()))
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), &StatusCode::Ok);
+    thread.join().unwrap();
+}
+
+#[test]
+fn client_blocking_drop_closes() {
+    use hyper::client::blocking;
+
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = blocking::Client::new().unwrap();
+    let (tx, rx) = mpsc::channel();
+
+    let thread = ::std::thread::spawn(move || {
+        let mut sock = server.accept().unwrap().0;
+        sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        sock.set_write_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut buf = [0; 4096];
+        sock.read(&mut buf).unwrap();
+        sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 67108864\r\n\r\n").unwrap();
+        let chunk = [b'x'; 8192];
+        // far more than the client buffers, so it stops reading until the
+        // caller catches up
+        loop {
+            if let Err(e) = sock.write_all(&chunk) {
+                tx.send(e.kind()).unwrap();
+                return;
+            }
+        }
+    });
+
+    let mut res = client.get(format!("http://{}/big", addr).parse().unwrap()).send().unwrap();
+    let mut buf = [0; 1024];
+    res.read(&mut buf).unwrap();
+    drop(res);
+
+    let kind = rx.recv_timeout(Duration::from_secs(10)).expect("server never saw the close");
+    assert!(kind != io::ErrorKind::WouldBlock && kind != io::ErrorKind::TimedOut,
+            "connection stayed open: {:?}", kind);
+    thread.join().unwrap();
+}
+
+#[test]
+fn client_fetch_many() {
+    use hyper::client::fetch::Fetch;
+
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = hyper::Client::<Fetch>::new().unwrap();
+
+    let thread = ::std::thread::spawn(move || {
+        for _ in 0..3 {
+            let mut sock = server.accept().unwrap().0;
+            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            let mut buf = [0; 4096];
+            sock.read(&mut buf).unwrap();
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello").unwrap();
+        }
+    });
+
+    let futures = (0..3).map(|i| client.get(format!("http://{}/{}", addr, i).parse().unwrap()))
+        .collect::<Vec<_>>();
+    for future in futures {
+        let (res, body) = future.wait().unwrap();
+        assert_eq!(res.status(), &StatusCode::Ok);
+        assert_eq!(body, b"hello");
+    }
+    thread.join().unwrap();
+    client.close();
+}
+
+#[test]
+fn client_fetch_limit() {
+    use hyper::client::fetch::Fetch;
+
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = hyper::Client::<Fetch>::new().unwrap();
+    let future = client.fetch(format!("http://{}/", addr).parse().unwrap(), Fetch::new(Method::Get).limit(4));
+
+    let mut sock = server.accept().unwrap().0;
+    sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut buf = [0; 4096];
+    sock.read(&mut buf).unwrap();
+    sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello").unwrap();
+
+    match future.wait() {
+        Err(hyper::Error::TooLarge) => (),
+        other => panic!("expected too large, actual: {:?}", other)
+    }
+    client.close();
+}
+
+#[test]
+fn client_fetch_cache() {
+    use hyper::client::Cache;
+    use hyper::client::fetch::Fetch;
+
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = hyper::Client::<Fetch>::new().unwrap();
+    let cache = Cache::memory();
+    let fresh = format!("http://{}/fresh", addr);
+    let validated = format!("http://{}/validated", addr);
+
+    let thread = ::std::thread::spawn(move || {
+        let mut buf = [0; 4096];
+        let mut sock = server.accept().unwrap().0;
+        sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let n = sock.read(&mut buf).unwrap();
+        assert!(s(&buf[..n]).starts_with("GET /fresh "));
+        sock.write_all(b"HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nContent-Length: 5\r\nConnection: close\r\n\r\nfresh").unwrap();
+
+        let mut sock = server.accept().unwrap().0;
+        sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let n = sock.read(&mut buf).unwrap();
+        assert!(s(&buf[..n]).starts_with("GET /validated "));
+        sock.write_all(b"HTTP/1.1 200 OK\r\nCache-Control: no-cache\r\nETag: \"v1\"\r\nContent-Length: 5\r\nConnection: close\r\n\r\nvalid").unwrap();
+
+        let mut sock = server.accept().unwrap().0;
+        sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let n = sock.read(&mut buf).unwrap();
+        assert!(s(&buf[..n]).contains("If-None-Match: \"v1\"\r\n"));
+        sock.write_all(b"HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n").unwrap();
+    });
+
+    for _ in 0..2 {
+        let future = client.fetch(fresh.parse().unwrap(), Fetch::new(Method::Get).cache(&cache));
+        let (res, body) = future.wait().unwrap();
+        assert_eq!(res.status(), &StatusCode::Ok);
+        assert_eq!(body, b"fresh");
+    }
+    for _ in 0..2 {
+        let future = client.fetch(validated.parse().unwrap(), Fetch::new(Method::Get).cache(&cache));
+        let (res, body) = future.wait().unwrap();
+        assert_eq!(res.status(), &StatusCode::Ok);
+        assert_eq!(body, b"valid");
+    }
+    thread.join().unwrap();
+    client.close();
+}
+
+#[test]
+fn client_fetch_digest_auth() {
+    use hyper::client::Credentials;
+    use hyper::client::fetch::Fetch;
+    use hyper::header::{Authorization, Digest, Header};
+
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let client = hyper::Client::<Fetch>::new().unwrap();
+    let credentials = Credentials::new("Mufasa", "Circle of Life");
+
+    let thread = ::std::thread::spawn(move || {
+        let mut buf = [0; 4096];
+        let mut sock = server.accept().unwrap().0;
+        sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let n = sock.read(&mut buf).unwrap();
+        assert!(!s(&buf[..n]).contains("Authorization:"));
+        sock.write_all(b"HTTP/1.1 401 Unauthorized\r\n\
+            WWW-Authenticate: Basic realm=\"r\", Digest realm=\"r\", qop=\"auth\", nonce=\"abc\"\r\n\
+            Content-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+
+        let mut sock = server.accept().unwrap().0;
+        sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let n = sock.read(&mut buf).unwrap();
+        let req = s(&buf[..n]).to_owned();
+        let line = req.lines().find(|line| line.starts_with("Authorization: ")).unwrap();
+        let raw = line["Authorization: ".len()..].as_bytes().to_vec();
+        let auth: Authorization<Digest> = Header::parse_header(&raw.into()).unwrap();
+        assert_eq!(auth.uri, "/secret");
+        assert_eq!(auth.nc, Some(1));
+        assert!(auth.verify("Circle of Life", &Method::Get));
+        sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\nConnection: close\r\n\r\nsecret").unwrap();
+    });
+
+    let url = format!("http://{}/secret", addr).parse().unwrap();
+    let future = client.fetch(url, Fetch::new(Method::Get).credentials(&credentials));
+    let (res, body) = future.wait().unwrap();
+    assert_eq!(res.status(), &StatusCode::Ok);
+    assert_eq!(body, b"secret");
+    thread.join().unwrap();
+    client.close();
+}
+
+#[test]
+fn client_download_resume() {
+    use hyper::client::blocking;
+
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let thread = ::std::thread::spawn(move || {
+        let mut buf = [0; 4096];
+        let mut sock = server.accept().unwrap().0;
+        sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        sock.read(&mut buf).unwrap();
+        // the connection closes after half of the body
+        sock.write_all(b"HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 10\r\n\r\nhello").unwrap();
+        drop(sock);
+
+        let mut sock = server.accept().unwrap().0;
+        sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let n = sock.read(&mut buf).unwrap();
+        let req = s(&buf[..n]);
+        assert!(req.contains("Range: bytes=5-\r\n"), "{}", req);
+        assert!(req.contains("If-Range: \"v1\"\r\n"), "{}", req);
+        sock.write_all(b"HTTP/1.1 206 Partial Content\r\nETag: \"v1\"\r\nContent-Range: bytes 5-9/10\r\nContent-Length: 5\r\nConnection: close\r\n\r\nworld").unwrap();
+    });
+
+    let client = blocking::Client::new().unwrap();
+    let mut sink = io::Cursor::new(Vec::new());
+    let len = client.download(format!("http://{}/", addr).parse().unwrap())
+        .timeout(Duration::from_secs(5))
+        .to(&mut sink)
+        .unwrap();
+    assert_eq!(len, 10);
+    assert_eq!(sink.into_inner(), b"helloworld");
+    thread.join().unwrap();
+}
@@ -175,6 +175,11 @@ fn serve_n(n: u32) -> Serve {
 }
 
 fn serve_n_with_timeout(n: u32, dur: Option<Duration>) -> Serve {
+    serve_wrapped(n, dur, |handler| handler)
+}
+
+fn serve_wrapped<W, H>(n: u32, dur: Option<Duration>, wrap: W) -> Serve
+where W: Fn(TestHandler) -> H + Send + 'static, H: Handler<HttpStream> {
     use std::thread;
 
     let (msg_tx, msg_rx) = mpsc::channel();
@@ -188,12 +193,12 @@ fn serve_n_with_timeout(n: u32, dur: Option<Duration>) -> Serve {
             while let Ok(reply) = reply_rx.try_recv() {
                 replies.push(reply);
             }
-            TestHandler {
+            wrap(TestHandler {
                 tx: msg_tx.clone(),
                 timeout: dur,
                 reply: replies,
                 peeked: None,
-            }
+            })
         }).unwrap();
 
 
@@ -479,3 +484,561 @@ fn server_get_with_body_three_listeners() {
         assert_eq!(server.body(), comparison);
     }
 }
+
+fn read_response(req: &mut TcpStream) -> (String, Vec<u8>) {
+    let mut res = Vec::new();
+    req.read_to_end(&mut res).unwrap();
+    let n = res.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    let body = res.split_off(n);
+    (String::from_utf8(res).unwrap(), body)
+}
+
+#[test]
+fn server_compress_response() {
+    let body = vec![b'a'; 4096];
+    let server = serve_wrapped(1, None, |handler| hyper::server::Compress::new(handler));
+    server.reply()
+        .status(hyper::Ok)
+        .header(hyper::header::ContentLength(body.len() as u64))
+        .body(&body);
+    let mut req = TcpStream::connect(server.addr()).unwrap();
+    req.write_all(b"\
+        GET / HTTP/1.1\r\n\
+        Host: example.domain\r\n\
+        Accept-Encoding: deflate;q=0.5, gzip\r\n\
+        Connection: close\r\n\
+        \r\n\
+    ").unwrap();
+    let (head, chunked) = read_response(&mut req);
+    assert!(head.contains("Content-Encoding: gzip\r\n"), "{}", head);
+    assert!(head.contains("Transfer-Encoding: chunked\r\n"), "{}", head);
+    assert!(head.contains("Vary: Accept-Encoding\r\n"), "{}", head);
+    assert!(!head.contains("Content-Length"), "{}", head);
+
+    // the first chunk starts with the gzip magic bytes
+    let n = chunked.windows(2).position(|w| w == b"\r\n").unwrap() + 2;
+    assert_eq!(&chunked[n..n + 2], &[0x1f, 0x8b]);
+    assert!(chunked.len() < body.len());
+    assert!(chunked.ends_with(b"0\r\n\r\n"));
+}
+
+#[test]
+fn server_compress_skips_small_response() {
+    let server = serve_wrapped(1, None, |handler| hyper::server::Compress::new(handler));
+    server.reply()
+        .status(hyper::Ok)
+        .header(hyper::header::ContentLength(11))
+        .body(b"foo bar baz");
+    let mut req = TcpStream::connect(server.addr()).unwrap();
+    req.write_all(b"\
+        GET / HTTP/1.1\r\n\
+        Host: example.domain\r\n\
+        Accept-Encoding: gzip\r\n\
+        Connection: close\r\n\
+        \r\n\
+    ").unwrap();
+    let (head, body) = read_response(&mut req);
+    assert!(!head.contains("Content-Encoding"), "{}", head);
+    assert_eq!(body, b"foo bar baz");
+}
+
+fn seen(rx: &mpsc::Receiver<&'static str>) -> Vec<&'static str> {
+    let mut seen = Vec::new();
+    while let Ok(event) = rx.try_recv() {
+        seen.push(event);
+    }
+    seen
+}
+
+#[derive(Clone)]
+struct Guard {
+    seen: mpsc::Sender<&'static str>,
+}
+
+impl hyper::server::Middleware<HttpStream> for Guard {
+    fn on_request(&mut self, req: &Request<HttpStream>) -> Option<hyper::server::Reply> {
+        self.seen.send("request").unwrap();
+        if req.path() == Some("/blocked") {
+            Some(hyper::server::Reply::new(hyper::status::StatusCode::Forbidden).body("no"))
+        } else {
+            None
+        }
+    }
+
+    fn on_response(&mut self, res: &mut Response) {
+        self.seen.send("response").unwrap();
+        res.headers_mut().set_raw("X-Guard", "checked");
+    }
+
+    fn on_response_writable(&mut self, encoder: &mut Encoder<HttpStream>,
+                            handler: &mut FnMut(&mut Encoder<HttpStream>) -> Next) -> Next {
+        self.seen.send("writable").unwrap();
+        handler(encoder)
+    }
+}
+
+#[test]
+fn server_middleware_chain() {
+    let (tx, rx) = mpsc::channel();
+    let server = serve_wrapped(1, None, move |handler| {
+        hyper::server::Chain::new(Guard { seen: tx.clone() }, handler)
+    });
+    server.reply()
+        .status(hyper::Ok)
+        .header(hyper::header::ContentLength(5))
+        .body("hello");
+    let mut req = TcpStream::connect(server.addr()).unwrap();
+    req.write_all(b"\
+        GET /open HTTP/1.1\r\n\
+        Host: example.domain\r\n\
+        Connection: close\r\n\
+        \r\n\
+    ").unwrap();
+    let (head, body) = read_response(&mut req);
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    assert!(head.contains("X-Guard: checked\r\n"), "{}", head);
+    assert_eq!(body, b"hello");
+    assert_eq!(seen(&rx), vec!["request", "response", "writable"]);
+}
+
+/// Reports when the `Handler` it wraps is removed.
+struct Removed<H>(H, mpsc::Sender<&'static str>);
+
+impl<H: Handler<HttpStream>> Handler<HttpStream> for Removed<H> {
+    fn on_request(&mut self, req: Request<HttpStream>) -> Next {
+        self.0.on_request(req)
+    }
+
+    fn on_request_readable(&mut self, decoder: &mut Decoder<HttpStream>) -> Next {
+        self.0.on_request_readable(decoder)
+    }
+
+    fn on_response(&mut self, res: &mut Response) -> Next {
+        self.0.on_response(res)
+    }
+
+    fn on_response_writable(&mut self, encoder: &mut Encoder<HttpStream>) -> Next {
+        self.0.on_response_writable(encoder)
+    }
+
+    fn on_remove(self, transport: HttpStream) {
+        self.1.send("handler removed").unwrap();
+        self.0.on_remove(transport);
+    }
+}
+
+#[test]
+fn server_middleware_short_circuit() {
+    let (tx, rx) = mpsc::channel();
+    let server = serve_wrapped(1, None, move |handler| {
+        hyper::server::Chain::new(Guard { seen: tx.clone() }, Removed(handler, tx.clone()))
+    });
+    let mut req = TcpStream::connect(server.addr()).unwrap();
+    req.write_all(b"\
+        POST /blocked HTTP/1.1\r\n\
+        Host: example.domain\r\n\
+        Content-Length: 4\r\n\
+        Connection: close\r\n\
+        \r\n\
+        body\
+    ").unwrap();
+    let (head, body) = read_response(&mut req);
+    assert!(head.starts_with("HTTP/1.1 403 Forbidden\r\n"), "{}", head);
+    assert!(head.contains("Content-Length: 2\r\n"), "{}", head);
+    assert!(head.contains("X-Guard: checked\r\n"), "{}", head);
+    assert_eq!(body, b"no");
+    // the handler never read the request body
+    assert_eq!(server.body(), b"");
+    // the handler is still removed, to release what it holds
+    assert_eq!(seen(&rx), vec!["request", "response", "handler removed"]);
+}
+
+/// Answers with a fixed body, left out when answering `HEAD`.
+struct Echo {
+    body: String,
+    head: bool,
+}
+
+impl Echo {
+    fn new(body: String) -> Echo {
+        Echo {
+            body: body,
+            head: false,
+        }
+    }
+}
+
+impl Handler<HttpStream> for Echo {
+    fn on_request(&mut self, req: Request<HttpStream>) -> Next {
+        self.head = *req.method() == hyper::Head;
+        Next::write()
+    }
+
+    fn on_request_readable(&mut self, _decoder: &mut Decoder<HttpStream>) -> Next {
+        Next::write()
+    }
+
+    fn on_response(&mut self, res: &mut Response) -> Next {
+        res.headers_mut().set(hyper::header::ContentLength(self.body.len() as u64));
+        if self.head {
+            Next::end()
+        } else {
+            Next::write()
+        }
+    }
+
+    fn on_response_writable(&mut self, encoder: &mut Encoder<HttpStream>) -> Next {
+        encoder.write(self.body.as_bytes()).unwrap();
+        Next::end()
+    }
+}
+
+fn request(addr: &SocketAddr, head: &str) -> (String, Vec<u8>) {
+    let mut req = TcpStream::connect(addr).unwrap();
+    req.write_all(head.as_bytes()).unwrap();
+    read_response(&mut req)
+}
+
+#[test]
+fn server_router() {
+    use hyper::server::router::{Params, Router};
+
+    let router = Router::new()
+        .get("/users/:id", |_, params: Params| Echo::new(format!("user {}", params.get("id").unwrap())))
+        .post("/users", |_, _| Echo::new("created".to_owned()))
+        .get("/files/*path", |_, params: Params| Echo::new(params.get("path").unwrap().to_owned()));
+    let addr = "127.0.0.1:0".parse().unwrap();
+    let (listening, server) = Server::new(vec![HttpListener::bind(&addr).unwrap()])
+        .handle(router).unwrap();
+    ::std::thread::spawn(move || server.run());
+    let addr = listening.addrs()[0];
+
+    let (head, body) = request(&addr, "GET /users/7 HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    assert_eq!(body, b"user 7");
+
+    let (_, body) = request(&addr, "GET /files/a/b%20c HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n");
+    assert_eq!(body, b"a/b c");
+
+    let (head, _) = request(&addr, "DELETE /users/7 HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"), "{}", head);
+    assert!(head.contains("Allow: GET, HEAD, OPTIONS\r\n"), "{}", head);
+
+    // HEAD falls back to the GET route
+    let (head, body) = request(&addr, "HEAD /users/7 HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    assert!(head.contains("Content-Length: 6\r\n"), "{}", head);
+    assert!(body.is_empty(), "{:?}", body);
+
+    let (head, _) = request(&addr, "HEAD /users HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"), "{}", head);
+    assert!(head.contains("Allow: POST, OPTIONS\r\n"), "{}", head);
+
+    let (head, _) = request(&addr, "OPTIONS /users HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    assert!(head.contains("Allow: POST, OPTIONS\r\n"), "{}", head);
+
+    let (head, _) = request(&addr, "GET /nowhere HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", head);
+
+    listening.close();
+}
+
+#[test]
+fn server_static_files() {
+    use std::fs::{self, File};
+    use hyper::server::fs::Static;
+
+    let dir = ::std::env::temp_dir().join("hyper-server-static-files");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    File::create(dir.join("sub").join("hello.txt")).unwrap().write_all(b"Hello, static world!").unwrap();
+
+    let addr = "127.0.0.1:0".parse().unwrap();
+    let (listening, server) = Server::new(vec![HttpListener::bind(&addr).unwrap()])
+        .handle(Static::new(dir.clone())).unwrap();
+    ::std::thread::spawn(move || server.run());
+    let addr = listening.addrs()[0];
+
+    let (head, body) = request(&addr, "GET /sub/hello.txt HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    assert!(head.contains("Content-Type: text/plain; charset=utf-8\r\n"), "{}", head);
+    assert!(head.contains("Accept-Ranges: bytes\r\n"), "{}", head);
+    assert!(head.contains("Last-Modified: "), "{}", head);
+    assert_eq!(body, b"Hello, static world!");
+    let etag = head.lines().find(|line| line.starts_with("ETag: ")).unwrap()["ETag: ".len()..].to_owned();
+
+    let (head, body) = request(&addr, "GET /sub/hello.txt HTTP/1.1\r\nHost: a\r\nRange: bytes=7-12\r\nConnection: close\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 206 Partial Content\r\n"), "{}", head);
+    assert!(head.contains("Content-Range: bytes 7-12/20\r\n"), "{}", head);
+    assert_eq!(body, b"static");
+
+    let (head, body) = request(&addr, "GET /sub/hello.txt HTTP/1.1\r\nHost: a\r\nRange: bytes=0-4,2-5,-5\r\nConnection: close\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 206 Partial Content\r\n"), "{}", head);
+    assert!(head.contains("Content-Type: multipart/byteranges; boundary="), "{}", head);
+    let body = String::from_utf8(body).unwrap();
+    assert!(body.contains("Content-Range: bytes 0-5/20\r\n\r\nHello,\r\n"), "{}", body);
+    assert!(body.contains("Content-Range: bytes 15-19/20\r\n\r\norld!\r\n"), "{}", body);
+
+    let (head, _) = request(&addr, "GET /sub/hello.txt HTTP/1.1\r\nHost: a\r\nRange: bytes=50-\r\nConnection: close\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 416 Range Not Satisfiable\r\n"), "{}", head);
+    assert!(head.contains("Content-Range: bytes */20\r\n"), "{}", head);
+
+    let (head, body) = request(&addr, &format!("GET /sub/hello.txt HTTP/1.1\r\nHost: a\r\nIf-None-Match: {}\r\nConnection: close\r\n\r\n", etag));
+    assert!(head.starts_with("HTTP/1.1 304 Not Modified\r\n"), "{}", head);
+    assert!(body.is_empty());
+
+    let (head, _) = request(&addr, "GET /sub/../../etc/passwd HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", head);
+
+    let (head, _) = request(&addr, "POST /sub/hello.txt HTTP/1.1\r\nHost: a\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"), "{}", head);
+    assert!(head.contains("Allow: GET, HEAD\r\n"), "{}", head);
+
+    listening.close();
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn server_cors() {
+    use hyper::method::Method;
+    use hyper::server::{Layer, Router};
+    use hyper::server::cors::Cors;
+
+    let cors = Cors::new()
+        .allow_origin("https://a.example")
+        .allow_methods(vec![Method::Get, Method::Put])
+        .allow_header("X-Token")
+        .max_age(600);
+    let router = Router::new().get("/data", |_, _| Echo::new("data".to_owned()));
+    let addr = "127.0.0.1:0".parse().unwrap();
+    let (listening, server) = Server::new(vec![HttpListener::bind(&addr).unwrap()])
+        .handle(Layer::new(router, cors)).unwrap();
+    ::std::thread::spawn(move || server.run());
+    let addr = listening.addrs()[0];
+
+    let (head, _) = request(&addr, "\
+        OPTIONS /data HTTP/1.1\r\n\
+        Host: a\r\n\
+        Origin: https://a.example\r\n\
+        Access-Control-Request-Method: PUT\r\n\
+        Access-Control-Request-Headers: x-token\r\n\
+        Connection: close\r\n\
+        \r\n");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    assert!(head.contains("Access-Control-Allow-Origin: https://a.example\r\n"), "{}", head);
+    assert!(head.contains("Access-Control-Allow-Methods: GET, PUT\r\n"), "{}", head);
+    assert!(head.contains("Access-Control-Allow-Headers: x-token\r\n"), "{}", head);
+    assert!(head.contains("Access-Control-Max-Age: 600\r\n"), "{}", head);
+
+    let (head, _) = request(&addr, "\
+        OPTIONS /data HTTP/1.1\r\n\
+        Host: a\r\n\
+        Origin: https://b.example\r\n\
+        Access-Control-Request-Method: GET\r\n\
+        Connection: close\r\n\
+        \r\n");
+    assert!(head.starts_with("HTTP/1.1 403 Forbidden\r\n"), "{}", head);
+    assert!(!head.contains("Access-Control-Allow-Origin"), "{}", head);
+
+    let (head, body) = request(&addr, "\
+        GET /data HTTP/1.1\r\n\
+        Host: a\r\n\
+        Origin: https://a.example\r\n\
+        Connection: close\r\n\
+        \r\n");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    assert!(head.contains("Access-Control-Allow-Origin: https://a.example\r\n"), "{}", head);
+    assert!(head.contains("Vary: Origin\r\n"), "{}", head);
+    assert_eq!(body, b"data");
+
+    listening.close();
+}
+
+#[test]
+fn server_sse() {
+    use hyper::server::sse::{Event, EventStream};
+
+    let addr = "127.0.0.1:0".parse().unwrap();
+    let (listening, server) = Server::new(vec![HttpListener::bind(&addr).unwrap()])
+        .handle(|ctrl| EventStream::new(ctrl, |events, last_event_id: Option<String>| {
+            ::std::thread::spawn(move || {
+                let from = last_event_id.unwrap_or_else(|| "none".to_owned());
+                events.send(Event::new(format!("resumed from {}", from)).id("8")).unwrap();
+                events.send(Event::new("line one\nline two").event("multi")).unwrap();
+            });
+        }).retry(2000)).unwrap();
+    ::std::thread::spawn(move || server.run());
+    let addr = listening.addrs()[0];
+
+    let (head, body) = request(&addr, "\
+        GET /events HTTP/1.1\r\n\
+        Host: a\r\n\
+        Last-Event-ID: 7\r\n\
+        Connection: close\r\n\
+        \r\n");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    assert!(head.contains("Content-Type: text/event-stream\r\n"), "{}", head);
+    assert!(head.contains("Transfer-Encoding: chunked\r\n"), "{}", head);
+    let body = String::from_utf8(body).unwrap();
+    assert!(body.contains("retry: 2000\n\n"), "{}", body);
+    assert!(body.contains("id: 8\ndata: resumed from 7\n\n"), "{}", body);
+    assert!(body.contains("event: multi\ndata: line one\ndata: line two\n\n"), "{}", body);
+
+    listening.close();
+}
+
+#[test]
+fn server_access_log() {
+    use std::sync::Mutex;
+    use hyper::method::Method;
+    use hyper::server::LogEntry;
+    use hyper::version::HttpVersion;
+
+    let (log_tx, log_rx) = mpsc::channel();
+    let log_tx = Mutex::new(log_tx);
+    let (msg_tx, _msg_rx) = mpsc::channel();
+    let addr = "127.0.0.1:0".parse().unwrap();
+    let (listening, server) = Server::new(vec![HttpListener::bind(&addr).unwrap()])
+        .access_log(move |entry: &LogEntry| {
+            let _ = log_tx.lock().unwrap().send(entry.clone());
+        })
+        .handle(move |_| TestHandler {
+            tx: msg_tx.clone(),
+            reply: vec![Reply::Status(hyper::Ok), Reply::Body(b"hello".to_vec())],
+            peeked: None,
+            timeout: None,
+        }).unwrap();
+    ::std::thread::spawn(move || server.run());
+    let addr = listening.addrs()[0];
+
+    let (head, body) = request(&addr, "\
+        POST /log?x=1 HTTP/1.1\r\n\
+        Host: a\r\n\
+        User-Agent: test\r\n\
+        Content-Length: 11\r\n\
+        Connection: close\r\n\
+        \r\n\
+        hello world");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    assert!(head.contains("Transfer-Encoding: chunked\r\n"), "{}", head);
+    assert_eq!(body, b"5\r\nhello\r\n0\r\n\r\n");
+
+    let entry = log_rx.recv().unwrap();
+    assert_eq!(entry.method, Method::Post);
+    assert_eq!(entry.uri.to_string(), "/log?x=1");
+    assert_eq!(entry.version, HttpVersion::Http11);
+    assert_eq!(entry.status, Some(hyper::Ok));
+    // the body bytes, without the chunked framing
+    assert_eq!(entry.request_bytes, 11);
+    assert_eq!(entry.response_bytes, 5);
+    assert_eq!(entry.peer.map(|peer| peer.ip()), Some(addr.ip()));
+    assert_eq!(entry.user_agent, Some("test".to_owned()));
+    let line = entry.combined();
+    assert!(line.starts_with("127.0.0.1 - - ["), "{}", line);
+    assert!(line.ends_with("] \"POST /log?x=1 HTTP/1.1\" 200 5 \"-\" \"test\""), "{}", line);
+
+    listening.close();
+}
+
+#[test]
+fn server_max_body_size() {
+    struct Sink {
+        read: usize,
+        errors: mpsc::Sender<&'static str>,
+    }
+
+    impl Handler<HttpStream> for Sink {
+        fn on_request(&mut self, mut req: Request<HttpStream>) -> Next {
+            if req.path() == Some("/big") {
+                req.set_max_body_size(Some(1024));
+            }
+            Next::read()
+        }
+
+        fn on_request_readable(&mut self, decoder: &mut Decoder<HttpStream>) -> Next {
+            let mut buf = [0; 64];
+            loop {
+                match decoder.read(&mut buf) {
+                    Ok(0) => return Next::write(),
+                    Ok(n) => self.read += n,
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Next::read(),
+                    Err(_) => return Next::remove(),
+                }
+            }
+        }
+
+        fn on_response(&mut self, res: &mut Response) -> Next {
+            res.headers_mut().set(hyper::header::ContentLength(format!("read {}", self.read).len() as u64));
+            Next::write()
+        }
+
+        fn on_response_writable(&mut self, encoder: &mut Encoder<HttpStream>) -> Next {
+            encoder.write(format!("read {}", self.read).as_bytes()).unwrap();
+            Next::end()
+        }
+
+        fn on_error(&mut self, err: hyper::Error) -> Next {
+            let _ = self.errors.send(match err {
+                hyper::Error::TooLarge => "too large",
+                _ => "other"
+            });
+            Next::remove()
+        }
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let addr = "127.0.0.1:0".parse().unwrap();
+    let (listening, server) = Server::new(vec![HttpListener::bind(&addr).unwrap()])
+        .max_body_size(Some(8))
+        .handle(move |_| Sink { read: 0, errors: tx.clone() }).unwrap();
+    ::std::thread::spawn(move || server.run());
+    let addr = listening.addrs()[0];
+
+    // refused before the body is read
+    let (head, body) = request(&addr, "\
+        POST / HTTP/1.1\r\n\
+        Host: a\r\n\
+        Content-Length: 100\r\n\
+        \r\n");
+    assert!(head.starts_with("HTTP/1.1 413 Payload Too Large\r\n"), "{}", head);
+    assert!(head.contains("Connection: close\r\n"), "{}", head);
+    assert_eq!(body, b"");
+    assert_eq!(rx.recv().unwrap(), "too large");
+
+    // cut off once the chunks are over the limit
+    let (head, _) = request(&addr, "\
+        POST / HTTP/1.1\r\n\
+        Host: a\r\n\
+        Transfer-Encoding: chunked\r\n\
+        \r\n\
+        6\r\nabcdef\r\n\
+        6\r\nghijkl\r\n\
+        0\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 413 Payload Too Large\r\n"), "{}", head);
+    assert_eq!(rx.recv().unwrap(), "too large");
+
+    let (head, body) = request(&addr, "\
+        POST / HTTP/1.1\r\n\
+        Host: a\r\n\
+        Content-Length: 8\r\n\
+        Connection: close\r\n\
+        \r\n\
+        abcdefgh");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    assert_eq!(body, b"read 8");
+
+    // the handler raised the limit of this request
+    let (head, body) = request(&addr, "\
+        POST /big HTTP/1.1\r\n\
+        Host: a\r\n\
+        Content-Length: 12\r\n\
+        Connection: close\r\n\
+        \r\n\
+        abcdefghijkl");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    assert_eq!(body, b"read 12");
+    assert!(rx.try_recv().is_err());
+
+    listening.close();
+}